@@ -0,0 +1,59 @@
+use std::{
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Output of the command, if it succeeds
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string())
+}
+
+// Exposes the build information to the crate as environment variables
+fn build_info() {
+    let git_commit = command_output("git", &["rev-parse", "HEAD"]);
+    println!(
+        "cargo:rustc-env=GIT_COMMIT={}",
+        git_commit.as_deref().unwrap_or("unknown")
+    );
+    // commit is updated once the checked out commit changes
+    if let Some(git_dir) = command_output("git", &["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        if let Some(head_ref) = command_output("git", &["symbolic-ref", "HEAD"]) {
+            println!("cargo:rerun-if-changed={git_dir}/{head_ref}");
+        }
+    }
+
+    // SOURCE_DATE_EPOCH is respected, so the builds can be reproducible
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let build_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(|epoch| UNIX_EPOCH + Duration::from_secs(epoch))
+        .unwrap_or_else(SystemTime::now);
+    println!(
+        "cargo:rustc-env=BUILD_TIMESTAMP={}",
+        httpdate::fmt_http_date(build_time)
+    );
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"]);
+    println!(
+        "cargo:rustc-env=RUSTC_VERSION={}",
+        rustc_version.as_deref().unwrap_or("unknown")
+    );
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    build_info();
+    // fall back to vendored protoc, so building doesn't depend on a system wide installation
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_build::compile_protos("proto/admin.proto")?;
+    Ok(())
+}
@@ -0,0 +1,255 @@
+//! Programmatic construction of the network part of the bootstrap node, for the embedders running the event loop on their own.
+
+use anyhow::{Context, Result};
+use libp2p::{
+    identity::Keypair,
+    swarm::{dummy, NetworkBehaviour},
+    Multiaddr,
+};
+use std::{error::Error, fmt::Debug, sync::Arc, time::Duration};
+use tokio::time::{interval_at, Instant};
+
+use crate::{
+    node,
+    p2p::{
+        self, client::Client, BoxedTransport, EventLoop, Extension, NetworkShutdown, TransportHook,
+    },
+    shutdown,
+    telemetry::{self, Metrics, TransferredBytes},
+    types::{
+        AutonatConfig, BootstrapConfig, ClientConfig, IdentifyConfig, KademliaConfig, LibP2PConfig,
+        RandomWalkConfig, RuntimeConfig, WssConfig,
+    },
+};
+
+/// Network client and the event loop of the built node, along with the shutdown controller
+/// stopping the metrics task, and the event loop once run with [shutdown::Controller::triggered]
+pub struct BootstrapNode<E: NetworkBehaviour = dummy::Behaviour> {
+    pub client: Client,
+    pub event_loop: EventLoop<E>,
+    pub shutdown: shutdown::Controller,
+    /// Stops the event loop on its own, awaiting its completion
+    pub network_shutdown: NetworkShutdown,
+}
+
+/// Builds the bootstrap node from the given options, using the [RuntimeConfig] defaults for the rest
+pub struct BootstrapNodeBuilder<E: NetworkBehaviour = dummy::Behaviour> {
+    keypair: Option<Keypair>,
+    listen_addrs: Vec<Multiaddr>,
+    cfg: LibP2PConfig,
+    is_ws_transport: bool,
+    is_quic_transport: bool,
+    transport_hook: Option<TransportHook>,
+    metrics: Option<Arc<dyn Metrics>>,
+    metrics_interval: Duration,
+    extension: Extension<E>,
+}
+
+impl Default for BootstrapNodeBuilder {
+    fn default() -> Self {
+        let cfg = RuntimeConfig::default();
+        BootstrapNodeBuilder {
+            keypair: None,
+            listen_addrs: vec![],
+            cfg: (&cfg).into(),
+            is_ws_transport: false,
+            is_quic_transport: false,
+            transport_hook: None,
+            metrics: None,
+            metrics_interval: Duration::from_secs(cfg.metrics_network_dump_interval),
+            extension: Default::default(),
+        }
+    }
+}
+
+impl BootstrapNodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<E: NetworkBehaviour> BootstrapNodeBuilder<E>
+where
+    E::ToSwarm: Debug,
+{
+    /// Keypair of the node, a new one is generated if not set
+    pub fn keypair(mut self, keypair: Keypair) -> Self {
+        self.keypair = Some(keypair);
+        self
+    }
+
+    /// Addresses the node listens on once the event loop is run
+    pub fn listen_addrs(mut self, listen_addrs: impl IntoIterator<Item = Multiaddr>) -> Self {
+        self.listen_addrs = listen_addrs.into_iter().collect();
+        self
+    }
+
+    /// Kademlia options, with the protocol name derived from the genesis hash by [KademliaConfig::from]
+    pub fn kademlia(mut self, kademlia: KademliaConfig) -> Self {
+        self.cfg.kademlia = kademlia;
+        self
+    }
+
+    pub fn identify(mut self, identify: IdentifyConfig) -> Self {
+        self.cfg.identify = identify;
+        self
+    }
+
+    pub fn autonat(mut self, autonat: AutonatConfig) -> Self {
+        self.cfg.autonat = autonat;
+        self
+    }
+
+    pub fn bootstrap(mut self, bootstrap: BootstrapConfig) -> Self {
+        self.cfg.bootstrap = bootstrap;
+        self
+    }
+
+    /// Periodic closest peers lookups of random keys, disabled if the interval is zero
+    pub fn random_walk(mut self, random_walk: RandomWalkConfig) -> Self {
+        self.cfg.random_walk = random_walk;
+        self
+    }
+
+    /// Deadlines of the network client commands
+    pub fn client(mut self, client: ClientConfig) -> Self {
+        self.cfg.client = client;
+        self
+    }
+
+    /// Maximum time for the event loop to finish the pending queries on shutdown, once the listeners are closed
+    pub fn shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.cfg.shutdown_grace_period = grace_period;
+        self
+    }
+
+    /// Prologue of the Noise handshakes, which the peers have to use as well, e.g. [RuntimeConfig::noise_prologue]
+    pub fn noise_prologue(mut self, noise_prologue: Vec<u8>) -> Self {
+        self.cfg.noise_prologue = noise_prologue;
+        self
+    }
+
+    /// Enables the WebSocket transport instead of the plain TCP one
+    pub fn websocket(mut self, is_enabled: bool) -> Self {
+        self.is_ws_transport = is_enabled;
+        self
+    }
+
+    /// Enables the secure WebSocket listener next to the other transports, listening on the `/wss` addresses
+    pub fn wss(mut self, wss: WssConfig) -> Self {
+        self.cfg.wss = Some(wss);
+        self
+    }
+
+    /// Enables the QUIC transport next to the TCP one
+    pub fn quic(mut self, is_enabled: bool) -> Self {
+        self.is_quic_transport = is_enabled;
+        self
+    }
+
+    /// Transport the swarm is built with, given the node keypair and the default transport,
+    /// e.g. the memory transport for the tests, or the default one wrapped for instrumentation
+    pub fn transport<F>(mut self, transport_hook: F) -> Self
+    where
+        F: Fn(&Keypair, BoxedTransport) -> Result<BoxedTransport, Box<dyn Error + Send + Sync>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.transport_hook = Some(Arc::new(transport_hook));
+        self
+    }
+
+    /// Metrics backend, the event loop records to as the events happen,
+    /// and the network metrics are recorded to on each interval until the shutdown
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>, interval: Duration) -> Self {
+        self.metrics = Some(metrics);
+        self.metrics_interval = interval;
+        self
+    }
+
+    /// Behaviour composed into the swarm next to the built-in ones, built from the node keypair,
+    /// with its events mapped to the data of the [p2p::client::NetworkEvent::Extension] events published to the subscribers
+    pub fn extension<X, B, M>(self, behaviour: B, events: M) -> BootstrapNodeBuilder<X>
+    where
+        X: NetworkBehaviour,
+        B: Fn(&Keypair) -> X + Send + Sync + 'static,
+        M: Fn(X::ToSwarm) -> Option<serde_json::Value> + Send + Sync + 'static,
+    {
+        BootstrapNodeBuilder {
+            keypair: self.keypair,
+            listen_addrs: self.listen_addrs,
+            cfg: self.cfg,
+            is_ws_transport: self.is_ws_transport,
+            is_quic_transport: self.is_quic_transport,
+            transport_hook: self.transport_hook,
+            metrics: self.metrics,
+            metrics_interval: self.metrics_interval,
+            extension: Extension {
+                behaviour: Arc::new(behaviour),
+                events: Arc::new(events),
+            },
+        }
+    }
+
+    /// Builds the event loop listening on the configured addresses, and starts the metrics task if the metrics are set
+    pub async fn build(self) -> Result<BootstrapNode<E>> {
+        let keypair = self.keypair.unwrap_or_else(Keypair::generate_ed25519);
+        let (client, mut network, network_shutdown) = p2p::init(
+            self.cfg,
+            keypair,
+            self.is_ws_transport,
+            self.is_quic_transport,
+            None,
+            self.metrics
+                .clone()
+                .unwrap_or_else(|| Arc::new(telemetry::noop::Metrics)),
+        );
+        if let Some(transport_hook) = self.transport_hook {
+            network = network.with_transport_hook(transport_hook);
+        }
+        let mut event_loop = network
+            .with_extension(self.extension)
+            .event_loop()
+            .await
+            .context("Failed to initialize P2P Network Service.")?;
+        for addr in self.listen_addrs {
+            event_loop
+                .listen_on(addr.clone())
+                .context(format!("Unable to listen on {addr}"))?;
+        }
+
+        let shutdown = shutdown::Controller::new();
+        if let Some(metrics) = self.metrics {
+            let client = client.clone();
+            let stopped = shutdown.triggered();
+            let period = self.metrics_interval;
+            shutdown.spawn("Metrics", async move {
+                tokio::pin!(stopped);
+                let node_started = Instant::now();
+                let mut interval = interval_at(node_started + period, period);
+                let mut transferred_bytes = TransferredBytes::default();
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = &mut stopped => break,
+                    }
+                    node::record_network_metrics(
+                        &client,
+                        metrics.as_ref(),
+                        &mut transferred_bytes,
+                        node_started,
+                    )
+                    .await;
+                }
+            });
+        }
+
+        Ok(BootstrapNode {
+            client,
+            event_loop,
+            shutdown,
+            network_shutdown,
+        })
+    }
+}
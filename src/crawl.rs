@@ -0,0 +1,152 @@
+use anyhow::Result;
+use libp2p::{
+    futures::{future::join_all, StreamExt},
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::p2p::client::Client;
+
+// number of random lookups issued to walk the DHT during a single crawl
+const CRAWL_LOOKUPS: usize = 16;
+const UNKNOWN_AGENT_VERSION: &str = "unknown";
+const CRAWL_BATCH_SIZE: usize = 100;
+
+/// Summary of the most recently completed DHT crawl
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlSummary {
+    /// Crawl start time, as UNIX timestamp in seconds
+    pub started_at: u64,
+    pub duration_ms: u128,
+    /// Number of distinct peers discovered, both by random lookups and from the routing table
+    pub peer_count: usize,
+    pub routing_table_peers: usize,
+    pub reachability: Reachability,
+    /// Number of routing table peers per reported agent version
+    pub agent_versions: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reachability {
+    /// Routing table peers with an active connection
+    pub connected: usize,
+    /// Routing table peers without an active connection
+    pub disconnected: usize,
+    /// Peers with at least one known address
+    pub with_addresses: usize,
+}
+
+pub struct Crawler {
+    client: Client,
+    is_running: AtomicBool,
+    latest: RwLock<Option<CrawlSummary>>,
+}
+
+impl Crawler {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            is_running: AtomicBool::new(false),
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Starts a crawl in the background, returning `false` if one is already running
+    pub fn start(self: &Arc<Self>) -> bool {
+        if self.is_running.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let crawler = self.clone();
+        tokio::spawn(async move {
+            match crawler.crawl().await {
+                Ok(summary) => {
+                    info!(
+                        "DHT crawl finished. Peers found: {}. Duration: {}ms.",
+                        summary.peer_count, summary.duration_ms
+                    );
+                    *crawler.latest.write().await = Some(summary);
+                }
+                Err(err) => warn!("DHT crawl failed: {err:#}"),
+            }
+            crawler.is_running.store(false, Ordering::SeqCst);
+        });
+        true
+    }
+
+    pub async fn latest(&self) -> Option<CrawlSummary> {
+        self.latest.read().await.clone()
+    }
+
+    /// Restores the summary of the crawl finished before the restart, unless a newer one is finished already
+    pub async fn restore(&self, summary: CrawlSummary) {
+        self.latest.write().await.get_or_insert(summary);
+    }
+
+    async fn crawl(&self) -> Result<CrawlSummary> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let start = Instant::now();
+        debug!("Starting DHT crawl with {CRAWL_LOOKUPS} random lookups.");
+
+        let lookups = (0..CRAWL_LOOKUPS).map(|_| self.client.get_closest_peers(PeerId::random()));
+        let mut peers: HashSet<String> = HashSet::new();
+        for result in join_all(lookups).await {
+            match result {
+                Ok(found) => peers.extend(found.iter().map(PeerId::to_string)),
+                Err(err) => debug!("Crawl lookup failed: {err:#}"),
+            }
+        }
+
+        // routing table is walked in batches, so the event loop isn't stalled by the large tables
+        let batches = self.client.stream_dht_entries(CRAWL_BATCH_SIZE);
+        tokio::pin!(batches);
+        let mut routing_table_peers = 0;
+        let mut agent_versions = HashMap::new();
+        let mut reachability = Reachability {
+            connected: 0,
+            disconnected: 0,
+            with_addresses: 0,
+        };
+        while let Some(batch) = batches.next().await {
+            let batch = batch?;
+            routing_table_peers += batch.len();
+            for entry in batch {
+                if entry.is_connected {
+                    reachability.connected += 1;
+                } else {
+                    reachability.disconnected += 1;
+                }
+                if !entry.addresses.is_empty() {
+                    reachability.with_addresses += 1;
+                }
+                let agent_version = entry
+                    .agent_version
+                    .unwrap_or_else(|| UNKNOWN_AGENT_VERSION.to_string());
+                *agent_versions.entry(agent_version).or_default() += 1;
+                peers.insert(entry.peer_id);
+            }
+        }
+
+        Ok(CrawlSummary {
+            started_at,
+            duration_ms: start.elapsed().as_millis(),
+            peer_count: peers.len(),
+            routing_table_peers,
+            reachability,
+            agent_versions,
+        })
+    }
+}
@@ -0,0 +1,93 @@
+//! Resolution of the `/dnsaddr` bootstrap addresses, so the fleet of the bootstrap nodes is changed through DNS,
+//! without pushing the config to each of them.
+//!
+//! Addresses are listed in the `dnsaddr=<multiaddr>` TXT records of the `_dnsaddr.<domain>` name,
+//! and may point to the other `/dnsaddr` names, resolved up to [MAX_DEPTH] levels deep.
+
+use anyhow::{Context, Result};
+use hickory_resolver::TokioAsyncResolver;
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+// nested names are resolved up to this depth, guarding against the loops
+const MAX_DEPTH: usize = 4;
+// resolved addresses are kept at least this long, even if the records have a shorter TTL
+const MIN_TTL: Duration = Duration::from_secs(60);
+const MAX_TTL: Duration = Duration::from_secs(3600);
+
+/// Addresses resolved from a `/dnsaddr` address, valid until the shortest TTL of the resolved records
+pub struct Resolution {
+    pub addresses: Vec<Multiaddr>,
+    pub valid_until: Instant,
+}
+
+pub fn is_dnsaddr(address: &Multiaddr) -> bool {
+    matches!(address.iter().next(), Some(Protocol::Dnsaddr(_)))
+}
+
+pub struct DnsaddrResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsaddrResolver {
+    /// Resolver using the system DNS configuration
+    pub fn new() -> Result<Self> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()
+            .context("Failed to read the system DNS configuration")?;
+        Ok(DnsaddrResolver { resolver })
+    }
+
+    /// Resolves the `/dnsaddr` address, keeping only the addresses of its peer, if it ends with `/p2p`
+    pub async fn resolve(&self, address: &Multiaddr) -> Result<Resolution> {
+        let peer_id = match address.iter().last() {
+            Some(Protocol::P2p(peer_id)) => Some(peer_id),
+            _ => None,
+        };
+        let now = Instant::now();
+        let mut valid_until = now + MAX_TTL;
+        let mut addresses = vec![];
+        let mut pending = vec![(address.clone(), 0)];
+        while let Some((address, depth)) = pending.pop() {
+            let Some(Protocol::Dnsaddr(domain)) = address.iter().next() else {
+                addresses.push(address);
+                continue;
+            };
+            if depth == MAX_DEPTH {
+                debug!("Skipping {address}, nested deeper than {MAX_DEPTH} names.");
+                continue;
+            }
+
+            let lookup = self
+                .resolver
+                .txt_lookup(format!("_dnsaddr.{domain}"))
+                .await
+                .context(format!("Failed to resolve {address}"))?;
+            valid_until = valid_until.min(lookup.valid_until());
+            for txt in lookup.iter() {
+                for data in txt.txt_data() {
+                    let Some(record) = std::str::from_utf8(data)
+                        .ok()
+                        .and_then(|data| data.strip_prefix("dnsaddr="))
+                    else {
+                        continue;
+                    };
+                    let Ok(resolved) = record.parse::<Multiaddr>() else {
+                        debug!("Skipping invalid address {record} of {address}.");
+                        continue;
+                    };
+                    let is_peer_address = peer_id.is_none_or(|peer_id| {
+                        resolved.iter().last() == Some(Protocol::P2p(peer_id))
+                    });
+                    if is_peer_address && !addresses.contains(&resolved) {
+                        pending.push((resolved, depth + 1));
+                    }
+                }
+            }
+        }
+        Ok(Resolution {
+            addresses,
+            valid_until: valid_until.clamp(now + MIN_TTL, now + MAX_TTL),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! Optional GeoIP lookups of the peer IP addresses, based on the MaxMind (e.g. GeoLite2) databases.
+
+use anyhow::{Context, Result};
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use std::net::IpAddr;
+use tracing::trace;
+
+use crate::types::GeoIpConfig;
+
+/// Country and autonomous system of the peer IP address, if found in the databases
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerLocation {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+}
+
+pub struct GeoIp {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+fn open(path: &Option<String>) -> Result<Option<Reader<Vec<u8>>>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let reader = Reader::open_readfile(path)
+        .with_context(|| format!("Unable to open GeoIP database {path}"))?;
+    Ok(Some(reader))
+}
+
+impl GeoIp {
+    /// Opens the configured databases. Returns `None` if none of them are configured.
+    pub fn open(cfg: &GeoIpConfig) -> Result<Option<Self>> {
+        let country = open(&cfg.country_db_path)?;
+        let asn = open(&cfg.asn_db_path)?;
+        if country.is_none() && asn.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(GeoIp { country, asn }))
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> PeerLocation {
+        let country = self.country.as_ref().and_then(|reader| {
+            let country: geoip2::Country = found(reader.lookup(ip), ip)?;
+            country
+                .country
+                .or(country.registered_country)
+                .and_then(|country| country.iso_code)
+                .map(str::to_string)
+        });
+        let asn = self.asn.as_ref().and_then(|reader| {
+            let asn: geoip2::Asn = found(reader.lookup(ip), ip)?;
+            asn.autonomous_system_number
+        });
+        PeerLocation { country, asn }
+    }
+}
+
+// Private and reserved addresses aren't in the databases, so lookup errors are expected
+fn found<T>(result: Result<T, MaxMindDBError>, ip: IpAddr) -> Option<T> {
+    result
+        .map_err(|err| trace!("GeoIP lookup of {ip} failed: {err}"))
+        .ok()
+}
@@ -0,0 +1,233 @@
+//! gRPC admin and introspection API, mirroring the HTTP admin surface.
+
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::{
+    wrappers::{BroadcastStream, ReceiverStream},
+    StreamExt,
+};
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{debug, error, info};
+
+use crate::{
+    crawl::Crawler,
+    p2p::client::{Client, DHTEntry, NetworkError, NetworkEvent},
+    server,
+    types::{Addr, HealthCheckConfig},
+};
+
+mod proto {
+    tonic::include_proto!("avail.bootstrap.v1");
+}
+
+use proto::{
+    admin_server::{Admin, AdminServer},
+    peer_event::Kind,
+    CrawlSummary, GetLatestCrawlRequest, GetLatestCrawlResponse, GetPeersRequest, GetPeersResponse,
+    HealthRequest, HealthResponse, Peer, PeerEvent, StartCrawlRequest, StartCrawlResponse,
+    SubscribePeerEventsRequest,
+};
+
+const PEER_EVENTS_BUFFER_SIZE: usize = 100;
+
+impl From<DHTEntry> for Peer {
+    fn from(entry: DHTEntry) -> Self {
+        Peer {
+            peer_id: entry.peer_id,
+            addresses: entry.addresses,
+            is_connected: entry.is_connected,
+            agent_version: entry.agent_version,
+            country: entry.country,
+            asn: entry.asn,
+        }
+    }
+}
+
+// Status of the failed network client command, same as the HTTP status of the admin surface
+fn network_error_status(err: &NetworkError) -> Status {
+    let message = err.to_string();
+    match err {
+        NetworkError::Timeout { .. } => Status::deadline_exceeded(message),
+        NetworkError::Overloaded { .. } => Status::resource_exhausted(message),
+        NetworkError::ListenFailure { .. } => Status::internal(message),
+        NetworkError::ChannelClosed { .. }
+        | NetworkError::NoKnownPeers
+        | NetworkError::DialFailure { .. }
+        | NetworkError::QueryFailed { .. } => Status::unavailable(message),
+    }
+}
+
+fn peer_event(kind: Kind, peer: Peer) -> PeerEvent {
+    PeerEvent {
+        kind: kind.into(),
+        peer: Some(peer),
+    }
+}
+
+// Maps the network event of the routing table or connection change to the peer event
+fn network_peer_event(event: NetworkEvent) -> Option<PeerEvent> {
+    let event = match event {
+        NetworkEvent::RoutingUpdated {
+            peer_id,
+            is_new_peer: true,
+            addresses,
+        } => peer_event(
+            Kind::Added,
+            Peer {
+                peer_id,
+                addresses,
+                ..Default::default()
+            },
+        ),
+        NetworkEvent::RoutingRemoved { peer_id } => peer_event(
+            Kind::Removed,
+            Peer {
+                peer_id,
+                ..Default::default()
+            },
+        ),
+        NetworkEvent::PeerConnected { peer_id, address } => peer_event(
+            Kind::Connected,
+            Peer {
+                peer_id,
+                addresses: vec![address],
+                is_connected: true,
+                ..Default::default()
+            },
+        ),
+        NetworkEvent::PeerDisconnected { peer_id } => peer_event(
+            Kind::Disconnected,
+            Peer {
+                peer_id,
+                ..Default::default()
+            },
+        ),
+        _ => return None,
+    };
+    Some(event)
+}
+
+struct AdminService {
+    client: Client,
+    crawler: Arc<Crawler>,
+    health_cfg: HealthCheckConfig,
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn health(
+        &self,
+        _request: Request<HealthRequest>,
+    ) -> Result<Response<HealthResponse>, Status> {
+        let reasons = server::check_health(&self.client, &self.health_cfg).await;
+        Ok(Response::new(HealthResponse {
+            is_healthy: reasons.is_empty(),
+            reasons,
+        }))
+    }
+
+    async fn get_peers(
+        &self,
+        _request: Request<GetPeersRequest>,
+    ) -> Result<Response<GetPeersResponse>, Status> {
+        let entries = self
+            .client
+            .get_dht_entries()
+            .await
+            .map_err(|err| network_error_status(&err))?;
+        Ok(Response::new(GetPeersResponse {
+            peers: entries.into_iter().map(Peer::from).collect(),
+        }))
+    }
+
+    async fn start_crawl(
+        &self,
+        _request: Request<StartCrawlRequest>,
+    ) -> Result<Response<StartCrawlResponse>, Status> {
+        Ok(Response::new(StartCrawlResponse {
+            started: self.crawler.start(),
+        }))
+    }
+
+    async fn get_latest_crawl(
+        &self,
+        _request: Request<GetLatestCrawlRequest>,
+    ) -> Result<Response<GetLatestCrawlResponse>, Status> {
+        let summary = self.crawler.latest().await.map(|summary| CrawlSummary {
+            started_at: summary.started_at,
+            duration_ms: summary.duration_ms as u64,
+            peer_count: summary.peer_count as u64,
+            routing_table_peers: summary.routing_table_peers as u64,
+            connected: summary.reachability.connected as u64,
+            disconnected: summary.reachability.disconnected as u64,
+            with_addresses: summary.reachability.with_addresses as u64,
+            agent_versions: summary
+                .agent_versions
+                .into_iter()
+                .map(|(version, count)| (version, count as u64))
+                .collect(),
+        });
+        Ok(Response::new(GetLatestCrawlResponse { summary }))
+    }
+
+    type SubscribePeerEventsStream = ReceiverStream<Result<PeerEvent, Status>>;
+
+    async fn subscribe_peer_events(
+        &self,
+        _request: Request<SubscribePeerEventsRequest>,
+    ) -> Result<Response<Self::SubscribePeerEventsStream>, Status> {
+        let (sender, receiver) = mpsc::channel(PEER_EVENTS_BUFFER_SIZE);
+        let mut events = BroadcastStream::new(self.client.subscribe());
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        debug!("Peer events subscriber is lagging behind: {err}");
+                        continue;
+                    }
+                };
+                let Some(event) = network_peer_event(event) else {
+                    continue;
+                };
+                if sender.send(Ok(event)).await.is_err() {
+                    debug!("Peer events subscriber disconnected.");
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+pub async fn run(
+    addr: Addr,
+    health_cfg: HealthCheckConfig,
+    client: Client,
+    crawler: Arc<Crawler>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let service = AdminService {
+        client,
+        crawler,
+        health_cfg,
+    };
+
+    let socket_addr: SocketAddr = match addr.try_into() {
+        Ok(socket_addr) => socket_addr,
+        Err(err) => {
+            error!("Unable to start gRPC server: {err:#}");
+            return;
+        }
+    };
+
+    info!("gRPC server running on {socket_addr}.");
+
+    if let Err(err) = Server::builder()
+        .add_service(AdminServer::new(service))
+        .serve_with_shutdown(socket_addr, shutdown)
+        .await
+    {
+        error!("gRPC server failed: {err}");
+    }
+}
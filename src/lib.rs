@@ -0,0 +1,26 @@
+//! Avail bootstrap node, to be embedded in other binaries and integration test harnesses.
+//!
+//! Node is started with [run], taking the same [RuntimeConfig](types::RuntimeConfig) as the `avail-light-bootstrap` binary,
+//! and controlled through the returned [Handle], which exposes the network [Client](p2p::client::Client) as well.
+//! Embedders running the network event loop on their own build it with [BootstrapNodeBuilder] instead.
+
+mod builder;
+mod crawl;
+mod dnsaddr;
+mod geoip;
+mod grpc;
+mod node;
+pub mod p2p;
+mod rpc;
+mod server;
+pub mod shutdown;
+pub mod simulate;
+pub mod state;
+#[cfg(unix)]
+mod systemd;
+pub mod telemetry;
+mod topology;
+pub mod types;
+
+pub use builder::{BootstrapNode, BootstrapNodeBuilder};
+pub use node::{run, Handle};
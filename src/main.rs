@@ -1,33 +1,208 @@
 #![doc = include_str!("../README.md")]
 
-use crate::{
-    telemetry::{MetricValue, Metrics},
-    types::{network_name, LibP2PConfig},
+use anyhow::{anyhow, Context, Result};
+use avail_light_bootstrap::{
+    p2p,
+    simulate::{self, SimulationConfig},
+    state,
+    telemetry::{self, FLUSH_TIMEOUT},
+    types::{self, network_name, LogFormat, Network, RuntimeConfig, SecretKey, CLIENT_ROLE},
+    Handle,
 };
-use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use libp2p::{multiaddr::Protocol, Multiaddr};
-use std::{net::Ipv4Addr, time::Duration};
-use tokio::time::{interval_at, Instant};
-use tracing::{debug, error, info, metadata::ParseLevelError, warn, Level, Subscriber};
+use opentelemetry_api::KeyValue;
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_sdk::logs::LoggerProvider;
+use std::{io::Write, path::Path, time::Duration};
+use tokio::{signal, time::timeout};
+use tracing::{error, info, metadata::ParseLevelError, warn, Level, Subscriber};
 use tracing_subscriber::{
-    fmt::format::{self},
-    EnvFilter, FmtSubscriber,
+    fmt::{self, format},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    reload, EnvFilter, Layer, Registry,
 };
-use types::RuntimeConfig;
 
-mod p2p;
-mod server;
-mod telemetry;
-mod types;
+// config fields applied on reload, changes of the other ones require restart
+const RELOADABLE_FIELDS: &[&str] = &[
+    "log_level",
+    "bootstrap_period",
+    "metrics_network_dump_interval",
+];
 
-const CLIENT_ROLE: &str = "bootnode";
+// version printed by `--version`, along with the build information
+const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ncommit: ",
+    env!("GIT_COMMIT"),
+    "\nbuilt: ",
+    env!("BUILD_TIMESTAMP"),
+    "\nrustc: ",
+    env!("RUSTC_VERSION"),
+);
 
-#[derive(Debug, Parser)]
-#[clap(name = "Avail Bootstrap Node")]
+#[derive(Debug, Clone, Parser)]
+#[clap(name = "Avail Bootstrap Node", version, long_version = LONG_VERSION)]
 struct CliOpts {
-    #[clap(long, short = 'c', help = "yaml configuration file")]
+    #[clap(subcommand)]
+    command: Option<CliCommand>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Known network, applying its genesis hash, bootstrap nodes and recommended settings"
+    )]
+    network: Option<Network>,
+    #[clap(long, short = 'c', help = "TOML or YAML configuration file")]
     config: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Format of the configuration file, detected by the file extension if not set"
+    )]
+    format: Option<ConfigFormat>,
+    #[clap(
+        long,
+        alias = "port",
+        help = "P2P service TCP port, overrides `tcp_port` of the config file"
+    )]
+    tcp_port: Option<u16>,
+    #[clap(
+        long,
+        help = "P2P service QUIC port, overrides `quic_port` of the config file"
+    )]
+    quic_port: Option<u16>,
+    #[clap(
+        long,
+        help = "HTTP server port, overrides `http_server_port` of the config file"
+    )]
+    http_port: Option<u16>,
+    #[clap(
+        long,
+        help = "Seed used to generate keypair, overrides `secret_key` of the config file"
+    )]
+    seed: Option<String>,
+    #[clap(
+        long,
+        help = "OpenTelemetry Collector endpoint, overrides `ot_collector_endpoint` of the config file"
+    )]
+    ot_endpoint: Option<String>,
+    #[clap(long, help = "Log level, overrides `log_level` of the config file")]
+    log_level: Option<String>,
+    #[clap(
+        long,
+        help = "Genesis hash of the network, overrides `genesis_hash` of the config file"
+    )]
+    genesis_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    // Files without a known extension are expected to be TOML
+    fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum CliCommand {
+    /// Writes the default configuration, with all the values commented, and exits
+    GenerateConfig {
+        #[clap(
+            help = "Path of the configuration file to be created, printed to stdout if not set"
+        )]
+        path: Option<String>,
+    },
+    /// Prints the peer ID derived from the configured secret key, and exits
+    PeerId {
+        #[clap(
+            long,
+            help = "Seed used to generate keypair, instead of the configured secret key"
+        )]
+        seed: Option<String>,
+        #[clap(
+            long,
+            help = "Hex encoded ed25519 private key, instead of the configured secret key"
+        )]
+        key: Option<String>,
+    },
+    /// Generates a new secret key, writes it to the identity file readable by the owner only, and exits
+    GenerateKey {
+        #[clap(
+            default_value = "identity.toml",
+            help = "Path of the identity file to be created"
+        )]
+        path: String,
+    },
+    /// Simulates light clients dialing the bootstrap node, running identify and random Kademlia lookups,
+    /// and prints their throughput and latencies once done
+    SimulateClients {
+        #[clap(help = "Multiaddress of the bootstrap node, e.g. /ip4/127.0.0.1/tcp/39000")]
+        target: Multiaddr,
+        #[clap(
+            long,
+            short = 'n',
+            default_value_t = 100,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Number of the simulated clients"
+        )]
+        clients: u64,
+        #[clap(
+            long,
+            default_value_t = 60,
+            help = "Duration of the simulation, in seconds"
+        )]
+        duration: u64,
+        #[clap(
+            long,
+            default_value_t = 1000,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Interval of the random lookups of each client, in milliseconds"
+        )]
+        lookup_interval: u64,
+    },
+}
+
+impl CliOpts {
+    // Values set on the command line take precedence over the ones from the config file
+    fn apply(self, cfg: &mut RuntimeConfig) {
+        // network preset goes first, so it can be changed by the other options
+        if let Some(network) = self.network {
+            cfg.apply_network(network);
+        }
+        if let Some(port) = self.tcp_port {
+            cfg.tcp_port = port;
+        }
+        if let Some(port) = self.quic_port {
+            cfg.quic_port = port;
+        }
+        if let Some(http_port) = self.http_port {
+            cfg.http_server_port = http_port;
+        }
+        if let Some(seed) = self.seed {
+            cfg.secret_key = Some(SecretKey::Seed { seed });
+        }
+        if let Some(endpoint) = self.ot_endpoint {
+            cfg.ot_collector_endpoint = endpoint;
+        }
+        if let Some(log_level) = self.log_level {
+            cfg.log_level = log_level;
+        }
+        if let Some(genesis_hash) = self.genesis_hash {
+            cfg.genesis_hash = genesis_hash;
+        }
+    }
 }
 
 fn parse_log_lvl(log_lvl: &str, default: Level) -> (Level, Option<ParseLevelError>) {
@@ -38,104 +213,407 @@ fn parse_log_lvl(log_lvl: &str, default: Level) -> (Level, Option<ParseLevelErro
         .unwrap_or_else(|err| (default, Some(err)))
 }
 
-fn json_subscriber(log_lvl: Level) -> impl Subscriber + Send + Sync {
-    FmtSubscriber::builder()
-        .with_env_filter(EnvFilter::new(format!("avail_light_bootstrap={log_lvl}")))
+fn log_filter(log_lvl: Level) -> EnvFilter {
+    EnvFilter::new(format!("avail_light_bootstrap={log_lvl}"))
+}
+
+// Plain log level applies to the node logs only,
+// while filter directives, such as `info,libp2p_kad=debug`, are applied as they are
+fn console_log_filter(log_level: &str) -> (EnvFilter, Option<String>) {
+    if !log_level.contains(['=', ',']) {
+        let (log_lvl, parse_err) = parse_log_lvl(log_level, Level::INFO);
+        return (log_filter(log_lvl), parse_err.map(|err| err.to_string()));
+    }
+    match EnvFilter::try_new(log_level) {
+        Ok(filter) => (filter, None),
+        Err(err) => (log_filter(Level::INFO), Some(err.to_string())),
+    }
+}
+
+fn json_layer<S>() -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fmt::layer()
+        .fmt_fields(format::JsonFields::new())
         .event_format(format::json())
-        .finish()
 }
 
-fn default_subscriber(log_lvl: Level) -> impl Subscriber + Send + Sync {
-    FmtSubscriber::builder()
-        .with_env_filter(EnvFilter::new(format!("avail_light_bootstrap={log_lvl}")))
-        .with_span_events(format::FmtSpan::CLOSE)
-        .finish()
+fn default_layer<S>() -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fmt::layer().with_span_events(format::FmtSpan::CLOSE)
 }
 
-async fn run() -> Result<()> {
-    let opts = CliOpts::parse();
+// Logs are exported in batches to the OpenTelemetry Collector, filtered by its own log level
+fn otlp_logs_layer<S>(
+    cfg: &RuntimeConfig,
+    log_lvl: Level,
+) -> Result<(impl Layer<S> + Send + Sync, LoggerProvider)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let resource = vec![
+        KeyValue::new("service.name", "avail-light-bootstrap"),
+        KeyValue::new("service.version", clap::crate_version!()),
+        KeyValue::new("role", CLIENT_ROLE),
+        KeyValue::new("origin", cfg.origin.clone()),
+        KeyValue::new("network", network_name(&cfg.genesis_hash)),
+    ];
+    let provider = telemetry::otlp::initialize_logs(
+        &cfg.ot_collector_endpoint,
+        cfg.ot_collector_protocol,
+        resource,
+    )?;
+    let layer = OpenTelemetryTracingBridge::new(&provider).with_filter(log_filter(log_lvl));
+    Ok((layer, provider))
+}
+
+fn load_config_file(path: &str, format: ConfigFormat) -> Result<RuntimeConfig> {
+    match format {
+        // missing TOML config file is created with the default values
+        ConfigFormat::Toml => Ok(confy::load_path(path)?),
+        ConfigFormat::Yaml => {
+            let content = std::fs::read_to_string(path)?;
+            match serde_yaml::from_str(&content) {
+                Ok(cfg) => Ok(cfg),
+                // earlier versions read TOML only, regardless of the extension,
+                // so TOML files named `.yaml` are still accepted
+                Err(err) => toml::from_str(&content).map_err(|_| err.into()),
+            }
+        }
+    }
+}
+
+// Loads the config file, overridden by the environment variables and command line options
+fn load_config(opts: CliOpts) -> Result<RuntimeConfig> {
     let mut cfg = RuntimeConfig::default();
     if let Some(cfg_path) = &opts.config {
-        cfg = confy::load_path(cfg_path)
+        let format = opts
+            .format
+            .unwrap_or_else(|| ConfigFormat::from_path(cfg_path));
+        cfg = load_config_file(cfg_path, format)
             .context(format!("Failed to load configuration from path {cfg_path}"))?;
+        // working directory of the services is usually the root, so the identity file is kept next to the config file
+        if let Some(config_dir) = Path::new(cfg_path).parent() {
+            cfg.identity_path = config_dir
+                .join(&cfg.identity_path)
+                .to_string_lossy()
+                .into_owned();
+        }
+    }
+    // RUST_LOG is applied the same way as the other environment variables,
+    // unless overridden by the prefixed one
+    if let Ok(directives) = std::env::var("RUST_LOG") {
+        cfg.log_level = directives;
+    }
+    cfg = cfg.with_env_overrides(std::env::vars_os())?;
+    opts.apply(&mut cfg);
+    cfg.validate()?;
+    Ok(cfg)
+}
+
+// Runs the command instead of starting the node
+async fn run_command(command: CliCommand, opts: CliOpts) -> Result<()> {
+    match command {
+        CliCommand::GenerateConfig { path: None } => {
+            print!("{}", types::default_config()?);
+        }
+        CliCommand::GenerateConfig { path: Some(path) } => {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .context(format!("Failed to create configuration file {path}"))?;
+            file.write_all(types::default_config()?.as_bytes())
+                .context(format!("Failed to write configuration file {path}"))?;
+            println!("Default configuration written to {path}");
+        }
+        CliCommand::PeerId { seed, key } => {
+            let mut cfg = load_config(opts)?;
+            if let Some(seed) = seed {
+                cfg.secret_key = Some(SecretKey::Seed { seed });
+            }
+            if let Some(key) = key {
+                cfg.secret_key = Some(SecretKey::Key { key });
+            }
+            // identity file of the earlier versions is migrated into the state directory
+            if let Some(state_path) = &cfg.state_path {
+                state::State::open(state_path, &cfg.identity_path)?;
+            }
+            let identity_file = cfg.identity_file();
+            if cfg.uses_identity_file() && p2p::read_identity(&identity_file)?.is_none() {
+                anyhow::bail!(
+                    "There is no identity file at {identity_file}, it is generated on the first start"
+                );
+            }
+            let (keypair, peer_id) = p2p::keypair((&cfg).into())?;
+            let multiaddr = cfg.listen_multiaddrs()?.remove(0);
+            let multiaddr = multiaddr.with(Protocol::P2p(keypair.public().to_peer_id()));
+            println!("Peer ID: {peer_id}");
+            // listener address is usually unspecified, public one is known only when the node is running
+            let multiaddr = multiaddr
+                .to_string()
+                .replacen("/ip4/0.0.0.0/", "/ip4/<public IP>/", 1)
+                .replacen("/ip6/::/", "/ip6/<public IP>/", 1);
+            println!("Example multiaddress: {multiaddr}");
+        }
+        CliCommand::GenerateKey { path } => {
+            let keypair = p2p::generate_identity(&path)?;
+            println!("Secret key written to {path}");
+            println!("Peer ID: {}", keypair.public().to_peer_id());
+        }
+        CliCommand::SimulateClients {
+            target,
+            clients,
+            duration,
+            lookup_interval,
+        } => {
+            // Kademlia protocol name is derived from the configured genesis hash
+            let cfg = load_config(opts)?;
+            println!("Simulating {clients} clients against {target} for {duration}s...");
+            let report = simulate::run(SimulationConfig {
+                target,
+                clients: clients as usize,
+                duration: Duration::from_secs(duration),
+                lookup_interval: Duration::from_millis(lookup_interval),
+                kademlia: (&cfg).into(),
+                identify: (&cfg).into(),
+                noise_prologue: cfg.noise_prologue(),
+            })
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
     }
+    Ok(())
+}
 
-    let (log_lvl, parse_err) = parse_log_lvl(&cfg.log_level, Level::INFO);
-    // set json trace format
-    if cfg.log_format_json {
-        tracing::subscriber::set_global_default(json_subscriber(log_lvl))
-            .expect("global json subscriber to be set");
-    } else {
-        tracing::subscriber::set_global_default(default_subscriber(log_lvl))
-            .expect("global default subscriber to be set");
+async fn run() -> Result<()> {
+    let mut opts = CliOpts::parse();
+    if let Some(command) = opts.command.take() {
+        return run_command(command, opts).await;
     }
+    // command line options are applied again on config reload
+    let reload_opts = opts.clone();
+    let cfg = load_config(opts)?;
+
+    let (console_filter, parse_err) = console_log_filter(&cfg.log_level);
+    let (otlp_log_lvl, otlp_parse_err) = parse_log_lvl(&cfg.ot_logs_level, Level::INFO);
+    let (console_filter, console_filter_handle) = reload::Layer::new(console_filter);
+    // set structured logs format, if configured
+    let console_layer = match cfg.log_format() {
+        LogFormat::Plain => default_layer().with_filter(console_filter).boxed(),
+        LogFormat::Json => json_layer().with_filter(console_filter).boxed(),
+        LogFormat::Logfmt => tracing_logfmt::layer().with_filter(console_filter).boxed(),
+    };
+    let (otlp_logs_layer, otlp_logs_err, logs_provider) = match cfg.ot_logs_enable {
+        false => (None, None, None),
+        true => match otlp_logs_layer(&cfg, otlp_log_lvl) {
+            Ok((layer, provider)) => (Some(layer), None, Some(provider)),
+            Err(err) => (None, Some(err), None),
+        },
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(console_layer)
+        .with(otlp_logs_layer);
+    tracing::subscriber::set_global_default(subscriber).expect("global subscriber to be set");
     if let Some(err) = parse_err {
         warn!("Using default log level: {err}");
     }
+    if let Some(err) = otlp_logs_err {
+        warn!("Logs are not exported to the OpenTelemetry Collector: {err:#}");
+    }
+    if let Some(err) = otlp_parse_err.filter(|_| cfg.ot_logs_enable) {
+        warn!("Using default OpenTelemetry log level: {err}");
+    }
 
-    info!("Using config: {:?}", cfg);
+    let node = avail_light_bootstrap::run(cfg.clone()).await?;
 
-    let cfg_libp2p: LibP2PConfig = (&cfg).into();
-    let (id_keys, peer_id) = p2p::keypair((&cfg).into())?;
+    #[cfg(unix)]
+    let reload = reload_on_hangup(
+        reload_opts,
+        cfg.clone(),
+        Reloadable {
+            log_filter: console_filter_handle,
+            node: &node,
+        },
+    );
+    #[cfg(not(unix))]
+    let reload = {
+        let _ = (reload_opts, console_filter_handle);
+        std::future::pending::<()>()
+    };
 
-    let (network_client, network_event_loop) =
-        p2p::init(cfg_libp2p, id_keys, cfg.ws_transport_enable)
+    let result = tokio::select! {
+        reason = node.stopped() => Err(anyhow!(reason)),
+        _ = shutdown_signal() => {
+            info!("Shutdown signal received, shutting down.");
+            Ok(())
+        }
+        _ = reload => Ok(()),
+    };
+
+    node.stop().await;
+    if let Some(provider) = logs_provider {
+        flush_logs(provider).await;
+    }
+
+    result
+}
+
+// Handles of the running node, the reloaded config values are applied through
+struct Reloadable<'a> {
+    log_filter: reload::Handle<EnvFilter, Registry>,
+    node: &'a Handle,
+}
+
+// Returns the names of the config fields with different values
+fn changed_fields(cfg: &RuntimeConfig, new_cfg: &RuntimeConfig) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(values)), Ok(serde_json::Value::Object(new_values))) =
+        (serde_json::to_value(cfg), serde_json::to_value(new_cfg))
+    else {
+        return vec![];
+    };
+    new_values
+        .into_iter()
+        .filter(|(field, value)| values.get(field) != Some(value))
+        .map(|(field, _)| field)
+        .collect()
+}
+
+// Applies the changes of the reloadable fields to the running node, updating the current config with the applied ones
+async fn apply_config(
+    cfg: &mut RuntimeConfig,
+    new_cfg: RuntimeConfig,
+    reloadable: &Reloadable<'_>,
+) {
+    let changed = changed_fields(cfg, &new_cfg);
+    if changed.is_empty() {
+        info!("Config reloaded, no changes found.");
+        return;
+    }
+
+    let mut applied = vec![];
+    if cfg.log_level != new_cfg.log_level {
+        let (filter, parse_err) = console_log_filter(&new_cfg.log_level);
+        if let Some(err) = parse_err {
+            warn!("Using default log level: {err}");
+        }
+        match reloadable.log_filter.reload(filter) {
+            Ok(()) => {
+                cfg.log_level = new_cfg.log_level;
+                applied.push("log_level");
+            }
+            Err(err) => error!("Unable to change the log level: {err}"),
+        }
+    }
+    if cfg.bootstrap_period != new_cfg.bootstrap_period {
+        let interval = Duration::from_secs(new_cfg.bootstrap_period);
+        match reloadable
+            .node
+            .client()
+            .set_bootstrap_interval(interval)
             .await
-            .context("Failed to initialize P2P Network Service.")?;
-
-    tokio::spawn(server::run((&cfg).into()));
-
-    let ot_metrics = telemetry::otlp::initialize(
-        cfg.ot_collector_endpoint,
-        peer_id,
-        CLIENT_ROLE.into(),
-        cfg.origin,
-        network_name(&cfg.genesis_hash),
-    )
-    .context("Cannot initialize OpenTelemetry service.")?;
-
-    // Spawn the network task
-    let loop_handle = tokio::spawn(network_event_loop.run());
-
-    // Spawn metrics task
-    let m_network_client = network_client.clone();
-    tokio::spawn(async move {
-        let pause_duration = Duration::from_secs(cfg.metrics_network_dump_interval);
-        let mut interval = interval_at(Instant::now() + pause_duration, pause_duration);
-        // repeat and send commands on given interval
-        loop {
-            interval.tick().await;
-            // try and read current multiaddress
-            if let Ok(Some(addr)) = m_network_client.get_multiaddress().await {
-                // set Multiaddress
-                _ = ot_metrics.set_multiaddress(addr.to_string()).await;
+        {
+            Ok(()) => {
+                cfg.bootstrap_period = new_cfg.bootstrap_period;
+                applied.push("bootstrap_period");
+            }
+            Err(err) => error!("Unable to change the bootstrap period: {err:#}"),
+        }
+    }
+    if cfg.metrics_network_dump_interval != new_cfg.metrics_network_dump_interval {
+        let interval = Duration::from_secs(new_cfg.metrics_network_dump_interval);
+        match reloadable.node.set_metrics_interval(interval) {
+            Ok(()) => {
+                cfg.metrics_network_dump_interval = new_cfg.metrics_network_dump_interval;
+                applied.push("metrics_network_dump_interval");
             }
-            if let Ok(counted_peers) = m_network_client.count_dht_entries().await {
-                debug!("Number of peers in the routing table: {}", counted_peers);
-                if let Err(err) = ot_metrics
-                    .record(MetricValue::KadRoutingPeerNum(counted_peers))
-                    .await
-                {
-                    error!("Error recording network stats metric: {err}");
+            Err(_) => error!("Unable to change the metrics interval, metrics task is stopped."),
+        }
+    }
+
+    if !applied.is_empty() {
+        info!(
+            "Config reloaded, applied changes of: {}.",
+            applied.join(", ")
+        );
+    }
+    let restart_required: Vec<_> = changed
+        .iter()
+        .map(String::as_str)
+        .filter(|field| !RELOADABLE_FIELDS.contains(field))
+        .collect();
+    if !restart_required.is_empty() {
+        warn!(
+            "Config reloaded, changes of {} require restart to be applied.",
+            restart_required.join(", ")
+        );
+    }
+}
+
+// Reloads the config on each SIGHUP, keeping the current one if the new config fails to load.
+// Never completes, so it is awaited along with the shutdown signal.
+#[cfg(unix)]
+async fn reload_on_hangup(opts: CliOpts, mut cfg: RuntimeConfig, reloadable: Reloadable<'_>) {
+    match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(mut hangup) => {
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP received, reloading config.");
+                match load_config(opts.clone()) {
+                    Ok(new_cfg) => apply_config(&mut cfg, new_cfg, &reloadable).await,
+                    Err(err) => error!("Config reload failed, keeping the current config: {err:#}"),
                 }
-            };
-            _ = ot_metrics.record(MetricValue::HealthCheck()).await;
+            }
         }
-    });
+        Err(err) => error!("Unable to listen for SIGHUP signal, config reload is disabled: {err}"),
+    }
+    std::future::pending::<()>().await;
+}
+
+// Exports the batched logs, which are otherwise only exported once the batch is full or on a timer
+async fn flush_logs(provider: LoggerProvider) {
+    // processor blocks until the export is done
+    let flush = tokio::task::spawn_blocking(move || provider.force_flush());
+    match timeout(FLUSH_TIMEOUT, flush).await {
+        Ok(Ok(results)) => {
+            for err in results.into_iter().filter_map(Result::err) {
+                error!("Failed to flush logs on shutdown: {err}");
+            }
+        }
+        Ok(Err(err)) => error!("Logs flush task failed: {err}"),
+        Err(_) => warn!("Logs were not flushed within {FLUSH_TIMEOUT:?}"),
+    }
+}
 
-    // Listen on all interfaces with TCP
-    network_client
-        .start_listening(construct_multiaddress(cfg.ws_transport_enable, cfg.port))
-        .await
-        .context("Unable to create P2P listener.")?;
-    info!("Started listening for TCP traffic on port: {:?}.", cfg.port);
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            error!("Unable to listen for Ctrl-C signal: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
 
-    info!("Bootstrap node starting ...");
-    network_client.bootstrap().await?;
-    info!("Bootstrap done.");
-    loop_handle.await?;
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                error!("Unable to listen for SIGTERM signal: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    Ok(())
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 #[tokio::main]
@@ -145,17 +623,3 @@ async fn main() -> Result<()> {
         err
     })
 }
-
-fn construct_multiaddress(is_websocket: bool, port: u16) -> Multiaddr {
-    let tcp_multiaddress = Multiaddr::empty()
-        .with(Protocol::from(Ipv4Addr::UNSPECIFIED))
-        .with(Protocol::Tcp(port));
-
-    if is_websocket {
-        return tcp_multiaddress.with(Protocol::Ws(std::borrow::Cow::Borrowed(
-            "avail-light-bootstrap",
-        )));
-    }
-
-    tcp_multiaddress
-}
@@ -0,0 +1,834 @@
+//! Bootstrap node, running the network event loop along with the servers and telemetry tasks.
+
+use anyhow::{Context, Result};
+use libp2p::Multiaddr;
+use std::{
+    collections::{HashSet, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{broadcast::error::RecvError, watch},
+    task::JoinHandle,
+    time::{interval, interval_at, sleep, sleep_until, timeout, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+#[cfg(unix)]
+use crate::systemd;
+use crate::{
+    crawl::Crawler,
+    dnsaddr::{self, DnsaddrResolver},
+    geoip::GeoIp,
+    grpc,
+    p2p::{
+        self,
+        client::{Client, NetworkError, NetworkEvent},
+    },
+    rpc, server, shutdown,
+    state::State,
+    telemetry::{
+        self, process::ProcessMonitor, BootstrapKind, MetricAttributes, MetricCounter,
+        MetricHistogram, MetricValue, Metrics, TransferredBytes, FLUSH_TIMEOUT,
+    },
+    topology::Topology,
+    types::{self, network_name, Addr, LibP2PConfig, MetricsBackend, RuntimeConfig},
+};
+
+// pause before the crashed network event loop is restarted
+const EVENT_LOOP_RESTART_DELAY: Duration = Duration::from_secs(1);
+// pause before the `/dnsaddr` bootstrap addresses failed to be resolved are resolved again
+const DNSADDR_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Handle of the running node, which keeps running until stopped or until any of its tasks fails
+pub struct Handle {
+    client: Client,
+    peer_id: String,
+    shutdown: shutdown::Controller,
+    handles: Vec<JoinHandle<()>>,
+    metrics_handle: JoinHandle<()>,
+    metrics_interval: watch::Sender<Duration>,
+    state: Option<State>,
+    crawler: Arc<Crawler>,
+    _pid_file: Option<PidFile>,
+}
+
+impl Handle {
+    /// Client of the running network event loop
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+
+    /// Changes the interval in which the network metrics are recorded
+    pub fn set_metrics_interval(&self, interval: Duration) -> Result<()> {
+        self.metrics_interval
+            .send(interval)
+            .map_err(|_| anyhow::anyhow!("Metrics task is stopped"))
+    }
+
+    /// Completes once any of the node tasks fails, returning the reason the node is stopped for
+    pub async fn stopped(&self) -> String {
+        self.shutdown.triggered().await;
+        self.shutdown.reason().unwrap_or_default()
+    }
+
+    /// Stops the node, saving its state and waiting for all the tasks to finish
+    pub async fn stop(self) {
+        // state is saved only while the event loop is running
+        if !self.shutdown.is_shutdown_triggered() {
+            if let Some(state) = &self.state {
+                if let Err(err) = state.save(&self.client, &self.crawler).await {
+                    error!("Unable to save the node state: {err:#}");
+                }
+            }
+            self.shutdown.trigger_shutdown("Node stopped");
+        }
+
+        #[cfg(unix)]
+        systemd::notify_stopping();
+        for handle in self.handles {
+            if let Err(err) = handle.await {
+                error!("Task failed on shutdown: {err}");
+            }
+        }
+        match timeout(FLUSH_TIMEOUT, self.metrics_handle).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("Metrics task failed: {err}"),
+            Err(_) => warn!("Metrics were not flushed within {FLUSH_TIMEOUT:?}"),
+        }
+    }
+}
+
+/// Starts the node with the given config, returning once all of its tasks are spawned
+pub async fn run(cfg: RuntimeConfig) -> Result<Handle> {
+    let node_started = Instant::now();
+    let build = types::BUILD_INFO;
+    info!(
+        "Avail bootstrap node version {}, commit {}, built {} with {}.",
+        build.version, build.git_commit, build.build_timestamp, build.rustc_version
+    );
+    info!("Using config: {:?}", cfg);
+
+    let pid_file = cfg.pid_file.as_deref().map(PidFile::create).transpose()?;
+    let state = cfg
+        .state_path
+        .as_deref()
+        .map(|path| State::open(path, &cfg.identity_path))
+        .transpose()?;
+
+    let cfg_libp2p: LibP2PConfig = (&cfg).into();
+    let (id_keys, peer_id) = p2p::keypair((&cfg).into())?;
+
+    let attributes = MetricAttributes::new(
+        peer_id.clone(),
+        types::CLIENT_ROLE.into(),
+        cfg.origin.clone(),
+        network_name(&cfg.genesis_hash),
+        &cfg.metrics_extra_labels,
+    );
+    let mut prometheus_metrics = None;
+    let mut backends: Vec<Arc<dyn Metrics>> = vec![];
+    for backend in cfg.metrics_backend.enabled() {
+        match backend {
+            // node can run without any telemetry stack, with no collector to push the metrics to
+            MetricsBackend::Otlp if cfg.ot_collector_endpoint.trim().is_empty() => {
+                warn!("OpenTelemetry Collector endpoint is not set, OTLP metrics are disabled.");
+            }
+            MetricsBackend::Otlp => backends.push(Arc::new(
+                telemetry::otlp::initialize(
+                    cfg.ot_collector_endpoint.clone(),
+                    cfg.ot_collector_protocol,
+                    cfg.ot_export_buffer_size,
+                    attributes.clone(),
+                )
+                .context("Cannot initialize OpenTelemetry service.")?,
+            )),
+            MetricsBackend::Prometheus => {
+                let metrics = Arc::new(telemetry::prometheus::initialize(attributes.clone()));
+                prometheus_metrics = Some(metrics.clone());
+                backends.push(metrics);
+            }
+            MetricsBackend::Statsd => backends.push(Arc::new(
+                telemetry::statsd::initialize((&cfg).into(), attributes.clone())
+                    .await
+                    .context("Cannot initialize StatsD metrics.")?,
+            )),
+            MetricsBackend::None => {}
+        }
+    }
+    let metrics: Arc<dyn Metrics> = match backends.len() {
+        0 => Arc::new(telemetry::noop::Metrics),
+        1 => backends.remove(0),
+        _ => Arc::new(telemetry::fanout::Metrics::new(backends)),
+    };
+    let metrics: Arc<dyn Metrics> =
+        Arc::new(telemetry::relabel::Metrics::new(metrics, (&cfg).into()));
+    metrics.count(MetricCounter::Starts).await;
+
+    let geoip = GeoIp::open(&(&cfg).into()).context("Failed to initialize GeoIP lookups.")?;
+    // event loop is stopped cooperatively on the shutdown it is run with
+    let (network_client, network, _) = p2p::init(
+        cfg_libp2p,
+        id_keys,
+        cfg.ws_transport_enable,
+        cfg.quic_enable,
+        geoip,
+        metrics.clone(),
+    );
+    let network_event_loop = network
+        .event_loop()
+        .await
+        .context("Failed to initialize P2P Network Service.")?;
+
+    // tasks are torn down once any of them fails, or on the shutdown signal
+    let shutdown = shutdown::Controller::new();
+
+    let crawler = Arc::new(Crawler::new(network_client.clone()));
+    let topology = Arc::new(Topology::new(network_client.clone(), peer_id.clone()));
+    let server_state = server::SharedState {
+        client: network_client.clone(),
+        crawler: crawler.clone(),
+        topology: topology.clone(),
+        metrics: metrics.clone(),
+        prometheus: prometheus_metrics,
+        cfg: Arc::new(cfg.clone()),
+    };
+    let mut handles = vec![shutdown.spawn(
+        "HTTP server",
+        server::run((&cfg).into(), server_state, shutdown.triggered()),
+    )];
+
+    if cfg.rpc_enable {
+        let rpc_addr = Addr {
+            host: cfg.rpc_host.clone(),
+            port: cfg.rpc_port,
+        };
+        handles.push(shutdown.spawn(
+            "JSON-RPC server",
+            rpc::run(
+                rpc_addr,
+                (&cfg).into(),
+                network_client.clone(),
+                shutdown.triggered(),
+            ),
+        ));
+    }
+
+    if cfg.grpc_enable {
+        let grpc_addr = Addr {
+            host: cfg.grpc_host.clone(),
+            port: cfg.grpc_port,
+        };
+        handles.push(shutdown.spawn(
+            "gRPC server",
+            grpc::run(
+                grpc_addr,
+                (&cfg).into(),
+                network_client.clone(),
+                crawler.clone(),
+                shutdown.triggered(),
+            ),
+        ));
+    }
+
+    // Spawn the network task
+    handles.push(shutdown.spawn(
+        "Network event loop supervisor",
+        supervise_event_loop(
+            network,
+            network_event_loop,
+            network_client.clone(),
+            cfg.clone(),
+            metrics.clone(),
+            shutdown.clone(),
+        ),
+    ));
+
+    let dnsaddr_bootstraps: Vec<Multiaddr> = cfg
+        .bootstraps
+        .iter()
+        .filter_map(|addr| addr.parse().ok())
+        .filter(dnsaddr::is_dnsaddr)
+        .collect();
+    if !dnsaddr_bootstraps.is_empty() {
+        handles.push(shutdown.spawn(
+            "Bootstrap addresses refresh",
+            refresh_dnsaddr_bootstraps(
+                network_client.clone(),
+                dnsaddr_bootstraps,
+                shutdown.triggered(),
+            ),
+        ));
+    }
+
+    if cfg.maintenance_interval > 0 {
+        handles.push(shutdown.spawn(
+            "Maintenance",
+            run_maintenance(network_client.clone(), cfg.clone(), shutdown.triggered()),
+        ));
+    }
+
+    if cfg.topology_snapshot_interval > 0 {
+        handles.push(shutdown.spawn(
+            "Topology snapshots",
+            take_topology_snapshots(
+                topology,
+                network_client.clone(),
+                state.clone(),
+                cfg.clone(),
+                shutdown.triggered(),
+            ),
+        ));
+    }
+
+    // Spawn metrics task
+    let m_network_client = network_client.clone();
+    let metrics_shutdown = shutdown.triggered();
+    let (metrics_interval_sender, mut metrics_interval) =
+        watch::channel(Duration::from_secs(cfg.metrics_network_dump_interval));
+    let metrics_handle = shutdown.spawn("Metrics", async move {
+        tokio::pin!(metrics_shutdown);
+        let pause_duration = *metrics_interval.borrow_and_update();
+        let mut interval = interval_at(Instant::now() + pause_duration, pause_duration);
+        let mut transferred_bytes = TransferredBytes::default();
+        let mut process_monitor = ProcessMonitor::new()
+            .map_err(|err| warn!("Process resource metrics are disabled: {err:#}"))
+            .ok();
+        // repeat and send commands on given interval
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                Ok(()) = metrics_interval.changed() => {
+                    let pause_duration = *metrics_interval.borrow_and_update();
+                    interval = interval_at(Instant::now() + pause_duration, pause_duration);
+                    continue;
+                }
+                _ = &mut metrics_shutdown => break,
+            }
+            if let Some(monitor) = process_monitor.as_mut() {
+                record_process_metrics(monitor, metrics.as_ref()).await;
+            }
+            record_network_metrics(
+                &m_network_client,
+                metrics.as_ref(),
+                &mut transferred_bytes,
+                node_started,
+            )
+            .await;
+        }
+        // values since the last interval are recorded once more, so they are flushed as well
+        record_network_metrics(
+            &m_network_client,
+            metrics.as_ref(),
+            &mut transferred_bytes,
+            node_started,
+        )
+        .await;
+        if let Err(err) = metrics.shutdown().await {
+            error!("Error flushing metrics on shutdown: {err:#}");
+        }
+    });
+
+    #[cfg(unix)]
+    handles.push(shutdown.spawn(
+        "systemd watchdog",
+        systemd::run_watchdog(network_client.clone(), shutdown.triggered()),
+    ));
+
+    if let Some(state) = &state {
+        if let Err(err) = state.restore(&network_client, &crawler).await {
+            warn!("Unable to restore the node state: {err:#}");
+        }
+    }
+
+    Ok(Handle {
+        client: network_client,
+        peer_id,
+        shutdown,
+        handles,
+        metrics_handle,
+        metrics_interval: metrics_interval_sender,
+        state,
+        crawler,
+        _pid_file: pid_file,
+    })
+}
+
+// Starts listening and dials the other bootstrap nodes, on start and once the event loop is restarted
+async fn connect(client: &Client, cfg: &RuntimeConfig) -> Result<()> {
+    // Listen on all interfaces with TCP and QUIC if enabled, unless listen addresses are set
+    for addr in cfg.listen_multiaddrs()? {
+        client
+            .start_listening(addr.clone())
+            .await
+            .context(format!("Unable to create P2P listener on {addr}."))?;
+        info!("Started listening for P2P traffic on {addr}.");
+    }
+
+    // other bootstrap nodes are added to the routing table once connected
+    let mut resolver = None;
+    for addr in &cfg.bootstraps {
+        let multiaddr: Multiaddr = addr.parse().context("Invalid bootstrap address.")?;
+        // DNS transport dials only the first reachable address of the name, so all of them are dialed instead
+        let multiaddrs = if dnsaddr::is_dnsaddr(&multiaddr) {
+            if resolver.is_none() {
+                resolver = Some(DnsaddrResolver::new()?);
+            }
+            match resolver
+                .as_ref()
+                .expect("resolver is set")
+                .resolve(&multiaddr)
+                .await
+            {
+                Ok(resolution) => resolution.addresses,
+                Err(err) => {
+                    warn!("Unable to resolve bootstrap node {addr}: {err:#}");
+                    continue;
+                }
+            }
+        } else {
+            vec![multiaddr]
+        };
+        for multiaddr in multiaddrs {
+            if let Err(err) = client.dial(multiaddr).await {
+                warn!("Unable to dial bootstrap node {addr}: {err:#}");
+            }
+        }
+    }
+    Ok(())
+}
+
+// Resolves the `/dnsaddr` bootstrap addresses again once their records expire,
+// dialing the bootstrap nodes added to the records since, until the shutdown
+async fn refresh_dnsaddr_bootstraps(
+    client: Client,
+    bootstraps: Vec<Multiaddr>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    tokio::pin!(shutdown);
+    let resolver = match DnsaddrResolver::new() {
+        Ok(resolver) => resolver,
+        Err(err) => {
+            warn!("Bootstrap addresses won't be resolved again: {err:#}");
+            // task is expected to run until the shutdown
+            shutdown.await;
+            return;
+        }
+    };
+    // addresses resolved on start are dialed by the event loop supervisor, along with the other bootstrap nodes
+    let mut known: HashSet<Multiaddr> = HashSet::new();
+    let mut is_first = true;
+    loop {
+        // names are resolved again once the first of their records expires,
+        // or after the retry interval if any of them failed to be resolved
+        let retry_at = Instant::now() + DNSADDR_RETRY_INTERVAL;
+        let mut next_resolution: Option<Instant> = None;
+        for bootstrap in &bootstraps {
+            let resolution = match resolver.resolve(bootstrap).await {
+                Ok(resolution) => resolution,
+                Err(err) => {
+                    warn!("Unable to resolve bootstrap node {bootstrap}: {err:#}");
+                    next_resolution = Some(next_resolution.map_or(retry_at, |at| at.min(retry_at)));
+                    continue;
+                }
+            };
+            let expires_at = Instant::from_std(resolution.valid_until);
+            next_resolution = Some(next_resolution.map_or(expires_at, |at| at.min(expires_at)));
+            for address in resolution.addresses {
+                let is_new = known.insert(address.clone());
+                if !is_new || is_first {
+                    continue;
+                }
+                info!("Dialing bootstrap node {address}, resolved from {bootstrap}.");
+                if let Err(err) = client.dial(address.clone()).await {
+                    warn!("Unable to dial bootstrap node {address}: {err:#}");
+                }
+            }
+        }
+        is_first = false;
+
+        tokio::select! {
+            _ = sleep_until(next_resolution.unwrap_or(retry_at)) => {}
+            _ = &mut shutdown => break,
+        }
+    }
+}
+
+// Runs the event loop maintenance on the interval, until the shutdown
+async fn run_maintenance(
+    client: Client,
+    cfg: RuntimeConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    tokio::pin!(shutdown);
+    let period = Duration::from_secs(cfg.maintenance_interval);
+    let stale_peer_timeout = Duration::from_secs(cfg.maintenance_stale_peer_timeout);
+    let mut interval = interval_at(Instant::now() + period, period);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut shutdown => break,
+        }
+        match client.run_maintenance(stale_peer_timeout).await {
+            Ok(summary) => info!(
+                stale_peers_pruned = summary.stale_peers_pruned,
+                peer_store_trimmed = summary.peer_store_trimmed,
+                external_addresses_probed = summary.external_addresses_probed,
+                provider_records_republished = summary.provider_records_republished,
+                routing_table_peers = summary.routing_table_peers,
+                "Maintenance done."
+            ),
+            // event loop may be restarting, maintenance is retried on the next interval
+            Err(err) => warn!("Unable to run maintenance: {err:#}"),
+        }
+    }
+}
+
+// Completes once the startup bootstrap is done, either successfully or not
+async fn startup_bootstrap_done(client: &Client) {
+    // subscribed before checking the state, so the bootstrap completed in between isn't missed
+    let mut events = client.subscribe();
+    if client
+        .get_health_state()
+        .await
+        .is_ok_and(|state| state.is_startup_bootstrap_done)
+    {
+        return;
+    }
+    loop {
+        match events.recv().await {
+            Ok(NetworkEvent::BootstrapCompleted {
+                kind: BootstrapKind::Startup,
+                ..
+            })
+            | Err(RecvError::Closed) => return,
+            _ => {}
+        }
+    }
+}
+
+// Takes the topology snapshots, the first one right after the startup bootstrap,
+// writing them into the state directory, if set
+async fn take_topology_snapshots(
+    topology: Arc<Topology>,
+    client: Client,
+    state: Option<State>,
+    cfg: RuntimeConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    tokio::pin!(shutdown);
+    tokio::select! {
+        _ = startup_bootstrap_done(&client) => {}
+        _ = &mut shutdown => return,
+    }
+    let period = Duration::from_secs(cfg.topology_snapshot_interval);
+    let mut interval = interval(period);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut shutdown => break,
+        }
+        let snapshot = match topology.take().await {
+            Ok(snapshot) => snapshot,
+            // event loop may be restarting, snapshot is taken on the next interval
+            Err(err) => {
+                warn!("Unable to take topology snapshot: {err:#}");
+                continue;
+            }
+        };
+        debug!(
+            routing_table_peers = snapshot.routing_table.len(),
+            connections = snapshot.connections.len(),
+            "Topology snapshot taken."
+        );
+        if let Some(state) = &state {
+            if let Err(err) = state.save_topology(&snapshot, cfg.topology_snapshot_retention) {
+                warn!("Unable to save topology snapshot: {err:#}");
+            }
+        }
+    }
+}
+
+// Returns the diagnostics of the stalled event loop, if it doesn't respond to the command in time,
+// or doesn't handle any swarm events while there are established connections
+async fn check_stall(client: &Client, cfg: &RuntimeConfig) -> Option<String> {
+    let command_timeout = Duration::from_secs(cfg.stall_command_timeout);
+    let swarm_event_timeout = Duration::from_secs(cfg.stall_swarm_event_timeout);
+    match timeout(command_timeout, client.get_health_state()).await {
+        Err(_) => Some(format!(
+            "no response within {command_timeout:?}, {} commands queued",
+            client.command_queue_depth()
+        )),
+        // stopped event loop is handled by the supervisor
+        Ok(Err(_)) => None,
+        Ok(Ok(state))
+            if state.established_connections > 0
+                && state.since_last_swarm_event > swarm_event_timeout =>
+        {
+            Some(format!(
+                "no swarm events handled for {:?}, with {} established connections, {} commands queued",
+                state.since_last_swarm_event,
+                state.established_connections,
+                client.command_queue_depth()
+            ))
+        }
+        Ok(Ok(_)) => None,
+    }
+}
+
+// Starts the network event loop, listening and bootstrapping with each (re)started one.
+// Event loop is rebuilt with a new swarm if it panics, stalls or stops before the shutdown,
+// and the node is shut down once it is restarted more than `event_loop_max_restarts` times within the window.
+async fn supervise_event_loop(
+    network: p2p::Network,
+    mut event_loop: p2p::EventLoop,
+    client: Client,
+    cfg: RuntimeConfig,
+    metrics: Arc<dyn Metrics>,
+    shutdown: shutdown::Controller,
+) {
+    let restart_window = Duration::from_secs(cfg.event_loop_restart_window);
+    // interval is unused if the stall checks are disabled
+    let stall_check_interval = Duration::from_secs(cfg.stall_check_interval.max(1));
+    let stall_command_timeout = Duration::from_secs(cfg.stall_command_timeout);
+    let mut restarts: VecDeque<Instant> = VecDeque::new();
+    let mut is_ready = false;
+    loop {
+        let mut running = tokio::spawn(event_loop.run(shutdown.triggered()));
+        let start = async {
+            connect(&client, &cfg).await?;
+            info!("Bootstrap node starting ...");
+            // first node of a network keeps waiting for its peers, even longer than the query timeout
+            loop {
+                match client.bootstrap().await {
+                    Err(err @ NetworkError::Timeout { .. }) => {
+                        warn!("Bootstrap not done yet, retrying: {err}")
+                    }
+                    result => break result?,
+                }
+            }
+            info!("Bootstrap done.");
+            Ok::<(), anyhow::Error>(())
+        };
+        tokio::pin!(start);
+        let mut is_started = false;
+        let mut stall_checks =
+            interval_at(Instant::now() + stall_check_interval, stall_check_interval);
+
+        let cause = loop {
+            tokio::select! {
+                biased;
+                result = &mut running => break match result {
+                    Ok(()) if shutdown.is_shutdown_triggered() => return,
+                    Ok(()) => "stopped".to_string(),
+                    Err(err) => shutdown::failure(err),
+                },
+                result = &mut start, if !is_started => {
+                    is_started = true;
+                    match result {
+                        // systemd is notified once, as the restarts are handled by the supervisor
+                        Ok(()) if !is_ready => {
+                            is_ready = true;
+                            #[cfg(unix)]
+                            systemd::notify_ready();
+                        }
+                        Ok(()) => {}
+                        // responses are dropped along with the failed event loop, which is handled once it is finished
+                        Err(err) if matches!(
+                            err.root_cause().downcast_ref(),
+                            Some(NetworkError::ChannelClosed { .. })
+                        ) => {
+                            warn!("Bootstrap node start interrupted: {err:#}");
+                        }
+                        Err(err) => shutdown.trigger_shutdown(format!("{err:#}")),
+                    }
+                }
+                _ = stall_checks.tick(), if cfg.stall_check_interval > 0 => {
+                    let Some(diagnostics) = check_stall(&client, &cfg).await else {
+                        continue;
+                    };
+                    error!("Network event loop is stalled: {diagnostics}.");
+                    running.abort();
+                    // event loop blocking the thread is never aborted, keeping the command receiver as well
+                    if timeout(stall_command_timeout, &mut running).await.is_err() {
+                        shutdown.trigger_shutdown(format!(
+                            "Network event loop is stalled and can't be stopped: {diagnostics}"
+                        ));
+                        return;
+                    }
+                    break format!("stalled: {diagnostics}");
+                }
+            }
+        };
+
+        let now = Instant::now();
+        restarts.retain(|restarted| now.duration_since(*restarted) < restart_window);
+        if restarts.len() >= cfg.event_loop_max_restarts {
+            shutdown.trigger_shutdown(format!(
+                "Network event loop {cause}, after {} restarts within {restart_window:?}",
+                restarts.len()
+            ));
+            return;
+        }
+        error!("Network event loop {cause}, restarting.");
+        restarts.push_back(now);
+        metrics.count(MetricCounter::EventLoopRestart).await;
+        sleep(EVENT_LOOP_RESTART_DELAY).await;
+
+        event_loop = match network.event_loop().await {
+            Ok(event_loop) => event_loop,
+            Err(err) => {
+                shutdown
+                    .trigger_shutdown(format!("Failed to rebuild P2P Network Service: {err:#}"));
+                return;
+            }
+        };
+    }
+}
+
+// PID file of the running node, removed once dropped on shutdown
+struct PidFile {
+    path: String,
+}
+
+impl PidFile {
+    fn create(path: &str) -> Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .context(format!("Unable to write PID file {path}"))?;
+        Ok(PidFile {
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            warn!("Unable to remove PID file {}: {err}", self.path);
+        }
+    }
+}
+
+async fn record_process_metrics(monitor: &mut ProcessMonitor, metrics: &dyn Metrics) {
+    match monitor.usage() {
+        Ok(usage) => {
+            if let Err(err) = metrics.record(MetricValue::ProcessUsage(usage)).await {
+                error!("Error recording process usage metric: {err}");
+            }
+        }
+        Err(err) => error!("Unable to measure process resource usage: {err:#}"),
+    }
+}
+
+// Records the network state and the metrics buffered by the event loop since the previous call
+pub(crate) async fn record_network_metrics(
+    client: &Client,
+    metrics: &dyn Metrics,
+    transferred_bytes: &mut TransferredBytes,
+    node_started: Instant,
+) {
+    // queue depth is read before sending any of the commands below
+    let depth = client.command_queue_depth();
+    if let Err(err) = metrics.record(MetricValue::CommandQueueDepth(depth)).await {
+        error!("Error recording command queue depth metric: {err}");
+    }
+    let saturation = client.command_queue_saturation();
+    if let Err(err) = metrics
+        .record(MetricValue::CommandQueueSaturation(saturation))
+        .await
+    {
+        error!("Error recording command queue saturation metric: {err}");
+    }
+    let overloaded = client.take_overloaded_commands();
+    if overloaded > 0 {
+        warn!("Network event loop is overloaded, {overloaded} commands rejected since the previous metrics interval.");
+        metrics
+            .count_n(MetricCounter::OverloadedCommand, overloaded)
+            .await;
+    }
+    let started = Instant::now();
+    if client.ping().await.is_ok() {
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        metrics
+            .record_histogram(MetricHistogram::EventLoopLatency, latency_ms)
+            .await;
+    }
+    // try and read current multiaddress
+    if let Ok(Some(addr)) = client.get_multiaddress().await {
+        // set Multiaddress
+        _ = metrics.set_multiaddress(addr.to_string()).await;
+    }
+    if let Ok(counted_peers) = client.count_dht_entries().await {
+        debug!("Number of peers in the routing table: {}", counted_peers);
+        if let Err(err) = metrics
+            .record(MetricValue::KadRoutingPeerNum(counted_peers))
+            .await
+        {
+            error!("Error recording network stats metric: {err}");
+        }
+    };
+    if let Ok(bucket_peers) = client.count_dht_bucket_entries().await {
+        if let Err(err) = metrics
+            .record(MetricValue::KadRoutingBucketPeerNum(bucket_peers))
+            .await
+        {
+            error!("Error recording routing table buckets metric: {err}");
+        }
+    }
+    if let Ok(counts) = client.count_connections().await {
+        if let Err(err) = metrics.record(MetricValue::ConnectionNum(counts)).await {
+            error!("Error recording connections metric: {err}");
+        }
+    }
+    if let Ok(Some(counts)) = client.count_peers_per_country().await {
+        if let Err(err) = metrics.record(MetricValue::CountryPeerNum(counts)).await {
+            error!("Error recording peers per country metric: {err}");
+        }
+    }
+    if let Ok(counts) = client.count_distinct_seen().await {
+        if let Err(err) = metrics.record(MetricValue::DistinctSeenNum(counts)).await {
+            error!("Error recording distinct peers metric: {err}");
+        }
+    }
+    if let Ok(num) = client.count_blocked_peers().await {
+        if let Err(err) = metrics.record(MetricValue::BlockedPeerNum(num)).await {
+            error!("Error recording blocked peers metric: {err}");
+        }
+    }
+    if let Ok(status) = client.get_nat_status().await {
+        if let Err(err) = metrics.record(MetricValue::AutoNatStatus(status)).await {
+            error!("Error recording NAT status metric: {err}");
+        }
+    }
+    if let Ok(usage) = client.get_autonat_throttle_usage().await {
+        if let Err(err) = metrics
+            .record(MetricValue::AutoNatThrottleUsage(usage))
+            .await
+        {
+            error!("Error recording AutoNAT throttle usage metric: {err}");
+        }
+    }
+    if let Ok(current) = client.get_transferred_bytes().await {
+        let interval_bytes = current.since(transferred_bytes);
+        *transferred_bytes = current;
+        for (direction, bytes) in interval_bytes.by_direction() {
+            let counter = MetricCounter::TransferredBytes(direction);
+            metrics.count_n(counter, bytes).await;
+        }
+        if let Err(err) = metrics
+            .record(MetricValue::IntervalTransferredBytes(interval_bytes))
+            .await
+        {
+            error!("Error recording transferred bytes metric: {err}");
+        }
+    }
+    _ = metrics
+        .record(MetricValue::HealthCheck(node_started.elapsed()))
+        .await;
+    metrics.count(MetricCounter::Heartbeat).await;
+}
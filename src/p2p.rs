@@ -1,135 +1,568 @@
 use allow_block_list::BlockedPeers;
 use anyhow::{Context, Result};
+#[allow(deprecated)]
+use libp2p::bandwidth::BandwidthSinks;
 use libp2p::{
-    autonat, identify,
+    autonat,
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade::Version},
+    dns, identify,
     identity::{self, Keypair},
     kad::{self, store::MemoryStore, Mode},
-    noise, ping,
-    swarm::NetworkBehaviour,
-    tcp, yamux, PeerId, SwarmBuilder,
+    noise, ping, quic, request_response,
+    swarm::{dummy, NetworkBehaviour},
+    tcp, websocket, yamux, PeerId, SwarmBuilder, Transport,
 };
 use multihash::Hasher;
-use tokio::sync::mpsc;
+use std::{error::Error, fmt::Debug, fs::OpenOptions, io::Write, sync::Arc};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
 
-mod client;
+mod bootstrap_info;
+pub mod client;
+mod disconnect;
 mod event_loop;
+mod keep_alive;
+mod peer_exchange;
 
 use crate::{
-    p2p::client::{Client, Command},
-    types::{LibP2PConfig, SecretKey},
+    geoip::GeoIp,
+    p2p::client::{Client, Command, NetworkEvent},
+    telemetry::{Metrics, TransferredBytes},
+    types::{IdentityFile, LibP2PConfig, SecretKey, WssConfig},
 };
-use event_loop::EventLoop;
+use bootstrap_info::BootstrapInfoCodec;
+use disconnect::DisconnectCodec;
+pub use event_loop::EventLoop;
 use libp2p_allow_block_list as allow_block_list;
-use tracing::info;
+use peer_exchange::PeerExchangeCodec;
+use tracing::{info, warn};
+
+// number of network events kept for the subscribers, until received by all of them
+const EVENTS_CAPACITY: usize = 1000;
 
 #[derive(NetworkBehaviour)]
-pub struct Behaviour {
+pub struct Behaviour<E: NetworkBehaviour> {
     kademlia: kad::Behaviour<MemoryStore>,
     identify: identify::Behaviour,
     auto_nat: autonat::Behaviour,
     ping: ping::Behaviour,
     blocked_peers: allow_block_list::Behaviour<BlockedPeers>,
+    disconnect: request_response::Behaviour<DisconnectCodec>,
+    peer_exchange: request_response::Behaviour<PeerExchangeCodec>,
+    bootstrap_info: request_response::Behaviour<BootstrapInfoCodec>,
+    keep_alive: keep_alive::Behaviour,
+    extension: E,
 }
 
-pub async fn init(
-    cfg: LibP2PConfig,
-    id_keys: Keypair,
-    is_ws_transport: bool,
-) -> Result<(Client, EventLoop)> {
-    let local_peer_id = PeerId::from(id_keys.public());
-    info!(
-        "Local Peer ID: {:?}. Public key: {:?}.",
-        local_peer_id,
-        id_keys.public()
-    );
+/// Behaviour composed into the swarm next to the built-in ones, e.g. a custom request-response protocol
+pub struct Extension<E: NetworkBehaviour> {
+    /// Builds the behaviour from the node keypair, on each (re)start of the event loop
+    pub behaviour: Arc<dyn Fn(&Keypair) -> E + Send + Sync>,
+    /// Maps the behaviour events to the data of the [NetworkEvent::Extension] events published to the subscribers,
+    /// skipping the events mapped to `None`
+    pub events: ExtensionEvents<E>,
+}
 
-    // create Identify Protocol Config
-    let identify_cfg =
-        identify::Config::new(cfg.identify.protocol_version.clone(), id_keys.public())
-            .with_agent_version(cfg.identify.agent_version.to_string());
-
-    // create AutoNAT Server Config
-    let autonat_cfg = autonat::Config {
-        only_global_ips: cfg.autonat.only_global_ips,
-        throttle_clients_global_max: cfg.autonat.throttle_clients_global_max,
-        throttle_clients_peer_max: cfg.autonat.throttle_clients_peer_max,
-        throttle_clients_period: cfg.autonat.throttle_clients_period,
-        ..Default::default()
+pub type ExtensionEvents<E> =
+    Arc<dyn Fn(<E as NetworkBehaviour>::ToSwarm) -> Option<serde_json::Value> + Send + Sync>;
+
+impl<E: NetworkBehaviour> Clone for Extension<E> {
+    fn clone(&self) -> Self {
+        Extension {
+            behaviour: self.behaviour.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+fn no_extension_events(
+    event: <dummy::Behaviour as NetworkBehaviour>::ToSwarm,
+) -> Option<serde_json::Value> {
+    match event {}
+}
+
+impl Default for Extension<dummy::Behaviour> {
+    fn default() -> Self {
+        Extension {
+            behaviour: Arc::new(|_| dummy::Behaviour),
+            events: Arc::new(no_extension_events),
+        }
+    }
+}
+
+/// Totals of the bytes transferred over all the connections, since the start
+pub struct Bandwidth {
+    #[allow(deprecated)]
+    sinks: Arc<BandwidthSinks>,
+}
+
+impl Bandwidth {
+    pub fn transferred_bytes(&self) -> TransferredBytes {
+        TransferredBytes {
+            received: self.sinks.total_inbound(),
+            sent: self.sinks.total_outbound(),
+        }
+    }
+}
+
+/// Authenticated and multiplexed transport the swarm is built with
+pub type BoxedTransport = Boxed<(PeerId, StreamMuxerBox)>;
+
+/// Builds the transport of the swarm from the node keypair and the default transport,
+/// either wrapping or replacing it, called on each (re)start of the event loop
+pub type TransportHook = Arc<
+    dyn Fn(&Keypair, BoxedTransport) -> Result<BoxedTransport, Box<dyn Error + Send + Sync>>
+        + Send
+        + Sync,
+>;
+
+// Reads the PEM encoded certificate chain and private key of the WSS listener,
+// or generates a self-signed certificate for the development setups
+fn wss_tls_config(cfg: &WssConfig) -> Result<websocket::tls::Config> {
+    let (Some(cert_path), Some(key_path)) = (&cfg.cert_path, &cfg.key_path) else {
+        warn!("WSS certificate is not set, using a self-signed one for localhost.");
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .context("Failed to generate self-signed WSS certificate")?;
+        let key = websocket::tls::PrivateKey::new(cert.serialize_private_key_der());
+        let cert = websocket::tls::Certificate::new(cert.serialize_der()?);
+        return Ok(websocket::tls::Config::new(key, [cert])?);
     };
 
-    // create new Kademlia Memory Store
-    let kad_store = MemoryStore::new(id_keys.public().to_peer_id());
-    // create Kademlia Config
-    let mut kad_cfg = kad::Config::default();
-    kad_cfg
-        .set_query_timeout(cfg.kademlia.query_timeout)
-        .set_protocol_names(vec![cfg.kademlia.protocol_name]);
-
-    // build the Swarm, connecting the lower transport logic with the
-    // higher layer network behaviour logic
-    let tokio_swarm = SwarmBuilder::with_existing_identity(id_keys.clone()).with_tokio();
-
-    let mut swarm;
-
-    let behaviour = |key: &identity::Keypair| {
-        Ok(Behaviour {
-            kademlia: kad::Behaviour::with_config(key.public().to_peer_id(), kad_store, kad_cfg),
-            identify: identify::Behaviour::new(identify_cfg),
-            auto_nat: autonat::Behaviour::new(local_peer_id, autonat_cfg),
-            ping: ping::Behaviour::new(ping::Config::new()),
-            blocked_peers: allow_block_list::Behaviour::default(),
-        })
+    let mut cert_file = std::io::BufReader::new(
+        std::fs::File::open(cert_path)
+            .context(format!("Failed to open WSS certificate {cert_path}"))?,
+    );
+    let certs: Vec<_> = rustls_pemfile::certs(&mut cert_file)
+        .context(format!("Failed to parse WSS certificate {cert_path}"))?
+        .into_iter()
+        .map(websocket::tls::Certificate::new)
+        .collect();
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in WSS certificate {cert_path}");
+    }
+
+    let mut key_file = std::io::BufReader::new(
+        std::fs::File::open(key_path).context(format!("Failed to open WSS key {key_path}"))?,
+    );
+    let key = loop {
+        match rustls_pemfile::read_one(&mut key_file)
+            .context(format!("Failed to parse WSS key {key_path}"))?
+        {
+            Some(
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key),
+            ) => break key,
+            Some(_) => continue,
+            None => anyhow::bail!("No private key found in WSS key {key_path}"),
+        }
     };
+    Ok(websocket::tls::Config::new(
+        websocket::tls::PrivateKey::new(key),
+        certs,
+    )?)
+}
 
-    if is_ws_transport {
-        swarm = tokio_swarm
-            .with_websocket(noise::Config::new, yamux::Config::default)
-            .await?
-            .with_behaviour(behaviour)?
-            .build()
+// Builds authenticated and multiplexed transport, either TCP or websocket, with DNS resolution,
+// along with QUIC and the secure websocket if enabled.
+// Noise handshakes of the TCP and websocket connections fail unless both peers use the same prologue,
+// while QUIC connections are authenticated by TLS instead.
+// Yamux connections opening more substreams than allowed are closed, while QUIC peers are kept from opening them.
+// It is built upfront instead of through the swarm builder shortcuts,
+// since those don't allow the bandwidth logging of websocket and DNS transports.
+fn build_transport(
+    key: &Keypair,
+    is_ws_transport: bool,
+    is_quic_transport: bool,
+    cfg: &LibP2PConfig,
+) -> Result<BoxedTransport, Box<dyn Error + Send + Sync>> {
+    let noise_cfg = || -> Result<noise::Config, noise::Error> {
+        Ok(noise::Config::new(key)?.with_prologue(cfg.noise_prologue.clone()))
+    };
+    let yamux_cfg = || {
+        let mut yamux_cfg = yamux::Config::default();
+        yamux_cfg.set_max_num_streams(cfg.max_substreams_per_connection);
+        yamux_cfg
+    };
+    let transport = if is_ws_transport {
+        let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default());
+        websocket::WsConfig::new(dns::tokio::Transport::system(tcp_transport)?)
+            .upgrade(Version::V1Lazy)
+            .authenticate(noise_cfg()?)
+            .multiplex(yamux_cfg())
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed()
     } else {
-        swarm = tokio_swarm
-            .with_tcp(
-                tcp::Config::default().port_reuse(false).nodelay(false),
-                noise::Config::new,
-                yamux::Config::default,
-            )?
-            .with_dns()?
-            .with_behaviour(behaviour)?
-            .build()
+        let tcp_cfg = tcp::Config::default().port_reuse(false).nodelay(false);
+        dns::tokio::Transport::system(tcp::tokio::Transport::new(tcp_cfg))?
+            .upgrade(Version::V1Lazy)
+            .authenticate(noise_cfg()?)
+            .multiplex(yamux_cfg())
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed()
+    };
+    // WSS goes first, since the plain websocket transport doesn't accept the /wss addresses without the TLS config
+    let transport = match &cfg.wss {
+        Some(wss) => {
+            let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default());
+            let mut wss_transport =
+                websocket::WsConfig::new(dns::tokio::Transport::system(tcp_transport)?);
+            wss_transport.set_tls_config(wss_tls_config(wss)?);
+            wss_transport
+                .upgrade(Version::V1Lazy)
+                .authenticate(noise_cfg()?)
+                .multiplex(yamux_cfg())
+                .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+                .or_transport(transport)
+                .map(|output, _| output.into_inner())
+                .boxed()
+        }
+        None => transport,
+    };
+    if !is_quic_transport {
+        return Ok(transport);
+    }
+    // QUIC goes first, since DNS transport accepts all the addresses, failing only once dialed
+    let mut quic_cfg = quic::Config::new(key);
+    quic_cfg.max_concurrent_stream_limit = cfg
+        .max_substreams_per_connection
+        .try_into()
+        .unwrap_or(u32::MAX);
+    let quic_transport = quic::tokio::Transport::new(quic_cfg)
+        .map(|(peer_id, connection), _| (peer_id, StreamMuxerBox::new(connection)));
+    Ok(quic_transport
+        .or_transport(transport)
+        .map(|output, _| output.into_inner())
+        .boxed())
+}
+
+/// Returned by [init], stopping the event loop cooperatively, including the restarted ones.
+/// Listeners are closed first, and the event loop keeps running until the pending queries are finished,
+/// or the shutdown grace period passes.
+#[derive(Clone)]
+pub struct NetworkShutdown {
+    requested: Arc<watch::Sender<bool>>,
+    stopped: Arc<watch::Sender<bool>>,
+}
+
+impl NetworkShutdown {
+    fn new() -> Self {
+        NetworkShutdown {
+            requested: Arc::new(watch::channel(false).0),
+            // nothing is running until the first event loop is started
+            stopped: Arc::new(watch::channel(true).0),
+        }
+    }
+
+    /// Requests the shutdown, completing once the event loop is stopped, or right away if it isn't running
+    pub async fn stop(&self) {
+        if *self.stopped.borrow() {
+            return;
+        }
+        self.requested.send_replace(true);
+        self.stopped().await;
     }
 
-    // enable Kademlila Server mode
-    swarm.behaviour_mut().kademlia.set_mode(Some(Mode::Server));
+    /// Completes once the running event loop is stopped, either on request or on the shutdown it was run with
+    pub async fn stopped(&self) {
+        // senders are owned by the event loop as well, so they aren't dropped while waiting
+        _ = self.stopped.subscribe().wait_for(|stopped| *stopped).await;
+    }
+
+    async fn requested(&self) {
+        _ = self
+            .requested
+            .subscribe()
+            .wait_for(|requested| *requested)
+            .await;
+    }
+
+    // Marks the (re)started event loop as running, clearing the shutdown requested for the previous one
+    fn start(&self) -> RunningGuard {
+        self.requested.send_replace(false);
+        self.stopped.send_replace(false);
+        RunningGuard(self.clone())
+    }
+}
+
+// Marks the event loop as stopped once dropped, even if its task panics or is aborted
+struct RunningGuard(NetworkShutdown);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.stopped.send_replace(true);
+    }
+}
+
+/// Network of the node, building the event loop with a new swarm on each (re)start,
+/// which keeps handling the commands of the same client
+pub struct Network<E: NetworkBehaviour = dummy::Behaviour> {
+    cfg: LibP2PConfig,
+    id_keys: Keypair,
+    is_ws_transport: bool,
+    is_quic_transport: bool,
+    geoip: Option<Arc<GeoIp>>,
+    command_receiver: Arc<Mutex<mpsc::Receiver<Command>>>,
+    events: broadcast::Sender<NetworkEvent>,
+    metrics: Arc<dyn Metrics>,
+    transport_hook: Option<TransportHook>,
+    extension: Extension<E>,
+    shutdown: NetworkShutdown,
+}
+
+impl<E: NetworkBehaviour> Network<E>
+where
+    E::ToSwarm: Debug,
+{
+    pub(crate) fn with_transport_hook(mut self, transport_hook: TransportHook) -> Self {
+        self.transport_hook = Some(transport_hook);
+        self
+    }
+
+    pub(crate) fn with_extension<X: NetworkBehaviour>(self, extension: Extension<X>) -> Network<X> {
+        Network {
+            cfg: self.cfg,
+            id_keys: self.id_keys,
+            is_ws_transport: self.is_ws_transport,
+            is_quic_transport: self.is_quic_transport,
+            geoip: self.geoip,
+            command_receiver: self.command_receiver,
+            events: self.events,
+            metrics: self.metrics,
+            transport_hook: self.transport_hook,
+            extension,
+            shutdown: self.shutdown,
+        }
+    }
+
+    /// Builds the event loop, once the previous one is stopped
+    pub async fn event_loop(&self) -> Result<EventLoop<E>> {
+        let cfg = self.cfg.clone();
+        let local_peer_id = PeerId::from(self.id_keys.public());
+
+        // create Identify Protocol Config
+        let identify_cfg =
+            identify::Config::new(cfg.identify.protocol_version.clone(), self.id_keys.public())
+                .with_agent_version(cfg.identify.agent_version.to_string());
+
+        // create AutoNAT Server Config
+        let autonat_cfg = autonat::Config {
+            only_global_ips: cfg.autonat.only_global_ips,
+            throttle_clients_global_max: cfg.autonat.throttle_clients_global_max,
+            throttle_clients_peer_max: cfg.autonat.throttle_clients_peer_max,
+            throttle_clients_period: cfg.autonat.throttle_clients_period,
+            boot_delay: cfg.autonat.boot_delay,
+            refresh_interval: cfg.autonat.refresh_interval,
+            retry_interval: cfg.autonat.retry_interval,
+            ..Default::default()
+        };
+
+        // create new Kademlia Memory Store
+        let kad_store = MemoryStore::with_config(local_peer_id, cfg.kademlia.store.clone());
+        // create Kademlia Config
+        let mut kad_cfg = kad::Config::default();
+        kad_cfg
+            .set_query_timeout(cfg.kademlia.query_timeout)
+            .set_protocol_names(cfg.kademlia.protocol_names())
+            .disjoint_query_paths(cfg.kademlia.is_disjoint_query_paths)
+            .set_parallelism(cfg.kademlia.query_parallelism)
+            .set_replication_factor(cfg.kademlia.replication_factor)
+            // records put by other peers are stored by the event loop, counting the rejected ones
+            .set_record_filtering(kad::StoreInserts::FilterBoth);
+
+        // build the Swarm, connecting the lower transport logic with the
+        // higher layer network behaviour logic
+        let behaviour = |key: &identity::Keypair| {
+            Ok(Behaviour {
+                kademlia: kad::Behaviour::with_config(
+                    key.public().to_peer_id(),
+                    kad_store,
+                    kad_cfg,
+                ),
+                identify: identify::Behaviour::new(identify_cfg),
+                auto_nat: autonat::Behaviour::new(local_peer_id, autonat_cfg),
+                ping: ping::Behaviour::new(ping::Config::new()),
+                blocked_peers: allow_block_list::Behaviour::default(),
+                disconnect: disconnect::behaviour(),
+                peer_exchange: peer_exchange::behaviour(),
+                bootstrap_info: bootstrap_info::behaviour(),
+                keep_alive: Default::default(),
+                extension: (self.extension.behaviour)(key),
+            })
+        };
+
+        // bandwidth logging is deprecated in favour of the Prometheus registry based metrics,
+        // which don't allow reading the totals back for the other telemetry backends
+        #[allow(deprecated)]
+        let (swarm_builder, bandwidth_sinks) =
+            SwarmBuilder::with_existing_identity(self.id_keys.clone())
+                .with_tokio()
+                .with_other_transport(|key| {
+                    let transport =
+                        build_transport(key, self.is_ws_transport, self.is_quic_transport, &cfg)?;
+                    match &self.transport_hook {
+                        Some(transport_hook) => transport_hook(key, transport),
+                        None => Ok(transport),
+                    }
+                })?
+                .with_bandwidth_logging();
+
+        let mut swarm = swarm_builder.with_behaviour(behaviour)?.build();
+
+        // enable Kademlila Server mode
+        swarm.behaviour_mut().kademlia.set_mode(Some(Mode::Server));
+
+        // receiver is released by the previous event loop once stopped, even if it panicked
+        let command_receiver = self.command_receiver.clone().lock_owned().await;
+
+        Ok(EventLoop::new(
+            swarm,
+            command_receiver,
+            Bandwidth {
+                sinks: bandwidth_sinks,
+            },
+            self,
+        ))
+    }
+}
+
+pub(crate) fn init(
+    cfg: LibP2PConfig,
+    id_keys: Keypair,
+    is_ws_transport: bool,
+    is_quic_transport: bool,
+    geoip: Option<GeoIp>,
+    metrics: Arc<dyn Metrics>,
+) -> (Client, Network, NetworkShutdown) {
+    info!(
+        "Local Peer ID: {:?}. Public key: {:?}.",
+        PeerId::from(id_keys.public()),
+        id_keys.public()
+    );
 
     // create channel for Event Loop Commands
-    let (command_sender, command_receiver) = mpsc::channel::<Command>(1000);
+    let (command_sender, command_receiver) =
+        mpsc::channel::<Command>(cfg.client.command_channel_capacity.max(1));
+    let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+    let shutdown = NetworkShutdown::new();
 
-    Ok((
-        Client::new(command_sender),
-        EventLoop::new(swarm, command_receiver, cfg.bootstrap_interval),
-    ))
+    (
+        Client::new(command_sender, events.clone(), cfg.client),
+        Network {
+            cfg,
+            id_keys,
+            is_ws_transport,
+            is_quic_transport,
+            geoip: geoip.map(Arc::new),
+            command_receiver: Arc::new(Mutex::new(command_receiver)),
+            events,
+            metrics,
+            transport_hook: None,
+            extension: Default::default(),
+            shutdown: shutdown.clone(),
+        },
+        shutdown,
+    )
 }
 
-pub fn keypair(cfg: LibP2PConfig) -> Result<(Keypair, String)> {
-    let keypair = match cfg.secret_key {
+fn keypair_from_hex(key: &str) -> Result<Keypair> {
+    let mut decoded_key = [0u8; 32];
+    hex::decode_to_slice(key, &mut decoded_key).context("Error decoding secret key.")?;
+    Keypair::ed25519_from_bytes(decoded_key).context("Error importing secret key.")
+}
+
+fn keypair_from_secret(secret_key: SecretKey) -> Result<Keypair> {
+    match secret_key {
         // if seed is provided, generate secret key from seed
-        Some(SecretKey::Seed { seed }) => {
+        SecretKey::Seed { seed } => {
             let digest = multihash::Sha3_256::digest(seed.as_bytes());
-            Keypair::ed25519_from_bytes(digest).context("Error generating secret key from seed")?
+            Keypair::ed25519_from_bytes(digest).context("Error generating secret key from seed")
         }
         // import secret key, if provided
-        Some(SecretKey::Key { key }) => {
-            let mut decoded_key = [0u8; 32];
-            hex::decode_to_slice(key.into_bytes(), &mut decoded_key)
-                .context("Error decoding secret key from config.")?;
-            Keypair::ed25519_from_bytes(decoded_key).context("Error importing secret key.")?
+        SecretKey::Key { key } => keypair_from_hex(&key),
+        SecretKey::KeyFile { path } => {
+            let key = std::fs::read_to_string(&path)
+                .context(format!("Failed to read secret key file {path}"))?;
+            keypair_from_hex(key.trim()).context(format!("Invalid secret key in {path}"))
+        }
+        SecretKey::Env { var } => {
+            let key = std::env::var(&var).context(format!(
+                "Failed to read secret key from environment variable {var}"
+            ))?;
+            keypair_from_hex(key.trim()).context(format!("Invalid secret key in {var}"))
+        }
+        // identity files hold the secret key itself, so they never point to the other ones
+        SecretKey::Identity(_) => anyhow::bail!("Identity file must hold the secret key"),
+    }
+}
+
+pub fn keypair(cfg: LibP2PConfig) -> Result<(Keypair, String)> {
+    let keypair = match cfg.secret_key {
+        // secret key is loaded from the identity file, generated on the first start,
+        // so the peer ID doesn't change on restarts
+        Some(SecretKey::Identity(_)) | None => match read_identity(&cfg.identity_path)? {
+            Some(keypair) => keypair,
+            None => {
+                let keypair = generate_identity(&cfg.identity_path)?;
+                info!("Generated new secret key into {}.", cfg.identity_path);
+                keypair
+            }
+        },
+        Some(secret_key) => {
+            keypair_from_secret(secret_key).context("Invalid secret key in config.")?
         }
-        // if neither seed nor secret key were provided,
-        // generate secret key from random seed
-        None => Keypair::generate_ed25519(),
     };
 
     let peer_id = PeerId::from(keypair.public()).to_string();
     Ok((keypair, peer_id))
 }
+
+/// Reads the keypair from the identity file, if the file exists
+pub fn read_identity(path: &str) -> Result<Option<Keypair>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context(format!("Failed to read identity file {path}")),
+    };
+    let identity: IdentityFile =
+        toml::from_str(&content).context(format!("Failed to parse identity file {path}"))?;
+    let Some(secret_key) = identity.secret_key() else {
+        anyhow::bail!("Identity file {path} holds neither `key` nor `avail_secret_seed_phrase`");
+    };
+    let keypair =
+        keypair_from_secret(secret_key).context(format!("Invalid secret key in {path}"))?;
+    Ok(Some(keypair))
+}
+
+/// Generates a new ed25519 keypair, writing its secret key to the identity file at the given path
+pub fn generate_identity(path: &str) -> Result<Keypair> {
+    let keypair = Keypair::generate_ed25519();
+    write_identity(path, &keypair)?;
+    Ok(keypair)
+}
+
+// Identity file is created readable by the owner only, and existing one is never overwritten
+fn write_identity(path: &str, keypair: &Keypair) -> Result<()> {
+    let secret = keypair
+        .clone()
+        .try_into_ed25519()
+        .context("Only ed25519 keys can be written to the identity file")?
+        .secret();
+    let identity = IdentityFile {
+        key: Some(hex::encode(secret.as_ref())),
+        ..Default::default()
+    };
+    let content = toml::to_string(&identity).context("Failed to serialize identity")?;
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .context(format!("Failed to create identity file {path}"))?;
+    file.write_all(content.as_bytes())
+        .context(format!("Failed to write identity file {path}"))
+}
@@ -0,0 +1,98 @@
+//! Protocol serving the network metadata of the node, so the clients can check they dialed
+//! a bootstrap node of the right network before joining the DHT.
+//!
+//! Request is an empty JSON object, answered with the JSON encoded [BootstrapInfo].
+
+use async_trait::async_trait;
+use libp2p::{
+    futures::{AsyncRead, AsyncWrite},
+    request_response::{self, ProtocolSupport},
+    StreamProtocol,
+};
+use serde::{Deserialize, Serialize};
+use std::{io, time::Duration};
+
+use super::peer_exchange::{read_json, write_json};
+
+pub const BOOTSTRAP_INFO_PROTOCOL: StreamProtocol = StreamProtocol::new("/avail/bootstrap-info/1");
+const BOOTSTRAP_INFO_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REQUEST_BYTES: u64 = 1024;
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BootstrapInfoRequest {}
+
+/// Network metadata of the bootstrap node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapInfo {
+    pub genesis_hash: String,
+    pub network: String,
+    /// Kademlia protocol names served by the node, the preferred one first
+    pub kad_protocols: Vec<String>,
+    /// Minimum release version of the light clients, older ones are disconnected
+    pub minimum_light_client_version: String,
+    pub minimum_bootstrap_version: String,
+    pub routing_table_peers: usize,
+    pub connected_peers: usize,
+    /// Multiaddresses of the other bootstrap nodes, both the configured and the connected ones
+    pub bootstraps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BootstrapInfoCodec;
+
+#[async_trait]
+impl request_response::Codec for BootstrapInfoCodec {
+    type Protocol = StreamProtocol;
+    type Request = BootstrapInfoRequest;
+    type Response = BootstrapInfo;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_json(io, MAX_REQUEST_BYTES).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_json(io, MAX_RESPONSE_BYTES).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_json(io, &request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        response: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_json(io, &response).await
+    }
+}
+
+pub fn behaviour() -> request_response::Behaviour<BootstrapInfoCodec> {
+    request_response::Behaviour::new(
+        [(BOOTSTRAP_INFO_PROTOCOL, ProtocolSupport::Inbound)],
+        request_response::Config::default().with_request_timeout(BOOTSTRAP_INFO_TIMEOUT),
+    )
+}
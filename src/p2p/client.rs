@@ -1,50 +1,186 @@
-use anyhow::{Context, Result};
-use libp2p::{Multiaddr, PeerId};
-use tokio::sync::{mpsc, oneshot};
+use libp2p::{
+    futures::{stream, Stream, StreamExt},
+    kad, Multiaddr, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    sync::{broadcast, mpsc, mpsc::error::TrySendError, oneshot},
+    time,
+};
+
+use crate::{
+    telemetry::{
+        AutoNatThrottleUsage, BootstrapKind, ConnectionCounts, DistinctCounts, NatStatus,
+        SeenWindow, TransferredBytes,
+    },
+    types::ClientConfig,
+};
+
+pub type Result<T, E = NetworkError> = std::result::Result<T, E>;
+
+// number of routing table entries fetched by a single command, when all of them are collected
+const DHT_ENTRIES_BATCH_SIZE: usize = 100;
 
 #[derive(Clone)]
 pub struct Client {
     command_sender: mpsc::Sender<Command>,
+    events: broadcast::Sender<NetworkEvent>,
+    cfg: ClientConfig,
+    // non-critical commands rejected on the full command channel, since the previous metrics interval
+    overloaded_commands: Arc<AtomicU64>,
+}
+
+/// Failure of the network client command, distinguishing the unavailable event loop from the failures on the network
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    /// Event loop is stopped, either before receiving the command or with the command pending
+    #[error("{command} command failed, network event loop is unavailable")]
+    ChannelClosed { command: &'static str },
+    /// Command channel is full, so the non-critical command is rejected instead of waiting for the capacity
+    #[error("{command} command rejected, network event loop is overloaded")]
+    Overloaded { command: &'static str },
+    #[error("{command} command timed out after {timeout:?}")]
+    Timeout {
+        command: &'static str,
+        timeout: Duration,
+    },
+    /// Routing table is empty, so there are no peers to query
+    #[error("No known peers")]
+    NoKnownPeers,
+    #[error("Failed to dial {address}: {reason}")]
+    DialFailure { address: Multiaddr, reason: String },
+    #[error("Failed to listen on {address}: {reason}")]
+    ListenFailure { address: Multiaddr, reason: String },
+    #[error("Kademlia {query} query failed: {reason}")]
+    QueryFailed { query: &'static str, reason: String },
+}
+
+impl From<kad::NoKnownPeers> for NetworkError {
+    fn from(_: kad::NoKnownPeers) -> Self {
+        NetworkError::NoKnownPeers
+    }
 }
 
 impl Client {
-    pub fn new(command_sender: mpsc::Sender<Command>) -> Self {
-        Self { command_sender }
+    pub fn new(
+        command_sender: mpsc::Sender<Command>,
+        events: broadcast::Sender<NetworkEvent>,
+        cfg: ClientConfig,
+    ) -> Self {
+        Self {
+            command_sender,
+            events,
+            cfg,
+            overloaded_commands: Default::default(),
+        }
     }
 
-    pub async fn start_listening(&self, addr: Multiaddr) -> Result<()> {
+    /// Subscribes to the events published by the event loop, including the ones of the restarted event loops.
+    /// Subscribers lagging behind miss the oldest events, as reported by the receiver.
+    pub fn subscribe(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.events.subscribe()
+    }
+
+    // Sends the command built with the response sender, failing with the timeout unless the response is received in time
+    async fn execute<T>(
+        &self,
+        command: &'static str,
+        timeout: Duration,
+        build: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T> {
         let (response_sender, response_receiver) = oneshot::channel();
-        self.command_sender
-            .send(Command::StartListening {
-                addr,
-                response_sender,
-            })
+        let round_trip = async {
+            self.command_sender
+                .send(build(response_sender))
+                .await
+                .map_err(|_| NetworkError::ChannelClosed { command })?;
+            response_receiver
+                .await
+                .map_err(|_| NetworkError::ChannelClosed { command })
+        };
+        time::timeout(timeout, round_trip)
             .await
-            .context("Command receiver should not be dropped")?;
-        response_receiver
-            .await
-            .context("Sender not to be dropped")?
+            .map_err(|_| NetworkError::Timeout { command, timeout })?
     }
 
-    pub async fn add_address(&self, peer_id: PeerId, multiaddr: Multiaddr) -> Result<()> {
+    // Executes the non-critical command, e.g. the metrics and status getters,
+    // failing right away if the command channel is full instead of waiting for the capacity
+    async fn try_command<T>(
+        &self,
+        command: &'static str,
+        build: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.command_sender
-            .send(Command::AddAddress {
-                peer_id,
-                multiaddr,
-                response_sender,
-            })
+            .try_send(build(response_sender))
+            .map_err(|err| match err {
+                TrySendError::Full(_) => {
+                    self.overloaded_commands.fetch_add(1, Ordering::Relaxed);
+                    NetworkError::Overloaded { command }
+                }
+                TrySendError::Closed(_) => NetworkError::ChannelClosed { command },
+            })?;
+        let timeout = self.cfg.command_timeout;
+        time::timeout(timeout, response_receiver)
             .await
-            .context("Command receiver should not be dropped.")?;
-        response_receiver
-            .await
-            .context("Sender not to be dropped.")?
+            .map_err(|_| NetworkError::Timeout { command, timeout })?
+            .map_err(|_| NetworkError::ChannelClosed { command })
+    }
+
+    // Executes the command handled by the event loop on its own
+    async fn command<T>(
+        &self,
+        command: &'static str,
+        build: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T> {
+        self.execute(command, self.cfg.command_timeout, build).await
+    }
+
+    // Executes the command waiting on the other peers, e.g. on the Kademlia queries
+    async fn query<T>(
+        &self,
+        command: &'static str,
+        build: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T> {
+        self.execute(command, self.cfg.query_timeout, build).await
+    }
+
+    pub async fn start_listening(&self, addr: Multiaddr) -> Result<()> {
+        self.command("StartListening", |response_sender| {
+            Command::StartListening {
+                addr,
+                response_sender,
+            }
+        })
+        .await?
+    }
+
+    pub async fn add_address(&self, peer_id: PeerId, multiaddr: Multiaddr) -> Result<()> {
+        self.command("AddAddress", |response_sender| Command::AddAddress {
+            peer_id,
+            multiaddr,
+            response_sender,
+        })
+        .await?
     }
 
     pub async fn bootstrap(&self) -> Result<()> {
         // bootstrapping is impossible on an empty DHT table
         // at least one node is required to be known, so check
-        let counted_peers = self.count_dht_entries().await?;
+        let counted_peers = self
+            .command("CountDHTPeers", |response_sender| Command::CountDHTPeers {
+                response_sender,
+            })
+            .await?;
         // for a bootstrap to succeed, we need at least 1 peer in our DHT
         if counted_peers < 1 {
             // we'll have to wait, until some one successfully connects us
@@ -54,47 +190,427 @@ impl Client {
         }
 
         // proceed to bootstrap only if connected with someone
-        let (boot_res_sender, boot_res_receiver) = oneshot::channel();
-        self.command_sender
-            .send(Command::Bootstrap {
-                response_sender: boot_res_sender,
-            })
-            .await
-            .context("Command receiver should not be dropped while bootstrapping.")?;
-        boot_res_receiver
-            .await
-            .context("Sender not to be dropped while bootstrapping.")?
+        self.query("Bootstrap", |response_sender| Command::Bootstrap {
+            response_sender,
+        })
+        .await?
     }
 
+    // Waits for the connection without the deadline, since the node may not be dialed for a while
     async fn wait_connection(&self, peer_id: Option<PeerId>) -> Result<(PeerId, Multiaddr)> {
         let (connection_res_sender, connection_res_receiver) = oneshot::channel();
+        let command = "WaitConnection";
         self.command_sender
             .send(Command::WaitConnection {
                 peer_id,
                 response_sender: connection_res_sender,
             })
             .await
-            .context("Command receiver should not be dropped while waiting on connection.")?;
-        Ok(connection_res_receiver.await?)
+            .map_err(|_| NetworkError::ChannelClosed { command })?;
+        connection_res_receiver
+            .await
+            .map_err(|_| NetworkError::ChannelClosed { command })
     }
 
     pub async fn count_dht_entries(&self) -> Result<usize> {
-        let (response_sender, response_receiver) = oneshot::channel();
-        self.command_sender
-            .send(Command::CountDHTPeers { response_sender })
-            .await
-            .context("Command receiver not to be dropped.")?;
-        response_receiver.await.context("Sender not to be dropped.")
+        self.try_command("CountDHTPeers", |response_sender| Command::CountDHTPeers {
+            response_sender,
+        })
+        .await
+    }
+
+    /// Returns number of entries per non-empty k-bucket, keyed by bucket index
+    pub async fn count_dht_bucket_entries(&self) -> Result<BTreeMap<u32, usize>> {
+        self.try_command("CountDHTBucketPeers", |response_sender| {
+            Command::CountDHTBucketPeers { response_sender }
+        })
+        .await
+    }
+
+    /// Returns number of connected peers per country, or `None` if GeoIP lookups are disabled
+    pub async fn count_peers_per_country(&self) -> Result<Option<BTreeMap<String, usize>>> {
+        self.try_command("CountPeersPerCountry", |response_sender| {
+            Command::CountPeersPerCountry { response_sender }
+        })
+        .await
+    }
+
+    /// Returns number of established connections, for every direction and transport
+    pub async fn count_connections(&self) -> Result<ConnectionCounts> {
+        self.try_command("CountConnections", |response_sender| {
+            Command::CountConnections { response_sender }
+        })
+        .await
+    }
+
+    pub async fn count_blocked_peers(&self) -> Result<usize> {
+        self.try_command("CountBlockedPeers", |response_sender| {
+            Command::CountBlockedPeers { response_sender }
+        })
+        .await
+    }
+
+    pub async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        self.try_command("GetBlockedPeers", |response_sender| {
+            Command::GetBlockedPeers { response_sender }
+        })
+        .await
+    }
+
+    /// Returns number of distinct peers and remote IPs connected within each of the rolling windows
+    pub async fn count_distinct_seen(&self) -> Result<BTreeMap<SeenWindow, DistinctCounts>> {
+        self.try_command("CountDistinctSeen", |response_sender| {
+            Command::CountDistinctSeen { response_sender }
+        })
+        .await
+    }
+
+    pub async fn get_nat_status(&self) -> Result<NatStatus> {
+        self.try_command("GetNatStatus", |response_sender| Command::GetNatStatus {
+            response_sender,
+        })
+        .await
+    }
+
+    /// Returns peak usage of the AutoNAT server throttling limits since the previous call
+    pub async fn get_autonat_throttle_usage(&self) -> Result<AutoNatThrottleUsage> {
+        self.try_command("GetAutoNatThrottleUsage", |response_sender| {
+            Command::GetAutoNatThrottleUsage { response_sender }
+        })
+        .await
+    }
+
+    pub async fn get_transferred_bytes(&self) -> Result<TransferredBytes> {
+        self.try_command("GetTransferredBytes", |response_sender| {
+            Command::GetTransferredBytes { response_sender }
+        })
+        .await
     }
 
     pub async fn get_multiaddress(&self) -> Result<Option<Multiaddr>> {
-        let (response_sender, response_receiver) = oneshot::channel();
-        self.command_sender
-            .send(Command::GetMultiaddress { response_sender })
+        self.try_command("GetMultiaddress", |response_sender| {
+            Command::GetMultiaddress { response_sender }
+        })
+        .await
+    }
+
+    pub async fn get_closest_peers(&self, peer_id: PeerId) -> Result<Vec<PeerId>> {
+        self.query("GetClosestPeers", |response_sender| {
+            Command::GetClosestPeers {
+                peer_id,
+                response_sender,
+            }
+        })
+        .await?
+    }
+
+    /// Number of commands waiting to be handled by the event loop
+    pub fn command_queue_depth(&self) -> usize {
+        self.command_sender.max_capacity() - self.command_sender.capacity()
+    }
+
+    /// Share of the command channel capacity taken by the queued commands, from 0 to 1
+    pub fn command_queue_saturation(&self) -> f64 {
+        self.command_queue_depth() as f64 / self.command_sender.max_capacity() as f64
+    }
+
+    /// Returns number of the commands rejected as overloaded since the previous call
+    pub fn take_overloaded_commands(&self) -> u64 {
+        self.overloaded_commands.swap(0, Ordering::Relaxed)
+    }
+
+    /// Checks the event loop is alive, waiting for the capacity of the full command channel,
+    /// since the busy event loop is still handling the commands
+    pub async fn ping(&self) -> Result<()> {
+        self.command("Ping", |response_sender| Command::Ping { response_sender })
             .await
-            .context("Command receiver not to be dropped.")?;
-        response_receiver.await.context("Sender not to be dropped.")
     }
+
+    pub async fn get_health_state(&self) -> Result<HealthState> {
+        self.command("GetHealthState", |response_sender| {
+            Command::GetHealthState { response_sender }
+        })
+        .await
+    }
+
+    /// Returns the tracked external addresses, the confirmed ones first, ordered by the confidence
+    pub async fn get_external_addresses(&self) -> Result<Vec<ExternalAddress>> {
+        self.try_command("GetExternalAddresses", |response_sender| {
+            Command::GetExternalAddresses { response_sender }
+        })
+        .await
+    }
+
+    /// Returns the number of entries per k-bucket and the established connections, both taken at once
+    pub async fn get_topology(&self) -> Result<NetworkTopology> {
+        self.command("GetTopology", |response_sender| Command::GetTopology {
+            response_sender,
+        })
+        .await
+    }
+
+    /// Tracks the external addresses saved before as unconfirmed, keeping their confidence,
+    /// until they are confirmed again by AutoNAT
+    pub async fn restore_external_addresses(&self, addresses: Vec<ExternalAddress>) -> Result<()> {
+        self.command("RestoreExternalAddresses", |response_sender| {
+            Command::RestoreExternalAddresses {
+                addresses,
+                response_sender,
+            }
+        })
+        .await
+    }
+
+    pub async fn get_startup_summary(&self) -> Result<StartupSummary> {
+        self.try_command("GetStartupSummary", |response_sender| {
+            Command::GetStartupSummary { response_sender }
+        })
+        .await
+    }
+
+    pub async fn dial(&self, multiaddr: Multiaddr) -> Result<()> {
+        self.query("Dial", |response_sender| Command::Dial {
+            multiaddr,
+            response_sender,
+        })
+        .await?
+    }
+
+    pub async fn block_peer(&self, peer_id: PeerId) -> Result<()> {
+        self.command("BlockPeer", |response_sender| Command::BlockPeer {
+            peer_id,
+            response_sender,
+        })
+        .await
+    }
+
+    pub async fn set_bootstrap_interval(&self, interval: Duration) -> Result<()> {
+        self.command("SetBootstrapInterval", |response_sender| {
+            Command::SetBootstrapInterval {
+                interval,
+                response_sender,
+            }
+        })
+        .await
+    }
+
+    pub async fn get_query_latencies(&self) -> Result<QueryLatencies> {
+        self.try_command("GetQueryLatencies", |response_sender| {
+            Command::GetQueryLatencies { response_sender }
+        })
+        .await
+    }
+
+    /// Runs the maintenance, pruning the routing table peers not connected within `stale_peer_timeout`
+    pub async fn run_maintenance(
+        &self,
+        stale_peer_timeout: Duration,
+    ) -> Result<MaintenanceSummary> {
+        self.command("RunMaintenance", |response_sender| {
+            Command::RunMaintenance {
+                stale_peer_timeout,
+                response_sender,
+            }
+        })
+        .await
+    }
+
+    /// Returns all the routing table entries, fetched in batches
+    pub async fn get_dht_entries(&self) -> Result<Vec<DHTEntry>> {
+        let mut entries = vec![];
+        let batches = self.stream_dht_entries(DHT_ENTRIES_BATCH_SIZE);
+        tokio::pin!(batches);
+        while let Some(batch) = batches.next().await {
+            entries.extend(batch?);
+        }
+        Ok(entries)
+    }
+
+    /// Streams the routing table entries in batches of up to `batch_size` entries, each fetched by a separate command,
+    /// so the event loop keeps handling the other events in between. Entries moved within the routing table
+    /// in between the batches may be skipped or repeated. Stream ends after the first failed batch.
+    pub fn stream_dht_entries(
+        &self,
+        batch_size: usize,
+    ) -> impl Stream<Item = Result<Vec<DHTEntry>>> + Send + 'static {
+        let client = self.clone();
+        stream::unfold(Some(DHTCursor::default()), move |cursor| {
+            let client = client.clone();
+            async move {
+                let cursor = cursor?;
+                let batch = client
+                    .try_command("GetDHTEntries", |response_sender| Command::GetDHTEntries {
+                        cursor,
+                        batch_size: batch_size.max(1),
+                        response_sender,
+                    })
+                    .await;
+                Some(match batch {
+                    Ok((entries, next)) => (Ok(entries), next),
+                    Err(err) => (Err(err), None),
+                })
+            }
+        })
+    }
+}
+
+/// Network event published by the event loop to the [Client::subscribe] subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NetworkEvent {
+    /// First connection with the peer established
+    PeerConnected {
+        peer_id: String,
+        address: String,
+    },
+    /// Last connection with the peer closed
+    PeerDisconnected {
+        peer_id: String,
+    },
+    RoutingUpdated {
+        peer_id: String,
+        is_new_peer: bool,
+        addresses: Vec<String>,
+    },
+    /// Peer removed from the routing table, either evicted or rejected
+    RoutingRemoved {
+        peer_id: String,
+    },
+    /// Bootstrap finished, either with all the buckets refreshed or failed
+    BootstrapCompleted {
+        kind: BootstrapKind,
+        is_ok: bool,
+    },
+    NatStatusChanged {
+        old: NatStatus,
+        new: NatStatus,
+    },
+    /// Event of the extension behaviour, as mapped by the embedder
+    Extension {
+        data: serde_json::Value,
+    },
+}
+
+/// Event loop state relevant for the node health checks
+#[derive(Debug, Clone)]
+pub struct HealthState {
+    pub is_startup_bootstrap_done: bool,
+    pub listeners: usize,
+    pub established_connections: u32,
+    /// Time since the last swarm event was handled
+    pub since_last_swarm_event: Duration,
+}
+
+/// Identity and addresses of the node, to be shared with the operators of the other nodes
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupSummary {
+    pub peer_id: String,
+    /// Confirmed external addresses first, followed by the listen addresses, all with `/p2p` suffix
+    pub multiaddrs: Vec<String>,
+    pub network: String,
+    pub kad_protocol: String,
+}
+
+/// External address of the node, either observed by the other peers over identify or confirmed by AutoNAT
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalAddress {
+    pub address: String,
+    /// Confirmed by the AutoNAT dial-backs, and not expired since
+    pub is_confirmed: bool,
+    /// Raised by each observation, and by each confirmation even more
+    pub confidence: u32,
+}
+
+/// Established connection of the node, enriched with the identify information known about the peer
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionEntry {
+    pub peer_id: String,
+    pub remote_address: String,
+    /// Either `inbound` or `outbound`
+    pub direction: &'static str,
+    pub transport: &'static str,
+    pub agent_version: Option<String>,
+    /// Time since the connection was established, in seconds
+    pub duration_secs: u64,
+}
+
+/// Routing table layout and the established connections of the node
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkTopology {
+    /// Number of entries per non-empty k-bucket, keyed by bucket index
+    pub buckets: BTreeMap<u32, usize>,
+    /// Established connections, the oldest first
+    pub connections: Vec<ConnectionEntry>,
+}
+
+/// Outcome of a single maintenance run
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceSummary {
+    /// Disconnected peers removed from the routing table, since they weren't connected for too long
+    pub stale_peers_pruned: usize,
+    /// Agent versions and locations dropped for the peers which are no longer known
+    pub peer_store_trimmed: usize,
+    /// Confirmed external addresses probed again over AutoNAT
+    pub external_addresses_probed: usize,
+    pub provider_records_republished: usize,
+    pub routing_table_peers: usize,
+}
+
+/// Latency percentiles of recently completed queries
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencySummary {
+    /// Total number of completed queries
+    pub count: u64,
+    pub p50_ms: Option<u128>,
+    pub p90_ms: Option<u128>,
+    pub p99_ms: Option<u128>,
+}
+
+impl LatencySummary {
+    /// Summarizes the sampled durations, out of `count` completed queries in total
+    pub(crate) fn new(count: u64, mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        // nearest-rank percentile
+        let percentile = |p: usize| {
+            let rank = (p * samples.len()).div_ceil(100);
+            samples.get(rank.saturating_sub(1)).map(Duration::as_millis)
+        };
+        LatencySummary {
+            count,
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+        }
+    }
+}
+
+/// Kademlia query latencies, per query type
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryLatencies {
+    pub bootstrap: LatencySummary,
+    pub get_closest_peers: LatencySummary,
+}
+
+/// Position of the next batch of the routing table entries, as the k-bucket index and the offset within the bucket
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DHTCursor {
+    pub(crate) bucket: usize,
+    pub(crate) offset: usize,
+}
+
+/// Routing table entry, enriched with the identify information known about the peer
+#[derive(Debug, Clone, Serialize)]
+pub struct DHTEntry {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+    pub is_connected: bool,
+    pub agent_version: Option<String>,
+    /// Country code of the connected peer, if GeoIP lookups are enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Autonomous system number of the connected peer, if GeoIP lookups are enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -118,7 +634,81 @@ pub enum Command {
     CountDHTPeers {
         response_sender: oneshot::Sender<usize>,
     },
+    CountDHTBucketPeers {
+        response_sender: oneshot::Sender<BTreeMap<u32, usize>>,
+    },
+    CountConnections {
+        response_sender: oneshot::Sender<ConnectionCounts>,
+    },
+    CountPeersPerCountry {
+        response_sender: oneshot::Sender<Option<BTreeMap<String, usize>>>,
+    },
+    CountBlockedPeers {
+        response_sender: oneshot::Sender<usize>,
+    },
+    GetBlockedPeers {
+        response_sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    CountDistinctSeen {
+        response_sender: oneshot::Sender<BTreeMap<SeenWindow, DistinctCounts>>,
+    },
+    GetNatStatus {
+        response_sender: oneshot::Sender<NatStatus>,
+    },
+    GetAutoNatThrottleUsage {
+        response_sender: oneshot::Sender<AutoNatThrottleUsage>,
+    },
+    GetTransferredBytes {
+        response_sender: oneshot::Sender<TransferredBytes>,
+    },
     GetMultiaddress {
         response_sender: oneshot::Sender<Option<Multiaddr>>,
     },
+    GetClosestPeers {
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<Result<Vec<PeerId>>>,
+    },
+    GetDHTEntries {
+        cursor: DHTCursor,
+        batch_size: usize,
+        response_sender: oneshot::Sender<(Vec<DHTEntry>, Option<DHTCursor>)>,
+    },
+    Ping {
+        response_sender: oneshot::Sender<()>,
+    },
+    GetHealthState {
+        response_sender: oneshot::Sender<HealthState>,
+    },
+    GetStartupSummary {
+        response_sender: oneshot::Sender<StartupSummary>,
+    },
+    GetExternalAddresses {
+        response_sender: oneshot::Sender<Vec<ExternalAddress>>,
+    },
+    GetTopology {
+        response_sender: oneshot::Sender<NetworkTopology>,
+    },
+    RestoreExternalAddresses {
+        addresses: Vec<ExternalAddress>,
+        response_sender: oneshot::Sender<()>,
+    },
+    Dial {
+        multiaddr: Multiaddr,
+        response_sender: oneshot::Sender<Result<()>>,
+    },
+    BlockPeer {
+        peer_id: PeerId,
+        response_sender: oneshot::Sender<()>,
+    },
+    SetBootstrapInterval {
+        interval: Duration,
+        response_sender: oneshot::Sender<()>,
+    },
+    GetQueryLatencies {
+        response_sender: oneshot::Sender<QueryLatencies>,
+    },
+    RunMaintenance {
+        stale_peer_timeout: Duration,
+        response_sender: oneshot::Sender<MaintenanceSummary>,
+    },
 }
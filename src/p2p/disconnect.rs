@@ -0,0 +1,95 @@
+//! Protocol sending the machine-readable reason to the rejected peers, right before their connections are closed.
+//!
+//! Request is a JSON encoded [DisconnectReason], answered with an empty response once received.
+
+use async_trait::async_trait;
+use libp2p::{
+    futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    request_response::{self, ProtocolSupport},
+    StreamProtocol,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, io, time::Duration};
+
+pub const DISCONNECT_PROTOCOL: StreamProtocol = StreamProtocol::new("/avail_kad/disconnect/1.0.0");
+// connections are closed once it passes, even if the peer doesn't respond
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// reasons are short, so the larger requests are rejected
+const MAX_REASON_BYTES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectCode {
+    /// Release version of the peer is below the minimum supported one
+    UnsupportedVersion,
+    /// Peer speaks the Kademlia protocol of another network, i.e. with a different genesis hash
+    IncompatibleNetwork,
+}
+
+/// Reason of the rejection, with the human readable details, e.g. the expected Kademlia protocol name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisconnectReason {
+    pub code: DisconnectCode,
+    pub message: String,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DisconnectCodec;
+
+#[async_trait]
+impl request_response::Codec for DisconnectCodec {
+    type Protocol = StreamProtocol;
+    type Request = DisconnectReason;
+    type Response = ();
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut bytes = vec![];
+        io.take(MAX_REASON_BYTES).read_to_end(&mut bytes).await?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    async fn read_response<T>(&mut self, _: &StreamProtocol, _: &mut T) -> io::Result<()>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Ok(())
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        reason: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = serde_json::to_vec(&reason)?;
+        io.write_all(&bytes).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(&mut self, _: &StreamProtocol, io: &mut T, _: ()) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.close().await
+    }
+}
+
+pub fn behaviour() -> request_response::Behaviour<DisconnectCodec> {
+    request_response::Behaviour::new(
+        [(DISCONNECT_PROTOCOL, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(DISCONNECT_TIMEOUT),
+    )
+}
@@ -1,26 +1,192 @@
-use anyhow::Result;
 use libp2p::{
-    autonat::{self, InboundProbeEvent, OutboundProbeEvent},
+    autonat::{
+        self, InboundProbeError, InboundProbeEvent, OutboundProbeError, OutboundProbeEvent,
+        ResponseError,
+    },
+    core::{transport::ListenerId, ConnectedPoint},
     futures::StreamExt,
     identify::{Event as IdentifyEvent, Info},
-    kad::{self, BootstrapOk, QueryId, QueryResult},
+    kad::{
+        self,
+        store::{self, RecordStore},
+        BootstrapOk, GetClosestPeersError, GetClosestPeersOk, InboundRequest, NodeStatus, QueryId,
+        QueryResult,
+    },
     multiaddr::Protocol,
-    swarm::{ConnectionError, SwarmEvent},
+    ping,
+    request_response::{self, OutboundRequestId},
+    swarm::{
+        dummy, ConnectionError, ConnectionId, DialError, ListenError, NetworkBehaviour, SwarmEvent,
+    },
     Multiaddr, PeerId, Swarm,
 };
-use std::{collections::HashMap, str::FromStr, time::Duration};
+use libp2p_allow_block_list::Blocked;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    future::Future,
+    net::IpAddr,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
-    sync::{mpsc, oneshot},
-    time::{interval_at, Instant, Interval},
+    sync::{broadcast, mpsc, mpsc::error::TrySendError, oneshot, OwnedMutexGuard},
+    task,
+    time::{interval_at, sleep, Instant, Interval},
+};
+use tracing::{debug, info, trace};
+
+use crate::{
+    geoip::{GeoIp, PeerLocation},
+    telemetry::{
+        AutoNatProbeOutcome, AutoNatThrottleLimit, AutoNatThrottleUsage, BootstrapKind,
+        ConnectionCloseCause, ConnectionCounts, ConnectionDirection, ConnectionTransport,
+        DistinctCounts, IdentifyEventKind, KadQueryOutcome, KadQueryType, KadStoreRejection,
+        MetricCounter, MetricHistogram, Metrics, NatStatus, PeerEvictionReason, SeenWindow,
+        OTHER_LABEL, UNKNOWN_LABEL,
+    },
+    types::{
+        AgentVersion, AutonatConfig, BootstrapConfig, ConnectionPolicyConfig, RandomWalkConfig,
+        IDENTITY_AGENT_ROLE, KADEMLIA_PROTOCOL_BASE, MINIMUM_SUPPORTED_BOOTSTRAP_VERSION,
+        MINIMUM_SUPPORTED_LIGHT_CLIENT_VERSION,
+    },
 };
-use tracing::{debug, trace};
 
-use crate::types::AgentVersion;
+use super::{
+    bootstrap_info::{BootstrapInfo, BootstrapInfoRequest},
+    client::{
+        Command, ConnectionEntry, DHTCursor, DHTEntry, ExternalAddress, HealthState,
+        LatencySummary, MaintenanceSummary, NetworkError, NetworkEvent, NetworkTopology,
+        QueryLatencies, Result, StartupSummary,
+    },
+    disconnect::{DisconnectCode, DisconnectReason},
+    peer_exchange::{ExchangedPeer, PeerExchangeRequest, PeerExchangeResponse},
+    Bandwidth, Behaviour, BehaviourEvent, ExtensionEvents, Network, NetworkShutdown,
+};
+
+fn connection_direction(endpoint: &ConnectedPoint) -> ConnectionDirection {
+    if endpoint.is_listener() {
+        ConnectionDirection::Inbound
+    } else {
+        ConnectionDirection::Outbound
+    }
+}
+
+// Outcome of the query results which are not handled by the event loop
+fn other_query_outcome(result: &QueryResult) -> KadQueryOutcome {
+    let is_ok = match result {
+        QueryResult::Bootstrap(result) => result.is_ok(),
+        QueryResult::GetClosestPeers(result) => result.is_ok(),
+        QueryResult::GetProviders(result) => result.is_ok(),
+        QueryResult::StartProviding(result) | QueryResult::RepublishProvider(result) => {
+            result.is_ok()
+        }
+        QueryResult::GetRecord(result) => result.is_ok(),
+        QueryResult::PutRecord(result) | QueryResult::RepublishRecord(result) => result.is_ok(),
+    };
+    if is_ok {
+        KadQueryOutcome::Ok
+    } else {
+        KadQueryOutcome::Error
+    }
+}
+
+fn nat_status(status: &autonat::NatStatus) -> NatStatus {
+    match status {
+        autonat::NatStatus::Public(_) => NatStatus::Public,
+        autonat::NatStatus::Private => NatStatus::Private,
+        autonat::NatStatus::Unknown => NatStatus::Unknown,
+    }
+}
+
+fn store_rejection(err: &store::Error) -> KadStoreRejection {
+    match err {
+        store::Error::MaxRecords => KadStoreRejection::MaxRecords,
+        store::Error::MaxProvidedKeys => KadStoreRejection::MaxProvidedKeys,
+        store::Error::ValueTooLarge => KadStoreRejection::ValueTooLarge,
+    }
+}
+
+fn remote_ip(endpoint: &ConnectedPoint) -> Option<IpAddr> {
+    endpoint
+        .get_remote_address()
+        .iter()
+        .find_map(|protocol| match protocol {
+            Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+            Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+            _ => None,
+        })
+}
+
+fn is_loopback(address: &Multiaddr) -> bool {
+    address.iter().any(|protocol| match protocol {
+        Protocol::Ip4(ip) => ip.is_loopback(),
+        Protocol::Ip6(ip) => ip.is_loopback(),
+        _ => false,
+    })
+}
+
+// Private, shared (CGNAT), link-local, loopback and unspecified addresses aren't dialable by the other peers
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let is_shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+            !(ip.is_private()
+                || is_shared
+                || ip.is_link_local()
+                || ip.is_loopback()
+                || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => {
+            let is_unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+            let is_link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+            !(is_unique_local || is_link_local || ip.is_loopback() || ip.is_unspecified())
+        }
+    }
+}
+
+// Returns the IP and UDP port of the QUIC address
+fn quic_ip_port(address: &Multiaddr) -> Option<(IpAddr, u16)> {
+    let mut ip = None;
+    let mut port = None;
+    let mut is_quic = false;
+    for protocol in address.iter() {
+        match protocol {
+            Protocol::Ip4(addr) => ip = Some(IpAddr::V4(addr)),
+            Protocol::Ip6(addr) => ip = Some(IpAddr::V6(addr)),
+            Protocol::Udp(udp_port) => port = Some(udp_port),
+            Protocol::QuicV1 => is_quic = true,
+            _ => {}
+        }
+    }
+    if !is_quic {
+        return None;
+    }
+    Some((ip?, port?))
+}
 
-use super::{client::Command, Behaviour, BehaviourEvent};
+fn connection_transport(endpoint: &ConnectedPoint) -> ConnectionTransport {
+    // local address of the listener holds all the protocols of the transport
+    let address = match endpoint {
+        ConnectedPoint::Dialer { address, .. } => address,
+        ConnectedPoint::Listener { local_addr, .. } => local_addr,
+    };
+    let mut transport = ConnectionTransport::Other;
+    for protocol in address.iter() {
+        match protocol {
+            Protocol::Ws(_) | Protocol::Wss(_) => return ConnectionTransport::Websocket,
+            Protocol::QuicV1 | Protocol::Quic => return ConnectionTransport::Quic,
+            Protocol::Tcp(_) => transport = ConnectionTransport::Tcp,
+            _ => {}
+        }
+    }
+    transport
+}
 
 enum QueryChannel {
     Bootstrap(oneshot::Sender<Result<()>>),
+    GetClosestPeers(oneshot::Sender<Result<Vec<PeerId>>>),
 }
 
 enum SwarmChannel {
@@ -34,52 +200,595 @@ struct BootstrapState {
     is_startup_done: bool,
     // timer that is responsible for firing periodic bootstraps
     timer: Interval,
+    // period of the timer, either the cold or the regular interval, depending on the routing table size
+    period: Duration,
+    cfg: BootstrapConfig,
+    // kinds of the currently running bootstrap queries
+    queries: HashMap<QueryId, BootstrapKind>,
+}
+
+// RandomWalkState keeps track of the periodic closest peers lookups of random keys
+struct RandomWalkState {
+    // left unpolled if the random walks are disabled
+    timer: Interval,
+    cfg: RandomWalkConfig,
+    queries: HashSet<QueryId>,
+}
+
+// interval of the checks closing the connections of the peers not identified in time
+const CONNECTION_POLICY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// ConnectionPolicy keeps the connections of the light clients alive for the minimum duration,
+// and closes the ones of the peers not identified as Avail peers in time
+struct ConnectionPolicy {
+    cfg: ConnectionPolicyConfig,
+    timer: Interval,
+    // peers and establishment times of the connections, until closed or expired
+    established: HashMap<ConnectionId, (PeerId, Instant)>,
+    // connected peers identified as Avail peers with a supported version
+    identified: HashSet<PeerId>,
+}
+
+// number of most recent query durations kept for latency percentiles
+const LATENCY_SAMPLES: usize = 1000;
+
+// LatencySamples keeps a bounded window of the most recent query durations
+#[derive(Default)]
+struct LatencySamples {
+    samples: VecDeque<Duration>,
+    count: u64,
+}
+
+impl LatencySamples {
+    fn record(&mut self, duration: Duration) {
+        if self.samples.len() == LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+        self.count += 1;
+    }
+
+    fn summary(&self) -> LatencySummary {
+        LatencySummary::new(self.count, self.samples.iter().copied().collect())
+    }
+}
+
+// QueryTimings keeps track of Kademlia query durations, from start until the final progress step
+#[derive(Default)]
+struct QueryTimings {
+    started: HashMap<QueryId, Instant>,
+    bootstrap: LatencySamples,
+    get_closest_peers: LatencySamples,
+}
+
+// Mirrors the AutoNAT server clients throttling, since throttled dial-back requests
+// are reported the same way as the otherwise refused ones
+struct AutoNatThrottle {
+    global_max: usize,
+    peer_max: usize,
+    period: Duration,
+    requests: VecDeque<(PeerId, Instant)>,
+    // peak number of requests within the period, globally and of a single peer, since the usage was taken
+    peak_global: usize,
+    peak_peer: usize,
+}
+
+impl AutoNatThrottle {
+    fn new(cfg: &AutonatConfig) -> Self {
+        Self {
+            global_max: cfg.throttle_clients_global_max,
+            peer_max: cfg.throttle_clients_peer_max,
+            period: cfg.throttle_clients_period,
+            requests: Default::default(),
+            peak_global: 0,
+            peak_peer: 0,
+        }
+    }
+
+    fn prune(&mut self) {
+        while let Some((_, time)) = self.requests.front() {
+            if time.elapsed() <= self.period {
+                break;
+            }
+            self.requests.pop_front();
+        }
+    }
+
+    fn peer_requests(&self, peer: &PeerId) -> usize {
+        self.requests.iter().filter(|(p, _)| p == peer).count()
+    }
+
+    fn record_request(&mut self, peer: PeerId) {
+        self.prune();
+        self.requests.push_back((peer, Instant::now()));
+        self.peak_global = self.peak_global.max(self.requests.len());
+        self.peak_peer = self.peak_peer.max(self.peer_requests(&peer));
+    }
+
+    // Returns the limit reached by the peer, with the global one checked first
+    fn reached_limit(&mut self, peer: &PeerId) -> Option<AutoNatThrottleLimit> {
+        self.prune();
+        if self.requests.len() >= self.global_max {
+            Some(AutoNatThrottleLimit::Global)
+        } else if self.peer_requests(peer) >= self.peer_max {
+            Some(AutoNatThrottleLimit::Peer)
+        } else {
+            None
+        }
+    }
+
+    // Returns the peak usage since the previous call, resetting the peaks to the current usage
+    fn take_usage(&mut self) -> AutoNatThrottleUsage {
+        let ratio = |requests: usize, max: usize| requests as f64 / max.max(1) as f64;
+        let usage = AutoNatThrottleUsage {
+            global: ratio(self.peak_global, self.global_max),
+            peer: ratio(self.peak_peer, self.peer_max),
+        };
+        self.prune();
+        self.peak_global = self.requests.len();
+        let mut peer_requests: HashMap<&PeerId, usize> = HashMap::new();
+        for (peer, _) in &self.requests {
+            *peer_requests.entry(peer).or_default() += 1;
+        }
+        self.peak_peer = peer_requests.into_values().max().unwrap_or_default();
+        usage
+    }
+}
+
+// SeenPeers keeps the time peers and remote IPs were last connected,
+// for as long as the longest of the rolling windows they're counted over
+#[derive(Default)]
+struct SeenPeers {
+    connected: HashMap<ConnectionId, (PeerId, Option<IpAddr>)>,
+    peers: HashMap<PeerId, Instant>,
+    ips: HashMap<IpAddr, Instant>,
+}
+
+impl SeenPeers {
+    fn connected(&mut self, connection_id: ConnectionId, peer_id: PeerId, ip: Option<IpAddr>) {
+        self.connected.insert(connection_id, (peer_id, ip));
+        self.seen(peer_id, ip, Instant::now());
+    }
+
+    fn closed(&mut self, connection_id: &ConnectionId) {
+        if let Some((peer_id, ip)) = self.connected.remove(connection_id) {
+            self.seen(peer_id, ip, Instant::now());
+        }
+    }
+
+    fn seen(&mut self, peer_id: PeerId, ip: Option<IpAddr>, now: Instant) {
+        self.peers.insert(peer_id, now);
+        if let Some(ip) = ip {
+            self.ips.insert(ip, now);
+        }
+    }
+
+    // peers seen longer than the longest window ago are forgotten
+    fn last_seen(&self, peer_id: &PeerId) -> Option<Instant> {
+        let is_connected = self.connected.values().any(|(peer, _)| peer == peer_id);
+        if is_connected {
+            return Some(Instant::now());
+        }
+        self.peers.get(peer_id).copied()
+    }
+
+    fn counts(&mut self) -> BTreeMap<SeenWindow, DistinctCounts> {
+        let now = Instant::now();
+        // peers which are still connected are seen right now
+        let connected: Vec<_> = self.connected.values().copied().collect();
+        for (peer_id, ip) in connected {
+            self.seen(peer_id, ip, now);
+        }
+
+        let longest = SeenWindow::Day.duration();
+        self.peers.retain(|_, seen| now - *seen <= longest);
+        self.ips.retain(|_, seen| now - *seen <= longest);
+
+        let count = |seen: &mut dyn Iterator<Item = &Instant>, window: SeenWindow| {
+            seen.filter(|seen| now - **seen <= window.duration())
+                .count()
+        };
+        SeenWindow::ALL
+            .into_iter()
+            .map(|window| {
+                let counts = DistinctCounts {
+                    peers: count(&mut self.peers.values(), window),
+                    ips: count(&mut self.ips.values(), window),
+                };
+                (window, counts)
+            })
+            .collect()
+    }
+}
+
+// Metrics counted by the synchronous handlers, since the metrics backends are async
+#[derive(Default)]
+struct PendingMetrics {
+    counters: HashMap<MetricCounter, u64>,
+    histograms: VecDeque<(MetricHistogram, f64)>,
+}
+
+impl PendingMetrics {
+    fn is_empty(&self) -> bool {
+        self.counters.is_empty() && self.histograms.is_empty()
+    }
+}
+
+// number of the handled events and commands whose metrics are queued for the recorder task
+const PENDING_METRICS_QUEUE_SIZE: usize = 1024;
+// histogram samples kept pending while the recorder queue is full, the oldest ones are dropped once exceeded
+const MAX_PENDING_HISTOGRAM_SAMPLES: usize = 10_000;
+
+// Records the metrics handed over by the event loop, so the slow metrics backends don't stall it.
+// Task is finished once the event loop is dropped.
+async fn record_metrics(metrics: Arc<dyn Metrics>, mut receiver: mpsc::Receiver<PendingMetrics>) {
+    while let Some(pending) = receiver.recv().await {
+        for (counter, value) in pending.counters {
+            metrics.count_n(counter, value).await;
+        }
+        for (histogram, value) in pending.histograms {
+            metrics.record_histogram(histogram, value).await;
+        }
+    }
+}
+
+// Runs the expensive part of the command handling, such as the serialization and aggregation,
+// on a worker task instead of the event loop, given the state snapshot it needs
+fn spawn_worker<T: Send + 'static>(
+    response_sender: oneshot::Sender<T>,
+    work: impl FnOnce() -> T + Send + 'static,
+) {
+    task::spawn_blocking(move || {
+        _ = response_sender.send(work());
+    });
 }
 
-pub struct EventLoop {
-    swarm: Swarm<Behaviour>,
-    command_receiver: mpsc::Receiver<Command>,
+// Routing table entry as snapshotted by the event loop, converted into the [DHTEntry] by the worker task
+struct DHTEntrySnapshot {
+    peer_id: PeerId,
+    addresses: Vec<Multiaddr>,
+    is_connected: bool,
+    agent_version: Option<String>,
+    location: Option<PeerLocation>,
+}
+
+impl From<DHTEntrySnapshot> for DHTEntry {
+    fn from(snapshot: DHTEntrySnapshot) -> Self {
+        let location = snapshot.location.unwrap_or_default();
+        DHTEntry {
+            peer_id: snapshot.peer_id.to_string(),
+            addresses: snapshot.addresses.iter().map(|a| a.to_string()).collect(),
+            is_connected: snapshot.is_connected,
+            agent_version: snapshot.agent_version,
+            country: location.country,
+            asn: location.asn,
+        }
+    }
+}
+
+// Established connection, counted by its direction and transport
+struct Connection {
+    peer_id: PeerId,
+    remote_address: Multiaddr,
+    direction: ConnectionDirection,
+    transport: ConnectionTransport,
+    established: Instant,
+}
+
+// Listen addresses of the peer reported over identify, which are added to the routing table
+struct IdentifiedAddresses {
+    addresses: HashSet<Multiaddr>,
+    // all the reported addresses are re-added once the refresh interval passes since
+    refreshed_at: Instant,
+}
+
+// unconfirmed addresses with the lowest confidence are forgotten once exceeded
+const MAX_EXTERNAL_ADDRESSES: usize = 32;
+// confidence gained with each AutoNAT confirmation, compared to a single observation by a peer
+const CONFIRMATION_CONFIDENCE: u32 = 10;
+
+struct ExternalAddressState {
+    is_confirmed: bool,
+    confidence: u32,
+}
+
+// External address candidates observed by the peers and the ones confirmed by AutoNAT,
+// since the swarm only exposes the currently confirmed ones, without telling them apart
+#[derive(Default)]
+struct ExternalAddresses {
+    addresses: HashMap<Multiaddr, ExternalAddressState>,
+}
+
+impl ExternalAddresses {
+    fn observed(&mut self, address: Multiaddr) {
+        self.raise(address, 1, None);
+    }
+
+    fn confirmed(&mut self, address: Multiaddr) {
+        self.raise(address, CONFIRMATION_CONFIDENCE, Some(true));
+    }
+
+    fn expired(&mut self, address: &Multiaddr) {
+        if let Some(state) = self.addresses.get_mut(address) {
+            state.is_confirmed = false;
+        }
+    }
+
+    fn restored(&mut self, address: Multiaddr, confidence: u32) {
+        self.raise(address, confidence, None);
+    }
+
+    fn raise(&mut self, address: Multiaddr, confidence: u32, is_confirmed: Option<bool>) {
+        let state = self
+            .addresses
+            .entry(address)
+            .or_insert(ExternalAddressState {
+                is_confirmed: false,
+                confidence: 0,
+            });
+        state.confidence = state.confidence.saturating_add(confidence);
+        if let Some(is_confirmed) = is_confirmed {
+            state.is_confirmed = is_confirmed;
+        }
+
+        while self.addresses.len() > MAX_EXTERNAL_ADDRESSES {
+            let Some(weakest) = self
+                .addresses
+                .iter()
+                .filter(|(_, state)| !state.is_confirmed)
+                .min_by_key(|(_, state)| state.confidence)
+                .map(|(address, _)| address.clone())
+            else {
+                break;
+            };
+            self.addresses.remove(&weakest);
+        }
+    }
+
+    // confirmed addresses first, then by the confidence
+    fn sorted(&self) -> Vec<(&Multiaddr, &ExternalAddressState)> {
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|(a, a_state), (b, b_state)| {
+            (b_state.is_confirmed, b_state.confidence)
+                .cmp(&(a_state.is_confirmed, a_state.confidence))
+                .then_with(|| a.cmp(b))
+        });
+        addresses
+    }
+
+    fn preferred_confirmed(&self) -> Option<&Multiaddr> {
+        self.sorted()
+            .into_iter()
+            .find(|(_, state)| state.is_confirmed)
+            .map(|(address, _)| address)
+    }
+
+    fn list(&self) -> Vec<ExternalAddress> {
+        self.sorted()
+            .into_iter()
+            .map(|(address, state)| ExternalAddress {
+                address: address.to_string(),
+                is_confirmed: state.is_confirmed,
+                confidence: state.confidence,
+            })
+            .collect()
+    }
+}
+
+pub struct EventLoop<E: NetworkBehaviour = dummy::Behaviour> {
+    swarm: Swarm<Behaviour<E>>,
+    command_receiver: OwnedMutexGuard<mpsc::Receiver<Command>>,
     pending_kad_queries: HashMap<QueryId, QueryChannel>,
     pending_kad_routing: HashMap<PeerId, oneshot::Sender<Result<()>>>,
     pending_swarm_events: HashMap<PeerId, SwarmChannel>,
+    // agent versions reported over identify by peers we know of
+    agent_versions: HashMap<PeerId, String>,
+    // addresses reported over identify by peers we know of, so only the new ones are added on each identify
+    identified_addresses: HashMap<PeerId, IdentifiedAddresses>,
+    identify_refresh_interval: Duration,
+    // remote addresses of the inbound QUIC connections, dialable if the NAT of the peer preserves its listen port
+    observed_quic_addrs: HashMap<ConnectionId, (PeerId, Multiaddr)>,
+    is_quic_address_translation: bool,
+    // rejected peers, disconnected once the reason is delivered or fails to be
+    pending_disconnects: HashMap<OutboundRequestId, PeerId>,
+    peer_exchange_max_peers: usize,
+    // peer exchange responses start this far into the matching peers, so consecutive requests get different ones
+    peer_exchange_offset: usize,
+    external_addresses: ExternalAddresses,
+    query_timings: QueryTimings,
+    // metrics accumulated while handling an event or a command, handed over to the recorder task once it is handled
+    pending_metrics: PendingMetrics,
+    metrics_sender: mpsc::Sender<PendingMetrics>,
+    // currently established connections
+    connections: HashMap<ConnectionId, Connection>,
+    bandwidth: Bandwidth,
+    autonat_throttle: AutoNatThrottle,
+    // peers blocked so far, since the block list behaviour doesn't expose them
+    blocked_peers: HashSet<PeerId>,
     bootstrap: BootstrapState,
+    random_walk: RandomWalkState,
+    connection_policy: ConnectionPolicy,
+    geoip: Option<Arc<GeoIp>>,
+    // locations of the connected peers, looked up once connected
+    peer_locations: HashMap<PeerId, PeerLocation>,
+    seen_peers: SeenPeers,
+    network_name: String,
+    genesis_hash: String,
+    // configured bootstrap nodes, recommended to the clients along with the connected ones
+    bootstraps: Vec<String>,
+    is_startup_summary_logged: bool,
+    // checked by the stall watchdog, along with the command responses
+    last_swarm_event: Instant,
+    // peers never connected since the start are pruned as stale once the timeout passes since it
+    started_at: Instant,
+    events: broadcast::Sender<NetworkEvent>,
+    extension_events: ExtensionEvents<E>,
+    // kept up to date on the routing updates and removals, instead of walking all the k-buckets on each count
+    routing_table_peers: usize,
+    // listeners started by the event loop, closed on shutdown
+    listeners: HashSet<ListenerId>,
+    shutdown: NetworkShutdown,
+    shutdown_grace_period: Duration,
 }
 
-impl EventLoop {
-    pub fn new(
-        swarm: Swarm<Behaviour>,
-        command_receiver: mpsc::Receiver<Command>,
-        bootstrap_interval: Duration,
+impl<E: NetworkBehaviour> EventLoop<E>
+where
+    E::ToSwarm: Debug,
+{
+    pub(crate) fn new(
+        swarm: Swarm<Behaviour<E>>,
+        command_receiver: OwnedMutexGuard<mpsc::Receiver<Command>>,
+        bandwidth: Bandwidth,
+        network: &Network<E>,
     ) -> Self {
+        let cfg = &network.cfg;
+        let bootstrap_cfg = cfg.bootstrap.clone();
+        // routing table is empty until the startup bootstrap
+        let bootstrap_period = if bootstrap_cfg.target_peers > 0 {
+            bootstrap_cfg.cold_interval
+        } else {
+            bootstrap_cfg.interval
+        };
+        // interval is unused if the random walks are disabled
+        let random_walk_period = cfg.random_walk.interval.max(Duration::from_secs(1));
+        let (metrics_sender, metrics_receiver) = mpsc::channel(PENDING_METRICS_QUEUE_SIZE);
+        task::spawn(record_metrics(network.metrics.clone(), metrics_receiver));
         Self {
             swarm,
             command_receiver,
+            bandwidth,
             pending_kad_queries: Default::default(),
             pending_kad_routing: Default::default(),
             pending_swarm_events: Default::default(),
+            agent_versions: Default::default(),
+            identified_addresses: Default::default(),
+            identify_refresh_interval: cfg.identify.address_refresh_interval,
+            observed_quic_addrs: Default::default(),
+            is_quic_address_translation: cfg.identify.quic_address_translation,
+            pending_disconnects: Default::default(),
+            peer_exchange_max_peers: cfg.peer_exchange_max_peers,
+            peer_exchange_offset: 0,
+            external_addresses: Default::default(),
+            query_timings: Default::default(),
+            pending_metrics: Default::default(),
+            metrics_sender,
+            connections: Default::default(),
+            autonat_throttle: AutoNatThrottle::new(&cfg.autonat),
+            blocked_peers: Default::default(),
             bootstrap: BootstrapState {
                 is_startup_done: false,
-                timer: interval_at(Instant::now() + bootstrap_interval, bootstrap_interval),
+                timer: interval_at(Instant::now() + bootstrap_period, bootstrap_period),
+                period: bootstrap_period,
+                cfg: bootstrap_cfg,
+                queries: Default::default(),
+            },
+            connection_policy: ConnectionPolicy {
+                cfg: cfg.connection_policy,
+                timer: interval_at(
+                    Instant::now() + CONNECTION_POLICY_CHECK_INTERVAL,
+                    CONNECTION_POLICY_CHECK_INTERVAL,
+                ),
+                established: Default::default(),
+                identified: Default::default(),
+            },
+            random_walk: RandomWalkState {
+                timer: interval_at(Instant::now() + random_walk_period, random_walk_period),
+                cfg: cfg.random_walk,
+                queries: Default::default(),
             },
+            geoip: network.geoip.clone(),
+            peer_locations: Default::default(),
+            seen_peers: Default::default(),
+            network_name: cfg.network_name.clone(),
+            genesis_hash: cfg.genesis_hash.clone(),
+            bootstraps: cfg.bootstraps.clone(),
+            is_startup_summary_logged: false,
+            last_swarm_event: Instant::now(),
+            started_at: Instant::now(),
+            events: network.events.clone(),
+            extension_events: network.extension.events.clone(),
+            routing_table_peers: 0,
+            listeners: Default::default(),
+            shutdown: network.shutdown.clone(),
+            shutdown_grace_period: cfg.shutdown_grace_period,
         }
     }
 
-    pub async fn run(mut self) {
+    /// Starts listening on the address, before the event loop is run
+    pub fn listen_on(&mut self, addr: Multiaddr) -> Result<()> {
+        let listener_id =
+            self.swarm
+                .listen_on(addr.clone())
+                .map_err(|err| NetworkError::ListenFailure {
+                    address: addr,
+                    reason: err.to_string(),
+                })?;
+        self.listeners.insert(listener_id);
+        Ok(())
+    }
+
+    /// Runs until the shutdown future completes or the [NetworkShutdown] is requested,
+    /// stopping cooperatively in both cases
+    pub async fn run(mut self, shutdown: impl Future<Output = ()>) {
+        let _running = self.shutdown.start();
+        tokio::pin!(shutdown);
+        let network_shutdown = self.shutdown.clone();
+        let requested = network_shutdown.requested();
+        tokio::pin!(requested);
         loop {
             tokio::select! {
-                event = self.swarm.next() => self.handle_event(event.expect("Swarm stream should be infinite")).await,
+                _ = &mut shutdown => break,
+                _ = &mut requested => break,
+                event = self.swarm.next() => {
+                    self.last_swarm_event = Instant::now();
+                    self.handle_event(event.expect("Swarm stream should be infinite")).await;
+                }
                 command = self.command_receiver.recv() => match command {
                     Some(cmd) => self.handle_command(cmd).await,
                     // command channel closed,
                     // shutting down whole network event loop
-                    None => return,
+                    None => break,
                 },
                 _ = self.bootstrap.timer.tick() => self.handle_periodic_bootstraps(),
+                _ = self.connection_policy.timer.tick(), if !self.connection_policy.cfg.unidentified_timeout.is_zero() => {
+                    self.expire_unidentified_connections()
+                }
+                _ = self.random_walk.timer.tick(), if !self.random_walk.cfg.interval.is_zero() => {
+                    self.handle_random_walks()
+                }
             }
+            self.record_pending_metrics();
+        }
+        self.drain().await;
+        info!("Network event loop stopped.");
+    }
+
+    // Closes the listeners and keeps handling the swarm events until the pending queries are finished,
+    // with the commands left unhandled, so no new queries are started
+    async fn drain(&mut self) {
+        for listener_id in self.listeners.drain() {
+            self.swarm.remove_listener(listener_id);
+        }
+        let grace_period = sleep(self.shutdown_grace_period);
+        tokio::pin!(grace_period);
+        while !self.pending_kad_queries.is_empty() {
+            tokio::select! {
+                _ = &mut grace_period => {
+                    debug!(
+                        "Shutdown grace period passed, dropping {} pending queries.",
+                        self.pending_kad_queries.len()
+                    );
+                    break;
+                }
+                event = self.swarm.next() => {
+                    self.handle_event(event.expect("Swarm stream should be infinite")).await;
+                }
+            }
+            self.record_pending_metrics();
         }
     }
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn handle_event(&mut self, event: SwarmEvent<BehaviourEvent>) {
+    async fn handle_event(&mut self, event: SwarmEvent<BehaviourEvent<E>>) {
         match event {
             SwarmEvent::Behaviour(BehaviourEvent::Kademlia(kad_event)) => match kad_event {
                 kad::Event::RoutingUpdated {
@@ -90,15 +799,62 @@ impl EventLoop {
                     ..
                 } => {
                     trace!("Routing updated. Peer: {peer:?}. Is new Peer: {is_new_peer:?}. Addresses: {addresses:#?}. Old Peer: {old_peer:#?}");
+                    self.publish(NetworkEvent::RoutingUpdated {
+                        peer_id: peer.to_string(),
+                        is_new_peer,
+                        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                    });
                     if let Some(ch) = self.pending_kad_routing.remove(&peer) {
                         _ = ch.send(Ok(()));
                     }
+                    if is_new_peer {
+                        self.routing_table_peers += 1;
+                    }
+                    if let Some(old_peer) = old_peer {
+                        self.publish(NetworkEvent::RoutingRemoved {
+                            peer_id: old_peer.to_string(),
+                        });
+                        self.routing_table_peers = self.routing_table_peers.saturating_sub(1);
+                        self.agent_versions.remove(&old_peer);
+                        self.identified_addresses.remove(&old_peer);
+                        self.count(MetricCounter::PeerEvicted(PeerEvictionReason::Unreachable));
+                    }
                 }
                 kad::Event::OutboundQueryProgressed {
                     id,
                     result: QueryResult::Bootstrap(bootstrap_result),
+                    step,
                     ..
                 } => {
+                    let outcome = match bootstrap_result {
+                        Ok(_) => KadQueryOutcome::Ok,
+                        Err(_) => KadQueryOutcome::Timeout,
+                    };
+                    self.count(MetricCounter::KadQueryResult(
+                        KadQueryType::Bootstrap,
+                        outcome,
+                    ));
+                    if step.last {
+                        let kind = self.bootstrap.queries.remove(&id);
+                        let started = self.query_timings.started.remove(&id);
+                        if let (Some(kind), Some(started)) = (kind, started) {
+                            let duration = started.elapsed();
+                            self.query_timings.bootstrap.record(duration);
+                            self.record_histogram(
+                                MetricHistogram::KadBootstrapDuration(kind),
+                                duration,
+                            );
+                            if bootstrap_result.is_ok() {
+                                self.count(MetricCounter::BootstrapSuccess(kind));
+                            } else {
+                                self.count(MetricCounter::BootstrapError(kind));
+                            }
+                            self.publish(NetworkEvent::BootstrapCompleted {
+                                kind,
+                                is_ok: bootstrap_result.is_ok(),
+                            });
+                        }
+                    }
                     match bootstrap_result {
                         Ok(BootstrapOk {
                             peer,
@@ -120,11 +876,61 @@ impl EventLoop {
                             if let Some(QueryChannel::Bootstrap(ch)) =
                                 self.pending_kad_queries.remove(&id)
                             {
-                                _ = ch.send(Err(err.into()));
+                                _ = ch.send(Err(NetworkError::QueryFailed {
+                                    query: KadQueryType::Bootstrap.as_str(),
+                                    reason: err.to_string(),
+                                }));
                             }
                         }
                     }
                 }
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetClosestPeers(closest_peers_result),
+                    step,
+                    ..
+                } => {
+                    if step.last && self.random_walk.queries.remove(&id) {
+                        trace!(
+                            "Random walk finished, routing table has {} peers.",
+                            self.routing_table_peers
+                        );
+                    }
+                    if step.last {
+                        if let Some(started) = self.query_timings.started.remove(&id) {
+                            let duration = started.elapsed();
+                            self.query_timings.get_closest_peers.record(duration);
+                            self.record_histogram(
+                                MetricHistogram::KadGetClosestPeersDuration,
+                                duration,
+                            );
+                        }
+                    }
+                    let outcome = match closest_peers_result {
+                        Ok(_) => KadQueryOutcome::Ok,
+                        Err(_) => KadQueryOutcome::Timeout,
+                    };
+                    let query = KadQueryType::GetClosestPeers;
+                    self.count(MetricCounter::KadQueryResult(query, outcome));
+                    let peers = match closest_peers_result {
+                        Ok(GetClosestPeersOk { peers, .. }) => peers,
+                        // timed out lookups still yield the peers found so far
+                        Err(GetClosestPeersError::Timeout { peers, .. }) => {
+                            trace!("Get closest peers timed out. Peers found: {}.", peers.len());
+                            peers
+                        }
+                    };
+                    if let Some(QueryChannel::GetClosestPeers(ch)) =
+                        self.pending_kad_queries.remove(&id)
+                    {
+                        _ = ch.send(Ok(peers));
+                    }
+                }
+                kad::Event::OutboundQueryProgressed { result, .. } => {
+                    let outcome = other_query_outcome(&result);
+                    self.count(MetricCounter::KadQueryResult(KadQueryType::Other, outcome));
+                }
+                kad::Event::InboundRequest { request } => self.handle_inbound_store(request),
                 _ => {}
             },
             SwarmEvent::Behaviour(BehaviourEvent::Identify(IdentifyEvent::Received {
@@ -139,6 +945,8 @@ impl EventLoop {
                     },
             })) => {
                 trace!("Identity Received from: {peer_id:?} on listen address: {listen_addrs:?}.");
+                let label = self.agent_version_label(Some(&agent_version));
+                self.count(MetricCounter::Identify(IdentifyEventKind::Received, label));
                 let incoming_peer_agent_version = match AgentVersion::from_str(&agent_version) {
                     Ok(agent) => agent,
                     Err(e) => {
@@ -156,22 +964,88 @@ impl EventLoop {
                         "Unsupported release version: {}",
                         incoming_peer_agent_version.release_version
                     );
-                    self.swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+                    self.remove_peer(&peer_id, PeerEvictionReason::UnsupportedVersion);
+                    self.reject_peer(
+                        peer_id,
+                        DisconnectCode::UnsupportedVersion,
+                        format!(
+                            "rejected: unsupported version {}",
+                            incoming_peer_agent_version.release_version
+                        ),
+                    );
                     return;
                 }
+                self.identified_avail_peer(peer_id, &incoming_peer_agent_version);
 
-                if protocols.contains(&self.swarm.behaviour_mut().kademlia.protocol_names()[0]) {
-                    debug!("Adding peer {peer_id} to routing table.");
-                    for addr in listen_addrs {
-                        self.swarm
-                            .behaviour_mut()
-                            .kademlia
-                            .add_address(&peer_id, addr);
+                // peers speaking any of the served protocol names are added, during migrations
+                let kad_protocols = self.swarm.behaviour().kademlia.protocol_names().to_vec();
+                if kad_protocols
+                    .iter()
+                    .any(|protocol| protocols.contains(protocol))
+                {
+                    self.agent_versions.insert(peer_id, agent_version);
+                    let listen_addrs = self.translate_quic_addresses(&peer_id, listen_addrs);
+                    // identify pushed by the peer once its addresses change is received the same way,
+                    // so the stale addresses are removed after the new ones are added
+                    let (added, removed) = self.identified_addresses(peer_id, listen_addrs);
+                    if added.is_empty() && removed.is_empty() {
+                        trace!("No new addresses of peer {peer_id} to add to routing table.");
+                        return;
+                    }
+                    debug!("Updating addresses of peer {peer_id} in routing table.");
+                    let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+                    for addr in added {
+                        kademlia.add_address(&peer_id, addr);
+                    }
+                    for addr in removed {
+                        trace!("Removing address {addr} no longer reported by peer {peer_id}.");
+                        kademlia.remove_address(&peer_id, &addr);
                     }
                 } else {
                     // Block and remove non-Avail peers
                     debug!("Removing and blocking non-avail peer from routing table. Peer: {peer_id}. Agent: {agent_version}. Protocol: {protocol_version}");
-                    self.swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+                    self.remove_peer(&peer_id, PeerEvictionReason::UnsupportedProtocol);
+                    // peers of the other networks are disconnected, while the ones not serving the DHT are kept connected
+                    let is_other_network = protocols
+                        .iter()
+                        .any(|protocol| protocol.as_ref().starts_with(KADEMLIA_PROTOCOL_BASE));
+                    if is_other_network {
+                        let expected = kad_protocols[0].to_string();
+                        self.reject_peer(
+                            peer_id,
+                            DisconnectCode::IncompatibleNetwork,
+                            format!("rejected: incompatible network, expected {expected}"),
+                        );
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Identify(IdentifyEvent::Sent { peer_id })) => {
+                trace!("Identity Sent to: {peer_id:?}.");
+                self.count_identify(IdentifyEventKind::Sent, &peer_id);
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Identify(IdentifyEvent::Pushed {
+                peer_id,
+                ..
+            })) => {
+                trace!("Identity Pushed to: {peer_id:?}.");
+                self.count_identify(IdentifyEventKind::Pushed, &peer_id);
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Disconnect(event)) => {
+                self.handle_disconnect_event(event)
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::BootstrapInfo(event)) => {
+                self.handle_bootstrap_info_event(event)
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::PeerExchange(event)) => {
+                self.handle_peer_exchange_event(event)
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event { peer, result, .. })) => {
+                match result {
+                    Ok(rtt) => {
+                        trace!("Ping to {peer} succeeded. RTT: {rtt:?}.");
+                        self.record_histogram(MetricHistogram::PingRtt, rtt);
+                    }
+                    Err(err) => trace!("Ping to {peer} failed. Error: {err}."),
                 }
             }
             SwarmEvent::Behaviour(BehaviourEvent::AutoNat(autonat_event)) => match autonat_event {
@@ -181,9 +1055,27 @@ impl EventLoop {
                             "AutoNAT Inbound Probe failed with Peer: {:?}. Error: {:?}.",
                             peer, error
                         );
+                        let outcome = match error {
+                            InboundProbeError::Response(ResponseError::DialRefused) => {
+                                match self.autonat_throttle.reached_limit(&peer) {
+                                    Some(limit) => {
+                                        self.count(MetricCounter::AutoNatThrottled(limit));
+                                        AutoNatProbeOutcome::Throttled
+                                    }
+                                    None => AutoNatProbeOutcome::Refused,
+                                }
+                            }
+                            _ => AutoNatProbeOutcome::Failed,
+                        };
+                        self.count(MetricCounter::AutoNatInboundProbe(outcome));
+                    }
+                    InboundProbeEvent::Request { peer, .. } => {
+                        trace!("AutoNAT Inbound Probe: {:#?}", inbound_event);
+                        self.autonat_throttle.record_request(peer);
                     }
-                    _ => {
+                    InboundProbeEvent::Response { .. } => {
                         trace!("AutoNAT Inbound Probe: {:#?}", inbound_event);
+                        self.count(MetricCounter::AutoNatInboundProbe(AutoNatProbeOutcome::Ok));
                     }
                 },
                 autonat::Event::OutboundProbe(outbound_event) => match outbound_event {
@@ -192,8 +1084,21 @@ impl EventLoop {
                             "AutoNAT Outbound Probe failed with Peer: {:#?}. Error: {:?}",
                             peer, error
                         );
+                        let outcome = match error {
+                            OutboundProbeError::NoServer => AutoNatProbeOutcome::NoServer,
+                            OutboundProbeError::NoAddresses => AutoNatProbeOutcome::NoAddresses,
+                            OutboundProbeError::Response(ResponseError::DialRefused) => {
+                                AutoNatProbeOutcome::Refused
+                            }
+                            _ => AutoNatProbeOutcome::Failed,
+                        };
+                        self.count(MetricCounter::AutoNatOutboundProbe(outcome));
+                    }
+                    OutboundProbeEvent::Response { .. } => {
+                        trace!("AutoNAT Outbound Probe: {:#?}", outbound_event);
+                        self.count(MetricCounter::AutoNatOutboundProbe(AutoNatProbeOutcome::Ok));
                     }
-                    _ => {
+                    OutboundProbeEvent::Request { .. } => {
                         trace!("AutoNAT Outbound Probe: {:#?}", outbound_event);
                     }
                 },
@@ -203,16 +1108,43 @@ impl EventLoop {
                         "AutoNAT Old status: {:#?}. AutoNAT New status: {:#?}",
                         old, new
                     );
+                    self.publish(NetworkEvent::NatStatusChanged {
+                        old: nat_status(&old),
+                        new: nat_status(&new),
+                    });
                 }
             },
             SwarmEvent::ConnectionClosed {
                 peer_id,
+                connection_id,
                 endpoint,
                 num_established,
                 cause,
                 ..
             } => {
                 trace!("Connection closed. PeerID: {peer_id:?}. Address: {:?}. Num established: {num_established:?}. Cause: {cause:?}.", endpoint.get_remote_address());
+                self.connections.remove(&connection_id);
+                self.observed_quic_addrs.remove(&connection_id);
+                self.seen_peers.closed(&connection_id);
+                self.connection_policy.established.remove(&connection_id);
+                if num_established == 0 {
+                    self.peer_locations.remove(&peer_id);
+                    self.connection_policy.identified.remove(&peer_id);
+                    self.publish(NetworkEvent::PeerDisconnected {
+                        peer_id: peer_id.to_string(),
+                    });
+                }
+                let cause = match cause {
+                    None => ConnectionCloseCause::Closed,
+                    Some(ConnectionError::IO(_)) => ConnectionCloseCause::Io,
+                    Some(ConnectionError::KeepAliveTimeout) => {
+                        ConnectionCloseCause::KeepAliveTimeout
+                    }
+                };
+                self.count(MetricCounter::ConnectionClosed(
+                    connection_direction(&endpoint),
+                    cause,
+                ));
             }
 
             SwarmEvent::OutgoingConnectionError {
@@ -221,10 +1153,73 @@ impl EventLoop {
                 error,
             } => {
                 trace!("Outgoing connection error. Connection id: {connection_id}. Peer: {peer_id}. Error: {error}.");
+                self.count(MetricCounter::OutgoingConnectionError);
+                if let DialError::Denied { cause } = &error {
+                    if cause.downcast_ref::<Blocked>().is_some() {
+                        let direction = ConnectionDirection::Outbound;
+                        self.count(MetricCounter::BlockedConnectionDenied(direction));
+                    }
+                }
+            }
+            SwarmEvent::IncomingConnectionError {
+                send_back_addr,
+                error,
+                ..
+            } => {
+                trace!("Incoming connection error. Address: {send_back_addr}. Error: {error}.");
+                self.count(MetricCounter::IncomingConnectionError);
+                if let ListenError::Denied { cause } = &error {
+                    if cause.downcast_ref::<Blocked>().is_some() {
+                        let direction = ConnectionDirection::Inbound;
+                        self.count(MetricCounter::BlockedConnectionDenied(direction));
+                    }
+                }
             }
             SwarmEvent::ConnectionEstablished {
-                endpoint, peer_id, ..
+                endpoint,
+                peer_id,
+                connection_id,
+                num_established,
+                ..
             } => {
+                if num_established.get() == 1 {
+                    self.publish(NetworkEvent::PeerConnected {
+                        peer_id: peer_id.to_string(),
+                        address: endpoint.get_remote_address().to_string(),
+                    });
+                }
+                self.connections.insert(
+                    connection_id,
+                    Connection {
+                        peer_id,
+                        remote_address: endpoint.get_remote_address().clone(),
+                        direction: connection_direction(&endpoint),
+                        transport: connection_transport(&endpoint),
+                        established: Instant::now(),
+                    },
+                );
+                self.count(MetricCounter::ConnectionEstablished(connection_direction(
+                    &endpoint,
+                )));
+                if endpoint.is_listener() {
+                    self.count(MetricCounter::IncomingConnection);
+                }
+                if let ConnectedPoint::Listener { send_back_addr, .. } = &endpoint {
+                    if quic_ip_port(send_back_addr).is_some() {
+                        self.observed_quic_addrs
+                            .insert(connection_id, (peer_id, send_back_addr.clone()));
+                    }
+                }
+                self.connection_policy
+                    .established
+                    .insert(connection_id, (peer_id, Instant::now()));
+                self.seen_peers
+                    .connected(connection_id, peer_id, remote_ip(&endpoint));
+                if let (Some(geoip), Some(ip)) = (&self.geoip, remote_ip(&endpoint)) {
+                    self.peer_locations
+                        .entry(peer_id)
+                        .or_insert_with(|| geoip.lookup(ip));
+                }
                 // while waiting for a first successful connection,
                 // we're interested in a case where we are dialing back
                 if endpoint.is_dialer() {
@@ -240,6 +1235,9 @@ impl EventLoop {
                     }
                 }
             }
+            SwarmEvent::ListenerClosed { listener_id, .. } => {
+                self.listeners.remove(&listener_id);
+            }
             SwarmEvent::NewListenAddr { address, .. } => {
                 let local_peer_id = *self.swarm.local_peer_id();
                 debug!(
@@ -247,6 +1245,34 @@ impl EventLoop {
                     address.with(Protocol::P2p(local_peer_id))
                 )
             }
+            SwarmEvent::NewExternalAddrCandidate { address } => {
+                self.external_addresses.observed(address);
+            }
+            SwarmEvent::ExternalAddrExpired { address } => {
+                debug!("External address expired: {address}");
+                self.external_addresses.expired(&address);
+            }
+            SwarmEvent::ExternalAddrConfirmed { address } => {
+                debug!("External address confirmed: {address}");
+                self.external_addresses.confirmed(address);
+                // logged once, as soon as the node can be dialed by the other nodes
+                if !self.is_startup_summary_logged {
+                    self.is_startup_summary_logged = true;
+                    let summary = self.startup_summary();
+                    info!(
+                        peer_id = %summary.peer_id,
+                        network = %summary.network,
+                        kad_protocol = %summary.kad_protocol,
+                        multiaddrs = %summary.multiaddrs.join(" "),
+                        "Node is dialable by the other nodes."
+                    );
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Extension(event)) => {
+                if let Some(data) = (self.extension_events)(event) {
+                    self.publish(NetworkEvent::Extension { data });
+                }
+            }
             _ => {}
         }
     }
@@ -257,10 +1283,7 @@ impl EventLoop {
                 addr,
                 response_sender,
             } => {
-                _ = match self.swarm.listen_on(addr) {
-                    Ok(_) => response_sender.send(Ok(())),
-                    Err(err) => response_sender.send(Err(err.into())),
-                }
+                _ = response_sender.send(self.listen_on(addr));
             }
             Command::AddAddress {
                 peer_id,
@@ -274,7 +1297,7 @@ impl EventLoop {
                 self.pending_kad_routing.insert(peer_id, response_sender);
             }
             Command::Bootstrap { response_sender } => {
-                match self.swarm.behaviour_mut().kademlia.bootstrap() {
+                match self.start_bootstrap(BootstrapKind::Startup) {
                     Ok(query_id) => {
                         self.pending_kad_queries
                             .insert(query_id, QueryChannel::Bootstrap(response_sender));
@@ -306,24 +1329,848 @@ impl EventLoop {
                 }
             },
             Command::CountDHTPeers { response_sender } => {
-                let mut total_peers: usize = 0;
-                for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
-                    total_peers += bucket.num_entries();
+                _ = response_sender.send(self.count_routing_table_peers());
+            }
+            Command::CountDHTBucketPeers { response_sender } => {
+                _ = response_sender.send(self.bucket_peers());
+            }
+            Command::CountConnections { response_sender } => {
+                let mut counts = ConnectionCounts::new();
+                for direction in ConnectionDirection::ALL {
+                    for transport in ConnectionTransport::ALL {
+                        counts.insert((direction, transport), 0);
+                    }
+                }
+                for connection in self.connections.values() {
+                    *counts
+                        .entry((connection.direction, connection.transport))
+                        .or_default() += 1;
                 }
-                _ = response_sender.send(total_peers);
+                _ = response_sender.send(counts);
+            }
+            Command::CountPeersPerCountry { response_sender } => {
+                let counts = self.geoip.as_ref().map(|_| {
+                    let mut counts = BTreeMap::new();
+                    for location in self.peer_locations.values() {
+                        let country = location.country.as_deref().unwrap_or(UNKNOWN_LABEL);
+                        *counts.entry(country.to_string()).or_default() += 1;
+                    }
+                    counts
+                });
+                _ = response_sender.send(counts);
+            }
+            Command::CountDistinctSeen { response_sender } => {
+                _ = response_sender.send(self.seen_peers.counts());
+            }
+            Command::GetAutoNatThrottleUsage { response_sender } => {
+                _ = response_sender.send(self.autonat_throttle.take_usage());
+            }
+            Command::CountBlockedPeers { response_sender } => {
+                _ = response_sender.send(self.blocked_peers.len());
+            }
+            Command::GetBlockedPeers { response_sender } => {
+                _ = response_sender.send(self.blocked_peers.iter().copied().collect());
+            }
+            Command::GetNatStatus { response_sender } => {
+                let status = nat_status(&self.swarm.behaviour().auto_nat.nat_status());
+                _ = response_sender.send(status);
+            }
+            Command::GetTransferredBytes { response_sender } => {
+                _ = response_sender.send(self.bandwidth.transferred_bytes());
             }
             Command::GetMultiaddress { response_sender } => {
-                let last_address = self.swarm.external_addresses().last();
-                _ = response_sender.send(last_address.cloned());
+                // swarm addresses are confirmed as well, in case any were added directly
+                let address = self
+                    .external_addresses
+                    .preferred_confirmed()
+                    .or_else(|| self.swarm.external_addresses().last());
+                _ = response_sender.send(address.cloned());
+            }
+            Command::GetClosestPeers {
+                peer_id,
+                response_sender,
+            } => {
+                let query_id = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .get_closest_peers(peer_id);
+                self.query_timings.started.insert(query_id, Instant::now());
+                self.pending_kad_queries
+                    .insert(query_id, QueryChannel::GetClosestPeers(response_sender));
+            }
+            Command::GetDHTEntries {
+                cursor,
+                batch_size,
+                response_sender,
+            } => {
+                let (snapshots, next) = self.dht_entries(cursor, batch_size);
+                spawn_worker(response_sender, move || {
+                    let entries = snapshots.into_iter().map(DHTEntry::from).collect();
+                    (entries, next)
+                });
+            }
+            Command::Ping { response_sender } => {
+                _ = response_sender.send(());
+            }
+            Command::Dial {
+                multiaddr,
+                response_sender,
+            } => {
+                _ = match self.swarm.dial(multiaddr.clone()) {
+                    Ok(_) => response_sender.send(Ok(())),
+                    Err(err) => response_sender.send(Err(NetworkError::DialFailure {
+                        address: multiaddr,
+                        reason: err.to_string(),
+                    })),
+                }
+            }
+            Command::BlockPeer {
+                peer_id,
+                response_sender,
+            } => {
+                debug!("Blocking peer {peer_id}.");
+                self.swarm.behaviour_mut().blocked_peers.block_peer(peer_id);
+                if self.blocked_peers.insert(peer_id) {
+                    self.count(MetricCounter::PeerBlocked);
+                }
+                self.remove_peer(&peer_id, PeerEvictionReason::Blocked);
+                _ = response_sender.send(());
+            }
+            Command::SetBootstrapInterval {
+                interval,
+                response_sender,
+            } => {
+                debug!("Periodic bootstrap interval set to {interval:?}.");
+                // cold interval is kept until the routing table grows to the target size
+                let is_cold = self.bootstrap.period != self.bootstrap.cfg.interval;
+                self.bootstrap.cfg.interval = interval;
+                if !is_cold {
+                    self.set_bootstrap_period(interval);
+                }
+                _ = response_sender.send(());
+            }
+            Command::GetQueryLatencies { response_sender } => {
+                _ = response_sender.send(QueryLatencies {
+                    bootstrap: self.query_timings.bootstrap.summary(),
+                    get_closest_peers: self.query_timings.get_closest_peers.summary(),
+                });
+            }
+            Command::GetHealthState { response_sender } => {
+                _ = response_sender.send(HealthState {
+                    is_startup_bootstrap_done: self.bootstrap.is_startup_done,
+                    listeners: self.swarm.listeners().count(),
+                    established_connections: self
+                        .swarm
+                        .network_info()
+                        .connection_counters()
+                        .num_established(),
+                    since_last_swarm_event: self.last_swarm_event.elapsed(),
+                });
+            }
+            Command::GetExternalAddresses { response_sender } => {
+                _ = response_sender.send(self.external_addresses.list());
+            }
+            Command::GetTopology { response_sender } => {
+                _ = response_sender.send(NetworkTopology {
+                    buckets: self.bucket_peers(),
+                    connections: self.connection_entries(),
+                });
+            }
+            Command::RestoreExternalAddresses {
+                addresses,
+                response_sender,
+            } => {
+                for address in addresses {
+                    let Ok(multiaddr) = address.address.parse::<Multiaddr>() else {
+                        debug!(
+                            "Unable to parse saved external address: {}",
+                            address.address
+                        );
+                        continue;
+                    };
+                    self.external_addresses
+                        .restored(multiaddr.clone(), address.confidence);
+                    // restored addresses are advertised only once confirmed again
+                    self.swarm.behaviour_mut().auto_nat.probe_address(multiaddr);
+                }
+                _ = response_sender.send(());
+            }
+            Command::GetStartupSummary { response_sender } => {
+                _ = response_sender.send(self.startup_summary());
+            }
+            Command::RunMaintenance {
+                stale_peer_timeout,
+                response_sender,
+            } => {
+                _ = response_sender.send(self.run_maintenance(stale_peer_timeout));
+            }
+        }
+    }
+
+    fn startup_summary(&self) -> StartupSummary {
+        let local_peer_id = *self.swarm.local_peer_id();
+        // confirmed addresses are listed in the order of preference
+        let mut addresses: Vec<&Multiaddr> = self
+            .external_addresses
+            .sorted()
+            .into_iter()
+            .filter(|(_, state)| state.is_confirmed)
+            .map(|(address, _)| address)
+            .collect();
+        addresses.extend(self.swarm.external_addresses());
+        let listeners: Vec<&Multiaddr> = self.swarm.listeners().collect();
+        // loopback addresses are dialable only by the local nodes
+        let has_public_listeners = listeners.iter().any(|address| !is_loopback(address));
+        addresses.extend(
+            listeners
+                .into_iter()
+                .filter(|address| !has_public_listeners || !is_loopback(address)),
+        );
+
+        let mut multiaddrs: Vec<String> = vec![];
+        for address in addresses {
+            let address = match address.iter().last() {
+                Some(Protocol::P2p(_)) => address.clone(),
+                _ => address.clone().with(Protocol::P2p(local_peer_id)),
+            };
+            let address = address.to_string();
+            if !multiaddrs.contains(&address) {
+                multiaddrs.push(address);
+            }
+        }
+
+        StartupSummary {
+            peer_id: local_peer_id.to_string(),
+            multiaddrs,
+            network: self.network_name.clone(),
+            kad_protocol: self
+                .swarm
+                .behaviour()
+                .kademlia
+                .protocol_names()
+                .iter()
+                .map(|protocol| protocol.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+
+    fn count(&mut self, counter: MetricCounter) {
+        *self.pending_metrics.counters.entry(counter).or_default() += 1;
+    }
+
+    // Counts identify event, labelled by agent version of the peer, if known
+    fn count_identify(&mut self, kind: IdentifyEventKind, peer_id: &PeerId) {
+        let agent_version = self.agent_versions.get(peer_id).cloned();
+        let label = self.agent_version_label(agent_version.as_deref());
+        self.count(MetricCounter::Identify(kind, label));
+    }
+
+    // Sends the reason of the rejection to the peer, which is disconnected once it's delivered
+    fn reject_peer(&mut self, peer_id: PeerId, code: DisconnectCode, message: String) {
+        debug!("Disconnecting peer {peer_id}, {message}.");
+        let request_id = self
+            .swarm
+            .behaviour_mut()
+            .disconnect
+            .send_request(&peer_id, DisconnectReason { code, message });
+        self.pending_disconnects.insert(request_id, peer_id);
+    }
+
+    fn handle_disconnect_event(&mut self, event: request_response::Event<DisconnectReason, ()>) {
+        let request_id = match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => {
+                debug!("Peer {peer} is disconnecting, {request}.");
+                _ = self
+                    .swarm
+                    .behaviour_mut()
+                    .disconnect
+                    .send_response(channel, ());
+                return;
+            }
+            request_response::Event::Message {
+                message: request_response::Message::Response { request_id, .. },
+                ..
+            } => request_id,
+            // peers not supporting the protocol are disconnected all the same
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                trace!("Disconnect reason not delivered to peer {peer}: {error}.");
+                request_id
+            }
+            _ => return,
+        };
+        if let Some(peer_id) = self.pending_disconnects.remove(&request_id) {
+            _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+    }
+
+    fn handle_peer_exchange_event(
+        &mut self,
+        event: request_response::Event<PeerExchangeRequest, PeerExchangeResponse>,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+            } => {
+                self.count(MetricCounter::PeerExchangeRequest);
+                let response = self.exchanged_peers(&peer, &request);
+                trace!("Handing out {} peers to peer {peer}.", response.peers.len());
+                _ = self
+                    .swarm
+                    .behaviour_mut()
+                    .peer_exchange
+                    .send_response(channel, response);
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                trace!("Peer exchange request of peer {peer} failed: {error}.");
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_bootstrap_info_event(
+        &mut self,
+        event: request_response::Event<BootstrapInfoRequest, BootstrapInfo>,
+    ) {
+        match event {
+            request_response::Event::Message {
+                peer,
+                message: request_response::Message::Request { channel, .. },
+            } => {
+                self.count(MetricCounter::BootstrapInfoRequest);
+                let info = self.bootstrap_info(&peer);
+                _ = self
+                    .swarm
+                    .behaviour_mut()
+                    .bootstrap_info
+                    .send_response(channel, info);
+            }
+            request_response::Event::InboundFailure { peer, error, .. } => {
+                trace!("Bootstrap info request of peer {peer} failed: {error}.");
+            }
+            _ => {}
+        }
+    }
+
+    fn bootstrap_info(&mut self, requester: &PeerId) -> BootstrapInfo {
+        let mut bootstraps = self.bootstraps.clone();
+        // connected bootstrap nodes are recognized by the role reported over identify
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                let peer_id = entry.node.key.preimage();
+                if entry.status != NodeStatus::Connected || peer_id == requester {
+                    continue;
+                }
+                let is_bootstrap = self
+                    .agent_versions
+                    .get(peer_id)
+                    .and_then(|agent_version| AgentVersion::from_str(agent_version).ok())
+                    .is_some_and(|agent_version| agent_version.role == IDENTITY_AGENT_ROLE);
+                if !is_bootstrap {
+                    continue;
+                }
+                for address in entry.node.value.iter() {
+                    let address = address.clone().with(Protocol::P2p(*peer_id)).to_string();
+                    if !bootstraps.contains(&address) {
+                        bootstraps.push(address);
+                    }
+                }
+            }
+        }
+
+        BootstrapInfo {
+            genesis_hash: self.genesis_hash.clone(),
+            network: self.network_name.clone(),
+            kad_protocols: self
+                .swarm
+                .behaviour()
+                .kademlia
+                .protocol_names()
+                .iter()
+                .map(|protocol| protocol.to_string())
+                .collect(),
+            minimum_light_client_version: MINIMUM_SUPPORTED_LIGHT_CLIENT_VERSION.to_string(),
+            minimum_bootstrap_version: MINIMUM_SUPPORTED_BOOTSTRAP_VERSION.to_string(),
+            routing_table_peers: self.routing_table_peers,
+            connected_peers: self.swarm.connected_peers().count(),
+            bootstraps,
+        }
+    }
+
+    // Connected routing table peers matching the request, except the requesting one,
+    // rotated on each request so the load is spread across them
+    fn exchanged_peers(
+        &mut self,
+        requester: &PeerId,
+        request: &PeerExchangeRequest,
+    ) -> PeerExchangeResponse {
+        let limit = request
+            .limit
+            .unwrap_or(self.peer_exchange_max_peers)
+            .min(self.peer_exchange_max_peers);
+        if limit == 0 {
+            return PeerExchangeResponse::default();
+        }
+
+        let mut peers = vec![];
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                let peer_id = entry.node.key.preimage();
+                if entry.status != NodeStatus::Connected || peer_id == requester {
+                    continue;
+                }
+                if let Some(client_type) = &request.client_type {
+                    let is_matching = self
+                        .agent_versions
+                        .get(peer_id)
+                        .and_then(|agent_version| AgentVersion::from_str(agent_version).ok())
+                        .is_some_and(|agent_version| &agent_version.client_type == client_type);
+                    if !is_matching {
+                        continue;
+                    }
+                }
+                peers.push(ExchangedPeer {
+                    peer_id: peer_id.to_string(),
+                    addresses: entry.node.value.iter().map(|a| a.to_string()).collect(),
+                });
+            }
+        }
+
+        if !peers.is_empty() {
+            let len = peers.len();
+            peers.rotate_left(self.peer_exchange_offset % len);
+        }
+        peers.truncate(limit);
+        self.peer_exchange_offset = self.peer_exchange_offset.wrapping_add(peers.len());
+        PeerExchangeResponse { peers }
+    }
+
+    // Adds the observed public addresses of the peer's inbound QUIC connections to the listen addresses it reports,
+    // if they have the same UDP port as any of its private listen addresses, meaning its NAT preserves the port,
+    // so the peers behind such NATs are handed out with the dialable addresses
+    fn translate_quic_addresses(
+        &self,
+        peer_id: &PeerId,
+        mut listen_addrs: Vec<Multiaddr>,
+    ) -> Vec<Multiaddr> {
+        if !self.is_quic_address_translation {
+            return listen_addrs;
+        }
+        let private_ports: HashSet<u16> = listen_addrs
+            .iter()
+            .filter_map(quic_ip_port)
+            .filter(|(ip, _)| !is_global(*ip))
+            .map(|(_, port)| port)
+            .collect();
+        for (observed_peer_id, observed_addr) in self.observed_quic_addrs.values() {
+            let Some((ip, port)) = quic_ip_port(observed_addr) else {
+                continue;
+            };
+            if observed_peer_id != peer_id
+                || !is_global(ip)
+                || !private_ports.contains(&port)
+                || listen_addrs.contains(observed_addr)
+            {
+                continue;
+            }
+            trace!("Translated observed QUIC address {observed_addr} of peer {peer_id}.");
+            listen_addrs.push(observed_addr.clone());
+        }
+        listen_addrs
+    }
+
+    // Updates the identified listen addresses of the peer, returning the ones to add to the routing table,
+    // either all of them once the refresh interval passed since they were last added, or only the ones not reported before,
+    // along with the previously reported ones which are no longer, e.g. after the NAT rebinding of the peer
+    fn identified_addresses(
+        &mut self,
+        peer_id: PeerId,
+        listen_addrs: Vec<Multiaddr>,
+    ) -> (Vec<Multiaddr>, Vec<Multiaddr>) {
+        let now = Instant::now();
+        let identified =
+            self.identified_addresses
+                .entry(peer_id)
+                .or_insert_with(|| IdentifiedAddresses {
+                    addresses: Default::default(),
+                    refreshed_at: now,
+                });
+        let reported: HashSet<Multiaddr> = listen_addrs.into_iter().collect();
+        let is_changed = !identified.addresses.is_empty() && identified.addresses != reported;
+        let is_refreshed = identified.addresses.is_empty()
+            || now - identified.refreshed_at >= self.identify_refresh_interval;
+        if is_refreshed {
+            identified.refreshed_at = now;
+        }
+        let added = reported
+            .iter()
+            .filter(|addr| is_refreshed || !identified.addresses.contains(*addr))
+            .cloned()
+            .collect();
+        // previous addresses are kept if the peer doesn't report any, so it isn't dropped from the routing table
+        let removed = if reported.is_empty() {
+            vec![]
+        } else {
+            identified
+                .addresses
+                .difference(&reported)
+                .cloned()
+                .collect()
+        };
+        identified.addresses = reported;
+        if is_changed {
+            self.count(MetricCounter::IdentifyAddressChange);
+        }
+        (added, removed)
+    }
+
+    // Number of distinct labels is limited by the telemetry relabeling
+    fn agent_version_label(&self, agent_version: Option<&str>) -> String {
+        let Some(agent_version) = agent_version else {
+            return UNKNOWN_LABEL.to_string();
+        };
+        AgentVersion::from_str(agent_version)
+            .ok()
+            .and_then(|agent_version| agent_version.metric_label())
+            .unwrap_or_else(|| OTHER_LABEL.to_string())
+    }
+
+    fn record_histogram(&mut self, histogram: MetricHistogram, duration: Duration) {
+        let duration_ms = duration.as_secs_f64() * 1000.0;
+        let histograms = &mut self.pending_metrics.histograms;
+        histograms.push_back((histogram, duration_ms));
+        if histograms.len() > MAX_PENDING_HISTOGRAM_SAMPLES {
+            histograms.pop_front();
+            self.count(MetricCounter::HistogramSampleDropped);
+        }
+    }
+
+    // Hands the pending metrics over to the recorder task, keeping them pending while its queue is full
+    fn record_pending_metrics(&mut self) {
+        if self.pending_metrics.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending_metrics);
+        if let Err(TrySendError::Full(pending)) = self.metrics_sender.try_send(pending) {
+            self.pending_metrics = pending;
+        }
+    }
+
+    // Stores the records put by other peers, since inserts are filtered to count the rejected ones
+    fn handle_inbound_store(&mut self, request: InboundRequest) {
+        let store = self.swarm.behaviour_mut().kademlia.store_mut();
+        let result = match request {
+            InboundRequest::PutRecord {
+                source,
+                record: Some(record),
+                ..
+            } => store
+                .put(record)
+                .map_err(|err| (format!("Record put by {source}"), err)),
+            InboundRequest::AddProvider {
+                record: Some(record),
+            } => {
+                let provider = record.provider;
+                store
+                    .add_provider(record)
+                    .map_err(|err| (format!("Provider record of {provider}"), err))
+            }
+            _ => return,
+        };
+        if let Err((record, err)) = result {
+            debug!("{record} not stored: {err}.");
+            self.count(MetricCounter::KadStoreRejected(store_rejection(&err)));
+        }
+    }
+
+    fn run_maintenance(&mut self, stale_peer_timeout: Duration) -> MaintenanceSummary {
+        let mut routing_table_peers = HashSet::new();
+        let mut stale_peers = vec![];
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                let peer_id = *entry.node.key.preimage();
+                routing_table_peers.insert(peer_id);
+                if entry.status == NodeStatus::Connected {
+                    continue;
+                }
+                let last_seen = self.seen_peers.last_seen(&peer_id);
+                if last_seen.unwrap_or(self.started_at).elapsed() > stale_peer_timeout {
+                    stale_peers.push(peer_id);
+                }
+            }
+        }
+        for peer_id in &stale_peers {
+            trace!("Pruning stale peer {peer_id}.");
+            routing_table_peers.remove(peer_id);
+            self.remove_peer(peer_id, PeerEvictionReason::Stale);
+        }
+
+        // identify and location details are kept only for the known and connected peers
+        let known_peers =
+            self.agent_versions.len() + self.identified_addresses.len() + self.peer_locations.len();
+        let swarm = &self.swarm;
+        let is_known =
+            |peer_id: &PeerId| routing_table_peers.contains(peer_id) || swarm.is_connected(peer_id);
+        self.agent_versions.retain(|peer_id, _| is_known(peer_id));
+        self.identified_addresses
+            .retain(|peer_id, _| is_known(peer_id));
+        self.peer_locations.retain(|peer_id, _| is_known(peer_id));
+        let peer_store_trimmed = known_peers
+            - self.agent_versions.len()
+            - self.identified_addresses.len()
+            - self.peer_locations.len();
+
+        // confirmed addresses expire unless AutoNAT keeps confirming them
+        let external_addresses: Vec<Multiaddr> = self.swarm.external_addresses().cloned().collect();
+        for address in &external_addresses {
+            self.swarm
+                .behaviour_mut()
+                .auto_nat
+                .probe_address(address.clone());
+        }
+
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        let provided_keys: Vec<_> = kademlia
+            .store_mut()
+            .provided()
+            .map(|record| record.key.clone())
+            .collect();
+        for key in &provided_keys {
+            if let Err(err) = kademlia.start_providing(key.clone()) {
+                debug!("Unable to re-publish provider record: {err}.");
             }
         }
+
+        // counter is corrected in case any of the routing table changes were missed
+        if self.routing_table_peers != routing_table_peers.len() {
+            debug!(
+                "Routing table peer count corrected from {} to {}.",
+                self.routing_table_peers,
+                routing_table_peers.len()
+            );
+            self.routing_table_peers = routing_table_peers.len();
+        }
+
+        MaintenanceSummary {
+            stale_peers_pruned: stale_peers.len(),
+            peer_store_trimmed,
+            external_addresses_probed: external_addresses.len(),
+            provider_records_republished: provided_keys.len(),
+            routing_table_peers: routing_table_peers.len(),
+        }
+    }
+
+    // Removes peer from the routing table, counting the eviction if it was there
+    fn remove_peer(&mut self, peer_id: &PeerId, reason: PeerEvictionReason) {
+        if self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .remove_peer(peer_id)
+            .is_some()
+        {
+            self.publish(NetworkEvent::RoutingRemoved {
+                peer_id: peer_id.to_string(),
+            });
+            self.routing_table_peers = self.routing_table_peers.saturating_sub(1);
+            self.count(MetricCounter::PeerEvicted(reason));
+        }
+        self.agent_versions.remove(peer_id);
+        self.identified_addresses.remove(peer_id);
+    }
+
+    fn publish(&self, event: NetworkEvent) {
+        // sending fails only while there are no subscribers
+        _ = self.events.send(event);
     }
 
     fn handle_periodic_bootstraps(&mut self) {
         // periodic bootstraps should only start after the initial one is done
         if self.bootstrap.is_startup_done {
             debug!("Starting periodic Bootstrap.");
-            _ = self.swarm.behaviour_mut().kademlia.bootstrap();
+            if let Err(err) = self.start_bootstrap(BootstrapKind::Periodic) {
+                debug!("Unable to start periodic Bootstrap: {err}");
+            }
+        }
+
+        // bootstraps are repeated more often until the routing table grows to the target size
+        let peers = self.count_routing_table_peers();
+        let cfg = &self.bootstrap.cfg;
+        let period = if peers < cfg.target_peers {
+            cfg.cold_interval
+        } else {
+            cfg.interval
+        };
+        if period != self.bootstrap.period {
+            debug!(
+                "Routing table has {peers} peers, periodic bootstrap interval set to {period:?}."
+            );
+            self.set_bootstrap_period(period);
+        }
+    }
+
+    // Keeps the connections of the light client alive for the minimum duration since established,
+    // once it is identified for the first time
+    fn identified_avail_peer(&mut self, peer_id: PeerId, agent_version: &AgentVersion) {
+        let policy = &mut self.connection_policy;
+        if !policy.identified.insert(peer_id)
+            || agent_version.role == IDENTITY_AGENT_ROLE
+            || policy.cfg.light_client_min_duration.is_zero()
+        {
+            return;
+        }
+        for (connection_id, (connected_peer_id, established)) in &policy.established {
+            if *connected_peer_id == peer_id {
+                self.swarm.behaviour_mut().keep_alive.keep_alive(
+                    peer_id,
+                    *connection_id,
+                    *established + policy.cfg.light_client_min_duration,
+                );
+            }
+        }
+    }
+
+    // Closes the connections of the peers not identified as Avail peers within the timeout since established
+    fn expire_unidentified_connections(&mut self) {
+        let timeout = self.connection_policy.cfg.unidentified_timeout;
+        let identified = &self.connection_policy.identified;
+        let expired: Vec<(ConnectionId, PeerId)> = self
+            .connection_policy
+            .established
+            .iter()
+            .filter(|(_, (peer_id, established))| {
+                !identified.contains(peer_id) && established.elapsed() >= timeout
+            })
+            .map(|(connection_id, (peer_id, _))| (*connection_id, *peer_id))
+            .collect();
+        for (connection_id, peer_id) in expired {
+            trace!("Closing connection {connection_id} of peer {peer_id}, not identified within {timeout:?}.");
+            self.connection_policy.established.remove(&connection_id);
+            if self.swarm.close_connection(connection_id) {
+                self.count(MetricCounter::ConnectionExpired);
+            }
+        }
+    }
+
+    // Starts the random walks up to the concurrency limit, once the startup bootstrap is done
+    fn handle_random_walks(&mut self) {
+        if !self.bootstrap.is_startup_done {
+            return;
+        }
+        while self.random_walk.queries.len() < self.random_walk.cfg.concurrency {
+            let target = PeerId::random();
+            trace!("Starting random walk towards {target}.");
+            let query_id = self
+                .swarm
+                .behaviour_mut()
+                .kademlia
+                .get_closest_peers(target);
+            self.random_walk.queries.insert(query_id);
+            self.count(MetricCounter::RandomWalk);
+        }
+    }
+
+    fn set_bootstrap_period(&mut self, period: Duration) {
+        self.bootstrap.timer = interval_at(Instant::now() + period, period);
+        self.bootstrap.period = period;
+    }
+
+    // Returns the batch of routing table entries starting at the cursor, along with the cursor of the next batch, if any
+    fn dht_entries(
+        &mut self,
+        cursor: DHTCursor,
+        batch_size: usize,
+    ) -> (Vec<DHTEntrySnapshot>, Option<DHTCursor>) {
+        let mut entries = Vec::with_capacity(batch_size);
+        let mut next = cursor;
+        for bucket in self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .kbuckets()
+            .skip(cursor.bucket)
+        {
+            for entry in bucket.iter().skip(next.offset) {
+                if entries.len() == batch_size {
+                    return (entries, Some(next));
+                }
+                let peer_id = entry.node.key.preimage();
+                entries.push(DHTEntrySnapshot {
+                    peer_id: *peer_id,
+                    addresses: entry.node.value.iter().cloned().collect(),
+                    is_connected: entry.status == NodeStatus::Connected,
+                    agent_version: self.agent_versions.get(peer_id).cloned(),
+                    location: self.peer_locations.get(peer_id).cloned(),
+                });
+                next.offset += 1;
+            }
+            next.bucket += 1;
+            next.offset = 0;
+        }
+        (entries, None)
+    }
+
+    // Number of entries per non-empty k-bucket, keyed by bucket index
+    fn bucket_peers(&mut self) -> BTreeMap<u32, usize> {
+        let mut bucket_peers = BTreeMap::new();
+        for bucket in self.swarm.behaviour_mut().kademlia.kbuckets() {
+            // bucket with index `i` holds peers at distance in range [2^i, 2^(i+1))
+            let (min_distance, _) = bucket.range();
+            let index = min_distance.ilog2().unwrap_or_default();
+            bucket_peers.insert(index, bucket.num_entries());
+        }
+        bucket_peers
+    }
+
+    // Established connections, the oldest first
+    fn connection_entries(&self) -> Vec<ConnectionEntry> {
+        let mut connections: Vec<&Connection> = self.connections.values().collect();
+        connections.sort_by_key(|connection| connection.established);
+        connections
+            .into_iter()
+            .map(|connection| ConnectionEntry {
+                peer_id: connection.peer_id.to_string(),
+                remote_address: connection.remote_address.to_string(),
+                direction: connection.direction.as_str(),
+                transport: connection.transport.as_str(),
+                agent_version: self.agent_versions.get(&connection.peer_id).cloned(),
+                duration_secs: connection.established.elapsed().as_secs(),
+            })
+            .collect()
+    }
+
+    fn count_routing_table_peers(&self) -> usize {
+        self.routing_table_peers
+    }
+
+    // Starts the bootstrap query, keeping track of its kind and duration
+    fn start_bootstrap(&mut self, kind: BootstrapKind) -> Result<QueryId, kad::NoKnownPeers> {
+        self.count(MetricCounter::BootstrapAttempt(kind));
+        match self.swarm.behaviour_mut().kademlia.bootstrap() {
+            Ok(query_id) => {
+                self.query_timings.started.insert(query_id, Instant::now());
+                self.bootstrap.queries.insert(query_id, kind);
+                Ok(query_id)
+            }
+            Err(err) => {
+                self.count(MetricCounter::BootstrapError(kind));
+                Err(err)
+            }
         }
     }
 }
@@ -0,0 +1,135 @@
+//! Behaviour keeping the connections alive until the deadlines set by the event loop,
+//! even if they are idle for longer than the idle connection timeout.
+//!
+//! It doesn't open or accept any substreams, only the keep-alive of its connection handlers is changed.
+
+use libp2p::{
+    core::{upgrade::DeniedUpgrade, Endpoint},
+    swarm::{
+        handler::ConnectionEvent, ConnectionDenied, ConnectionHandler, ConnectionHandlerEvent,
+        ConnectionId, FromSwarm, NetworkBehaviour, NotifyHandler, SubstreamProtocol, THandler,
+        THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::time::{sleep_until, Instant, Sleep};
+
+#[derive(Default)]
+pub struct Behaviour {
+    pending: VecDeque<(PeerId, ConnectionId, Instant)>,
+}
+
+impl Behaviour {
+    /// Keeps the connection alive until the deadline, replacing the one set before
+    pub fn keep_alive(&mut self, peer_id: PeerId, connection_id: ConnectionId, until: Instant) {
+        self.pending.push_back((peer_id, connection_id, until));
+    }
+}
+
+impl NetworkBehaviour for Behaviour {
+    type ConnectionHandler = Handler;
+    type ToSwarm = Infallible;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(Handler::default())
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _: ConnectionId,
+        _: PeerId,
+        _: &Multiaddr,
+        _: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        Ok(Handler::default())
+    }
+
+    fn on_swarm_event(&mut self, _: FromSwarm) {}
+
+    fn on_connection_handler_event(
+        &mut self,
+        _: PeerId,
+        _: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(&mut self, _: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        match self.pending.pop_front() {
+            Some((peer_id, connection_id, until)) => Poll::Ready(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(connection_id),
+                event: until,
+            }),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Handler {
+    // wakes the connection once the deadline passes, so the idle timeout starts right away
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl ConnectionHandler for Handler {
+    type FromBehaviour = Instant;
+    type ToBehaviour = Infallible;
+    type InboundProtocol = DeniedUpgrade;
+    type OutboundProtocol = DeniedUpgrade;
+    type InboundOpenInfo = ();
+    type OutboundOpenInfo = Infallible;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        SubstreamProtocol::new(DeniedUpgrade, ())
+    }
+
+    fn connection_keep_alive(&self) -> bool {
+        self.deadline
+            .as_ref()
+            .is_some_and(|deadline| Instant::now() < deadline.deadline())
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::ToBehaviour>,
+    > {
+        if let Some(deadline) = &mut self.deadline {
+            if deadline.as_mut().poll(cx).is_ready() {
+                self.deadline = None;
+            }
+        }
+        Poll::Pending
+    }
+
+    fn on_behaviour_event(&mut self, until: Instant) {
+        self.deadline = Some(Box::pin(sleep_until(until)));
+    }
+
+    fn on_connection_event(
+        &mut self,
+        _: ConnectionEvent<
+            Self::InboundProtocol,
+            Self::OutboundProtocol,
+            Self::InboundOpenInfo,
+            Self::OutboundOpenInfo,
+        >,
+    ) {
+    }
+}
@@ -0,0 +1,120 @@
+//! Protocol handing out the connected routing table peers to the connecting clients,
+//! for a faster warm start than walking the DHT through the bootstrap node.
+//!
+//! Request and response are JSON encoded [PeerExchangeRequest] and [PeerExchangeResponse].
+
+use async_trait::async_trait;
+use libp2p::{
+    futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    request_response::{self, ProtocolSupport},
+    StreamProtocol,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{io, time::Duration};
+
+pub const PEER_EXCHANGE_PROTOCOL: StreamProtocol = StreamProtocol::new("/avail/peer-exchange/1");
+const PEER_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+// requests hold only the filters, while the responses hold up to `peer_exchange_max_peers` peers
+const MAX_REQUEST_BYTES: u64 = 1024;
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerExchangeRequest {
+    /// If set, only the peers reporting this client type over identify are returned, e.g. `rust-client`
+    #[serde(default)]
+    pub client_type: Option<String>,
+    /// Maximum number of the returned peers, capped by `peer_exchange_max_peers`
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangedPeer {
+    pub peer_id: String,
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerExchangeResponse {
+    pub peers: Vec<ExchangedPeer>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PeerExchangeCodec;
+
+pub(super) async fn read_json<T, R>(io: &mut R, max_bytes: u64) -> io::Result<T>
+where
+    T: DeserializeOwned,
+    R: AsyncRead + Unpin + Send,
+{
+    let mut bytes = vec![];
+    io.take(max_bytes).read_to_end(&mut bytes).await?;
+    serde_json::from_slice(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub(super) async fn write_json<T, W>(io: &mut W, value: &T) -> io::Result<()>
+where
+    T: Serialize,
+    W: AsyncWrite + Unpin + Send,
+{
+    let bytes = serde_json::to_vec(value)?;
+    io.write_all(&bytes).await?;
+    io.close().await
+}
+
+#[async_trait]
+impl request_response::Codec for PeerExchangeCodec {
+    type Protocol = StreamProtocol;
+    type Request = PeerExchangeRequest;
+    type Response = PeerExchangeResponse;
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_json(io, MAX_REQUEST_BYTES).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_json(io, MAX_RESPONSE_BYTES).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        request: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_json(io, &request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        response: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_json(io, &response).await
+    }
+}
+
+// Bootstrap node only serves the peers, it doesn't ask the others for theirs
+pub fn behaviour() -> request_response::Behaviour<PeerExchangeCodec> {
+    request_response::Behaviour::new(
+        [(PEER_EXCHANGE_PROTOCOL, ProtocolSupport::Inbound)],
+        request_response::Config::default().with_request_timeout(PEER_EXCHANGE_TIMEOUT),
+    )
+}
@@ -0,0 +1,237 @@
+//! JSON-RPC 2.0 admin interface, mapped onto the network [Client] commands.
+
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::{convert::Infallible, future::Future, net::SocketAddr, str::FromStr};
+use tracing::{debug, error, info};
+use warp::{
+    http::StatusCode,
+    hyper::body::Bytes,
+    reply::{self, Reply},
+    Filter,
+};
+
+use crate::{
+    p2p::client::Client,
+    server,
+    types::{Addr, HealthCheckConfig},
+};
+
+const JSONRPC_VERSION: &str = "2.0";
+const MAX_REQUEST_SIZE: u64 = 64 * 1024;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Not set for the notifications, which are answered with no response, unlike the `null` ID
+    #[serde(default, deserialize_with = "deserialize_id")]
+    id: Option<Value>,
+}
+
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+    id: Value,
+}
+
+#[derive(Serialize)]
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Response {
+    fn new(id: Value, result: Result<Value, Error>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Response {
+            jsonrpc: JSONRPC_VERSION,
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Handler {
+    client: Client,
+    health_cfg: HealthCheckConfig,
+}
+
+impl Handler {
+    // Returns nothing if the body holds only the notifications
+    async fn handle_body(&self, body: Bytes) -> Option<Value> {
+        let value: Value = match serde_json::from_slice(&body) {
+            Ok(value) => value,
+            Err(err) => {
+                let error = Error::new(PARSE_ERROR, format!("Parse error: {err}"));
+                return Some(json!(Response::new(Value::Null, Err(error))));
+            }
+        };
+
+        match value {
+            Value::Array(requests) if requests.is_empty() => {
+                let error = Error::new(INVALID_REQUEST, "Invalid request: empty batch");
+                Some(json!(Response::new(Value::Null, Err(error))))
+            }
+            Value::Array(requests) => {
+                let mut responses = vec![];
+                for request in requests {
+                    responses.extend(self.handle_request(request).await);
+                }
+                (!responses.is_empty()).then(|| json!(responses))
+            }
+            request => self
+                .handle_request(request)
+                .await
+                .map(|response| json!(response)),
+        }
+    }
+
+    // Returns no response to the notifications, i.e. the requests without the ID
+    async fn handle_request(&self, value: Value) -> Option<Response> {
+        let request: Request = match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(err) => {
+                let error = Error::new(INVALID_REQUEST, format!("Invalid request: {err}"));
+                return Some(Response::new(Value::Null, Err(error)));
+            }
+        };
+        if request.jsonrpc != JSONRPC_VERSION {
+            let error = Error::new(INVALID_REQUEST, "Unsupported JSON-RPC version");
+            return Some(Response::new(request.id.unwrap_or(Value::Null), Err(error)));
+        }
+
+        debug!("JSON-RPC request: {}", request.method);
+        let result = match request.method.as_str() {
+            "system_health" => self.system_health().await,
+            "p2p_peers" => self.p2p_peers().await,
+            "p2p_dial" => self.p2p_dial(request.params).await,
+            "p2p_ban" => self.p2p_ban(request.params).await,
+            method => Err(Error::new(
+                METHOD_NOT_FOUND,
+                format!("Method not found: {method}"),
+            )),
+        };
+        request.id.map(|id| Response::new(id, result))
+    }
+
+    async fn system_health(&self) -> Result<Value, Error> {
+        let reasons = server::check_health(&self.client, &self.health_cfg).await;
+        Ok(json!({
+            "is_healthy": reasons.is_empty(),
+            "reasons": reasons,
+        }))
+    }
+
+    async fn p2p_peers(&self) -> Result<Value, Error> {
+        let entries = self
+            .client
+            .get_dht_entries()
+            .await
+            .map_err(|err| Error::new(INTERNAL_ERROR, format!("{err:#}")))?;
+        Ok(json!(entries))
+    }
+
+    async fn p2p_dial(&self, params: Value) -> Result<Value, Error> {
+        let multiaddr: Multiaddr = first_param(&params)?
+            .parse()
+            .map_err(|err| Error::new(INVALID_PARAMS, format!("Invalid multiaddress: {err}")))?;
+        self.client
+            .dial(multiaddr)
+            .await
+            .map_err(|err| Error::new(INTERNAL_ERROR, format!("{err:#}")))?;
+        Ok(Value::Null)
+    }
+
+    async fn p2p_ban(&self, params: Value) -> Result<Value, Error> {
+        let peer_id = PeerId::from_str(first_param(&params)?)
+            .map_err(|err| Error::new(INVALID_PARAMS, format!("Invalid peer ID: {err}")))?;
+        self.client
+            .block_peer(peer_id)
+            .await
+            .map_err(|err| Error::new(INTERNAL_ERROR, format!("{err:#}")))?;
+        Ok(Value::Null)
+    }
+}
+
+// Positional parameters are expected, with a single string argument
+fn first_param(params: &Value) -> Result<&str, Error> {
+    params
+        .get(0)
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::new(INVALID_PARAMS, "Expected a single string parameter"))
+}
+
+async fn handle(handler: Handler, body: Bytes) -> Result<reply::Response, Infallible> {
+    Ok(match handler.handle_body(body).await {
+        Some(response) => reply::json(&response).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+pub async fn run(
+    addr: Addr,
+    health_cfg: HealthCheckConfig,
+    client: Client,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let handler = Handler { client, health_cfg };
+    let rpc_route = warp::post()
+        .and(warp::path::end())
+        .and(warp::any().map(move || handler.clone()))
+        .and(warp::body::content_length_limit(MAX_REQUEST_SIZE))
+        .and(warp::body::bytes())
+        .and_then(handle)
+        .with(warp::log::custom(server::access_log))
+        .with(warp::trace(server::request_span));
+
+    let socket_addr: SocketAddr = match addr.try_into() {
+        Ok(socket_addr) => socket_addr,
+        Err(err) => {
+            error!("Unable to start JSON-RPC server: {err:#}");
+            return;
+        }
+    };
+
+    let server = match warp::serve(rpc_route).try_bind_with_graceful_shutdown(socket_addr, shutdown)
+    {
+        Ok((_, server)) => server,
+        Err(err) => {
+            error!("Unable to bind JSON-RPC server to {socket_addr}: {err}");
+            return;
+        }
+    };
+    info!("JSON-RPC server running on http://{socket_addr}.");
+    server.await;
+}
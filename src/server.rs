@@ -1,18 +1,300 @@
-use std::net::SocketAddr;
-use tracing::info;
-use warp::Filter;
+use serde::Serialize;
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+use tokio::time::{timeout, Instant};
+use tracing::{debug, error, info, info_span, Span};
+use warp::{
+    filters::BoxedFilter,
+    http::StatusCode,
+    reply::{self, Reply},
+    Filter, Rejection,
+};
 
-use crate::types::Addr;
+use crate::{
+    crawl::Crawler,
+    p2p::client::{Client, NetworkError},
+    telemetry::{self, Metrics},
+    topology::Topology,
+    types::{Addr, HealthCheckConfig, RuntimeConfig},
+};
 
-pub async fn run(addr: Addr) {
+mod v1;
+
+/// Boxed routes filter, allowing routes of different versions to be composed
+pub type Routes = BoxedFilter<(Box<dyn Reply>,)>;
+
+/// State shared by all the HTTP route handlers
+#[derive(Clone)]
+pub struct SharedState {
+    pub client: Client,
+    pub crawler: Arc<Crawler>,
+    pub topology: Arc<Topology>,
+    pub metrics: Arc<dyn Metrics>,
+    /// Set when metrics are exposed for scraping, instead of being pushed
+    pub prometheus: Option<Arc<telemetry::prometheus::Metrics>>,
+    pub cfg: Arc<RuntimeConfig>,
+}
+
+/// Versioned API, mounted under its own path prefix
+struct ApiVersion {
+    prefix: &'static str,
+    routes: fn(SharedState) -> Routes,
+    /// Sunset date (as HTTP date) announced in the response headers of deprecated versions
+    sunset: Option<&'static str>,
+}
+
+const API_VERSIONS: [ApiVersion; 1] = [ApiVersion {
+    prefix: "v1",
+    routes: v1::routes,
+    sunset: None,
+}];
+
+pub fn with_state(
+    state: SharedState,
+) -> impl Filter<Extract = (SharedState,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// Wraps the route, recording the request count, errors and latency under the given route name
+pub fn instrument<F, R>(name: &'static str, state: &SharedState, route: F) -> Routes
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let metrics = state.metrics.clone();
+    warp::any()
+        .map(Instant::now)
+        .and(route)
+        .and_then(move |start: Instant, reply: R| {
+            let metrics = metrics.clone();
+            async move {
+                let response = reply.into_response();
+                metrics
+                    .record_http_request(name, response.status().as_u16(), start.elapsed())
+                    .await;
+                Ok::<_, Infallible>(Box::new(response) as Box<dyn Reply>)
+            }
+        })
+        .boxed()
+}
+
+// Mounts version routes under the version prefix, marking responses of deprecated versions
+fn mount(version: &ApiVersion, state: SharedState) -> Routes {
+    let routes = warp::path(version.prefix).and((version.routes)(state));
+    match version.sunset {
+        None => routes.boxed(),
+        Some(sunset) => routes
+            .map(move |reply| -> Box<dyn Reply> {
+                let reply = reply::with_header(reply, "Deprecation", "true");
+                Box::new(reply::with_header(reply, "Sunset", sunset))
+            })
+            .boxed(),
+    }
+}
+
+fn routes(state: SharedState) -> Routes {
     let health_route = warp::head()
         .or(warp::get())
+        .unify()
         .and(warp::path("health"))
-        .map(|_| warp::reply::with_status("", warp::http::StatusCode::OK));
+        .and(with_state(state.clone()))
+        .and_then(health);
+    let health_route = instrument("/health", &state, health_route);
+
+    let metrics_route = warp::get()
+        .and(warp::path("metrics"))
+        .and(with_state(state.clone()))
+        .and_then(prometheus_metrics);
+    let metrics_route = instrument("/metrics", &state, metrics_route);
+
+    let routes = health_route.or(metrics_route).unify().boxed();
+    API_VERSIONS.iter().fold(routes, |routes, version| {
+        routes.or(mount(version, state.clone())).unify().boxed()
+    })
+}
+
+/// Creates a tracing span per HTTP request, holding the request method, path and remote IP
+pub fn request_span(info: warp::trace::Info) -> Span {
+    info_span!(
+        "http_request",
+        method = %info.method(),
+        path = info.path(),
+        remote_addr = info.remote_addr().map(|addr| addr.ip().to_string()),
+    )
+}
 
-    info!("HTTP server running on http://{addr}. Health endpoint available at '/health'.");
+/// Emits access log event for a served HTTP request, within the [request_span]
+pub fn access_log(info: warp::log::Info) {
+    info!(
+        status = info.status().as_u16(),
+        latency_ms = info.elapsed().as_secs_f64() * 1000.0,
+        "HTTP request served."
+    );
+}
+
+/// Status of the response to the failed network client command
+fn network_error_status(err: &NetworkError) -> StatusCode {
+    match err {
+        NetworkError::ChannelClosed { .. } | NetworkError::NoKnownPeers => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        NetworkError::Overloaded { .. } => StatusCode::TOO_MANY_REQUESTS,
+        NetworkError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+        NetworkError::DialFailure { .. } | NetworkError::QueryFailed { .. } => {
+            StatusCode::BAD_GATEWAY
+        }
+        NetworkError::ListenFailure { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    reasons: Vec<String>,
+}
+
+pub async fn check_health(client: &Client, cfg: &HealthCheckConfig) -> Vec<String> {
+    // ping round-trip comes first, other checks are pointless when the event loop is stuck
+    match timeout(cfg.timeout, client.ping()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => return vec![format!("Event loop is not running: {err:#}")],
+        Err(_) => {
+            return vec![format!(
+                "Event loop did not respond within {:?}",
+                cfg.timeout
+            )]
+        }
+    }
+
+    let mut reasons = vec![];
+    match timeout(cfg.timeout, client.get_health_state()).await {
+        Ok(Ok(state)) => {
+            if !state.is_startup_bootstrap_done {
+                reasons.push("Initial bootstrap is not done".to_string());
+            }
+            if state.listeners == 0 {
+                reasons.push("No active P2P listeners".to_string());
+            }
+        }
+        Ok(Err(err)) => reasons.push(format!("Unable to get event loop state: {err:#}")),
+        Err(_) => reasons.push("Event loop state request timed out".to_string()),
+    }
+    match timeout(cfg.timeout, client.count_dht_entries()).await {
+        Ok(Ok(peers)) if peers < cfg.min_dht_peers => reasons.push(format!(
+            "Routing table has {peers} peers, at least {} required",
+            cfg.min_dht_peers
+        )),
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => reasons.push(format!("Unable to count routing table peers: {err:#}")),
+        Err(_) => reasons.push("Routing table peer count request timed out".to_string()),
+    }
+    reasons
+}
+
+async fn health(state: SharedState) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    let cfg = HealthCheckConfig::from(state.cfg.as_ref());
+    let reasons = check_health(&state.client, &cfg).await;
+    if reasons.is_empty() {
+        let response = HealthResponse {
+            status: "healthy",
+            reasons,
+        };
+        return Ok(reply::with_status(reply::json(&response), StatusCode::OK));
+    }
+
+    debug!("Health check failed: {}", reasons.join(". "));
+    let response = HealthResponse {
+        status: "unhealthy",
+        reasons,
+    };
+    Ok(reply::with_status(
+        reply::json(&response),
+        StatusCode::SERVICE_UNAVAILABLE,
+    ))
+}
+
+async fn prometheus_metrics(state: SharedState) -> Result<Box<dyn Reply>, Rejection> {
+    let Some(metrics) = state.prometheus else {
+        return Err(warp::reject::not_found());
+    };
+    match metrics.encode() {
+        Ok(body) => Ok(Box::new(reply::with_header(
+            body,
+            "Content-Type",
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        ))),
+        Err(err) => {
+            error!("{err:#}");
+            Ok(Box::new(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn run_unix(
+    path: &str,
+    routes: impl Filter<Extract = (impl Reply,), Error = warp::Rejection>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    use tokio::net::UnixListener;
+    use tokio_stream::wrappers::UnixListenerStream;
+
+    // socket file left over from a previous run would prevent binding
+    if let Err(err) = std::fs::remove_file(path) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            error!("Unable to remove existing unix socket {path}: {err}");
+            return;
+        }
+    }
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Unable to bind HTTP server to unix socket {path}: {err}");
+            return;
+        }
+    };
+
+    info!("HTTP server running on unix socket {path}. Health endpoint available at '/health'.");
+
+    warp::serve(routes)
+        .serve_incoming_with_graceful_shutdown(UnixListenerStream::new(listener), shutdown)
+        .await;
+    _ = std::fs::remove_file(path);
+}
 
-    let socket_addr: SocketAddr = addr.try_into().unwrap();
+pub async fn run(
+    addr: Addr,
+    state: SharedState,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let unix_socket = state.cfg.http_server_unix_socket.clone();
+    let routes = routes(state)
+        .with(warp::log::custom(access_log))
+        .with(warp::trace(request_span));
 
-    warp::serve(health_route).run(socket_addr).await;
+    match unix_socket {
+        #[cfg(unix)]
+        Some(path) => run_unix(&path, routes, shutdown).await,
+        #[cfg(not(unix))]
+        Some(_) => {
+            error!("Serving HTTP server on unix socket is not supported on this platform");
+        }
+        None => {
+            let socket_addr: SocketAddr = addr.try_into().unwrap();
+            let server =
+                match warp::serve(routes).try_bind_with_graceful_shutdown(socket_addr, shutdown) {
+                    Ok((_, server)) => server,
+                    Err(err) => {
+                        error!("Unable to bind HTTP server to {socket_addr}: {err}");
+                        return;
+                    }
+                };
+            info!("HTTP server running on http://{socket_addr}. Health endpoint available at '/health'.");
+            server.await;
+        }
+    }
+    info!("HTTP server stopped.");
 }
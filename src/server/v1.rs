@@ -0,0 +1,199 @@
+use std::convert::Infallible;
+use tokio_stream::{self as stream, wrappers::BroadcastStream, StreamExt};
+use tracing::{debug, warn};
+use warp::{
+    http::StatusCode,
+    hyper::Body,
+    reply::{self, Reply},
+    sse, Filter,
+};
+
+use super::{instrument, network_error_status, with_state, Routes, SharedState};
+use crate::{p2p::client::NetworkError, types::BUILD_INFO};
+
+// number of routing table entries serialized at once
+const DHT_BATCH_SIZE: usize = 100;
+
+async fn start_crawl(state: SharedState) -> Result<impl Reply, Infallible> {
+    if !state.crawler.start() {
+        return Ok(reply::with_status(
+            "Crawl already in progress",
+            StatusCode::CONFLICT,
+        ));
+    }
+    Ok(reply::with_status("", StatusCode::ACCEPTED))
+}
+
+async fn latest_crawl(state: SharedState) -> Result<reply::Response, Infallible> {
+    match state.crawler.latest().await {
+        Some(summary) => Ok(reply::json(&summary).into_response()),
+        None => {
+            Ok(reply::with_status("No crawl completed yet", StatusCode::NOT_FOUND).into_response())
+        }
+    }
+}
+
+async fn latest_topology(state: SharedState) -> Result<reply::Response, Infallible> {
+    match state.topology.latest().await {
+        Some(snapshot) => Ok(reply::json(&snapshot).into_response()),
+        None => Ok(
+            reply::with_status("No topology snapshot taken yet", StatusCode::NOT_FOUND)
+                .into_response(),
+        ),
+    }
+}
+
+async fn query_latencies(state: SharedState) -> Result<reply::Response, Infallible> {
+    match state.client.get_query_latencies().await {
+        Ok(latencies) => Ok(reply::json(&latencies).into_response()),
+        Err(err) => Ok(reply::with_status(
+            format!("Unable to get query latencies: {err:#}"),
+            network_error_status(&err),
+        )
+        .into_response()),
+    }
+}
+
+async fn summary(state: SharedState) -> Result<reply::Response, Infallible> {
+    match state.client.get_startup_summary().await {
+        Ok(summary) => Ok(reply::json(&summary).into_response()),
+        Err(err) => Ok(reply::with_status(
+            format!("Unable to get node summary: {err:#}"),
+            network_error_status(&err),
+        )
+        .into_response()),
+    }
+}
+
+async fn external_addresses(state: SharedState) -> Result<reply::Response, Infallible> {
+    match state.client.get_external_addresses().await {
+        Ok(addresses) => Ok(reply::json(&addresses).into_response()),
+        Err(err) => Ok(reply::with_status(
+            format!("Unable to get external addresses: {err:#}"),
+            network_error_status(&err),
+        )
+        .into_response()),
+    }
+}
+
+// Streams the routing table entries as JSON array, serialized batch by batch as they are fetched from the event loop
+async fn dht(state: SharedState) -> Result<impl Reply, Infallible> {
+    let mut is_first = true;
+    let entries = state
+        .client
+        .stream_dht_entries(DHT_BATCH_SIZE)
+        .map(move |batch| {
+            let mut chunk = String::new();
+            for entry in batch? {
+                // entries failing to serialize are skipped, so the array stays valid
+                let json = match serde_json::to_string(&entry) {
+                    Ok(json) => json,
+                    Err(err) => {
+                        warn!("Skipping routing table entry of {}: {err}", entry.peer_id);
+                        continue;
+                    }
+                };
+                if !std::mem::take(&mut is_first) {
+                    chunk.push(',');
+                }
+                chunk.push_str(&json);
+            }
+            Ok::<_, NetworkError>(chunk)
+        });
+    // response is aborted if any of the batches fail, since the status is already sent
+    let body = stream::once(Ok("[".to_string()))
+        .chain(entries)
+        .chain(stream::once(Ok("]".to_string())));
+    Ok(reply::with_header(
+        reply::Response::new(Body::wrap_stream(body)),
+        "Content-Type",
+        "application/json",
+    ))
+}
+
+// Streams the network events as server-sent events, skipping the ones missed by the lagging client
+async fn events(state: SharedState) -> Result<impl Reply, Infallible> {
+    let events = BroadcastStream::new(state.client.subscribe()).filter_map(|event| match event {
+        Ok(event) => Some(sse::Event::default().json_data(event)),
+        Err(err) => {
+            debug!("Events stream client is lagging behind: {err}");
+            None
+        }
+    });
+    Ok(sse::reply(sse::keep_alive().stream(events)))
+}
+
+async fn version() -> Result<impl Reply, Infallible> {
+    Ok(reply::json(&BUILD_INFO))
+}
+
+pub fn routes(state: SharedState) -> Routes {
+    let crawl_route = warp::post()
+        .and(warp::path!("crawl"))
+        .and(with_state(state.clone()))
+        .and_then(start_crawl);
+
+    let latest_crawl_route = warp::get()
+        .and(warp::path!("crawl" / "latest"))
+        .and(with_state(state.clone()))
+        .and_then(latest_crawl);
+
+    let topology_route = warp::get()
+        .and(warp::path!("topology"))
+        .and(with_state(state.clone()))
+        .and_then(latest_topology);
+
+    let query_latencies_route = warp::get()
+        .and(warp::path!("metrics" / "queries"))
+        .and(with_state(state.clone()))
+        .and_then(query_latencies);
+
+    let summary_route = warp::get()
+        .and(warp::path!("summary"))
+        .and(with_state(state.clone()))
+        .and_then(summary);
+
+    let external_addresses_route = warp::get()
+        .and(warp::path!("external_addresses"))
+        .and(with_state(state.clone()))
+        .and_then(external_addresses);
+
+    let dht_route = warp::get()
+        .and(warp::path!("dht"))
+        .and(with_state(state.clone()))
+        .and_then(dht);
+
+    let events_route = warp::get()
+        .and(warp::path!("events"))
+        .and(with_state(state.clone()))
+        .and_then(events);
+
+    let version_route = warp::get().and(warp::path!("version")).and_then(version);
+
+    instrument("/v1/crawl", &state, crawl_route)
+        .or(instrument("/v1/crawl/latest", &state, latest_crawl_route))
+        .unify()
+        .or(instrument("/v1/topology", &state, topology_route))
+        .unify()
+        .or(instrument(
+            "/v1/metrics/queries",
+            &state,
+            query_latencies_route,
+        ))
+        .unify()
+        .or(instrument("/v1/summary", &state, summary_route))
+        .unify()
+        .or(instrument(
+            "/v1/external_addresses",
+            &state,
+            external_addresses_route,
+        ))
+        .unify()
+        .or(instrument("/v1/dht", &state, dht_route))
+        .unify()
+        .or(instrument("/v1/events", &state, events_route))
+        .unify()
+        .or(instrument("/v1/version", &state, version_route))
+        .unify()
+        .boxed()
+}
@@ -0,0 +1,92 @@
+//! Shutdown coordination, letting any of the node tasks trigger an orderly teardown of the others.
+
+use std::{future::Future, sync::Arc};
+use tokio::{
+    sync::watch,
+    task::{JoinError, JoinHandle},
+};
+use tracing::error;
+
+/// Shared by all the tasks, holding the reason of the shutdown once it is triggered
+#[derive(Clone)]
+pub struct Controller {
+    reason: Arc<watch::Sender<Option<String>>>,
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        let (reason, _) = watch::channel(None);
+        Controller {
+            reason: Arc::new(reason),
+        }
+    }
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Triggers the shutdown, keeping the reason of the first trigger
+    pub fn trigger_shutdown(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.reason.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(reason);
+            true
+        });
+    }
+
+    pub fn is_shutdown_triggered(&self) -> bool {
+        self.reason.borrow().is_some()
+    }
+
+    /// Reason of the shutdown, if triggered
+    pub fn reason(&self) -> Option<String> {
+        self.reason.borrow().clone()
+    }
+
+    /// Completes once the shutdown is triggered
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.reason.subscribe();
+        async move {
+            // sender is owned by the controller, so it isn't dropped while waiting
+            _ = receiver.wait_for(Option::is_some).await;
+        }
+    }
+
+    /// Spawns the task expected to run until the shutdown,
+    /// triggering the shutdown if the task stops or panics before
+    pub fn spawn<F>(&self, name: &'static str, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let controller = self.clone();
+        tokio::spawn(async move {
+            let result = tokio::spawn(task).await;
+            let reason = match result {
+                Ok(()) if controller.is_shutdown_triggered() => return,
+                Ok(()) => format!("{name} stopped unexpectedly"),
+                Err(err) => format!("{name} {}", failure(err)),
+            };
+            error!("{reason}, shutting down.");
+            controller.trigger_shutdown(reason);
+        })
+    }
+}
+
+/// Describes the failure of the task, including the panic message if it panicked
+pub fn failure(err: JoinError) -> String {
+    if !err.is_panic() {
+        return format!("failed: {err}");
+    }
+    let panic = err.into_panic();
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    format!("panicked: {message}")
+}
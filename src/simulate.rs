@@ -0,0 +1,252 @@
+//! Load generation against a running bootstrap node, used to capacity-test it before the network events.
+//!
+//! Each simulated client is a lightweight libp2p peer dialing the bootstrap node over TCP,
+//! running identify and random Kademlia lookups until the simulation ends.
+
+use anyhow::{Context, Result};
+use libp2p::{
+    futures::StreamExt,
+    identify,
+    identity::Keypair,
+    kad::{self, store::MemoryStore, Mode, QueryId, QueryResult},
+    noise,
+    swarm::{NetworkBehaviour, SwarmEvent},
+    tcp, yamux, Multiaddr, PeerId, Swarm, SwarmBuilder,
+};
+use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
+use tokio::time::{interval_at, sleep_until, Instant, MissedTickBehavior};
+use tracing::debug;
+
+use crate::{
+    p2p::client::LatencySummary,
+    types::{AgentVersion, IdentifyConfig, KademliaConfig},
+};
+
+// client type reported over identify, so the simulated clients are told apart in the bootstrap node metrics
+const SIMULATED_CLIENT_TYPE: &str = "simulated-client";
+
+#[derive(NetworkBehaviour)]
+struct SimulatedBehaviour {
+    kademlia: kad::Behaviour<MemoryStore>,
+    identify: identify::Behaviour,
+}
+
+/// Load generation options
+pub struct SimulationConfig {
+    /// Address of the bootstrap node, dialed by all the clients
+    pub target: Multiaddr,
+    pub clients: usize,
+    pub duration: Duration,
+    /// Interval of the random lookups of each client, with the first ones spread evenly across the clients
+    pub lookup_interval: Duration,
+    pub kademlia: KademliaConfig,
+    pub identify: IdentifyConfig,
+    /// Prologue of the Noise handshakes, the same as the one of the bootstrap node
+    pub noise_prologue: Vec<u8>,
+}
+
+/// Throughput and latencies observed by the simulated clients
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    pub clients: usize,
+    /// Clients connected to the bootstrap node
+    pub connected: usize,
+    pub dial_failures: usize,
+    /// Connections to the bootstrap node closed before the end of the simulation
+    pub disconnections: usize,
+    /// Clients which received the identify info of the bootstrap node
+    pub identified: usize,
+    pub connect_latency: LatencySummary,
+    pub failed_lookups: u64,
+    /// Completed lookups per second, over the whole simulation
+    pub lookups_per_second: f64,
+    pub lookup_latency: LatencySummary,
+}
+
+// Outcome of a single simulated client
+#[derive(Default)]
+struct ClientStats {
+    connect_latency: Option<Duration>,
+    is_dial_failed: bool,
+    is_disconnected: bool,
+    is_identified: bool,
+    lookup_latencies: Vec<Duration>,
+    failed_lookups: u64,
+}
+
+/// Runs the simulated clients until the simulation duration passes, returning their report
+pub async fn run(cfg: SimulationConfig) -> Result<SimulationReport> {
+    let started = Instant::now();
+    let deadline = started + cfg.duration;
+    let mut clients = Vec::with_capacity(cfg.clients);
+    for index in 0..cfg.clients {
+        let swarm = build_swarm(&cfg).context("Failed to build simulated client")?;
+        let offset = cfg
+            .lookup_interval
+            .mul_f64(index as f64 / cfg.clients as f64);
+        clients.push(tokio::spawn(run_client(
+            swarm,
+            cfg.target.clone(),
+            offset,
+            cfg.lookup_interval,
+            deadline,
+        )));
+    }
+
+    let mut stats = Vec::with_capacity(clients.len());
+    for client in clients {
+        stats.push(client.await.context("Simulated client panicked")?);
+    }
+    Ok(report(stats, started.elapsed()))
+}
+
+fn build_swarm(cfg: &SimulationConfig) -> Result<Swarm<SimulatedBehaviour>> {
+    let agent_version = AgentVersion {
+        client_type: SIMULATED_CLIENT_TYPE.to_string(),
+        ..cfg.identify.agent_version.clone()
+    };
+    let mut kad_cfg = kad::Config::default();
+    kad_cfg
+        .set_query_timeout(cfg.kademlia.query_timeout)
+        .set_protocol_names(vec![cfg.kademlia.protocol_name.clone()])
+        .disjoint_query_paths(cfg.kademlia.is_disjoint_query_paths)
+        .set_parallelism(cfg.kademlia.query_parallelism)
+        .set_replication_factor(cfg.kademlia.replication_factor);
+
+    let mut swarm = SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default(),
+            |key: &Keypair| {
+                noise::Config::new(key).map(|noise| noise.with_prologue(cfg.noise_prologue.clone()))
+            },
+            yamux::Config::default,
+        )?
+        .with_dns()?
+        .with_behaviour(|key| {
+            let peer_id = key.public().to_peer_id();
+            let identify_cfg =
+                identify::Config::new(cfg.identify.protocol_version.clone(), key.public())
+                    .with_agent_version(agent_version.to_string());
+            SimulatedBehaviour {
+                kademlia: kad::Behaviour::with_config(peer_id, MemoryStore::new(peer_id), kad_cfg),
+                identify: identify::Behaviour::new(identify_cfg),
+            }
+        })?
+        // connection is kept for the whole simulation, even if idle between the lookups
+        .with_swarm_config(|c| c.with_idle_connection_timeout(cfg.duration))
+        .build();
+
+    // Kademlia protocol is advertised, so the clients are accepted the same way as the light clients,
+    // while, without any listen addresses, they are not added to the routing table
+    swarm.behaviour_mut().kademlia.set_mode(Some(Mode::Server));
+    Ok(swarm)
+}
+
+// Dials the bootstrap node and runs the lookups until the deadline,
+// stopping early if the bootstrap node is not reachable or closes the connection
+async fn run_client(
+    mut swarm: Swarm<SimulatedBehaviour>,
+    target: Multiaddr,
+    offset: Duration,
+    lookup_interval: Duration,
+    deadline: Instant,
+) -> ClientStats {
+    let mut stats = ClientStats::default();
+    let dialed = Instant::now();
+    if let Err(err) = swarm.dial(target.clone()) {
+        debug!("Simulated client unable to dial {target}: {err}.");
+        stats.is_dial_failed = true;
+        return stats;
+    }
+
+    let mut lookups = interval_at(dialed + offset, lookup_interval);
+    // lookups missed while connecting are not run in a burst once connected
+    lookups.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut pending_lookups: HashMap<QueryId, Instant> = HashMap::new();
+    // peer ID of the bootstrap node, known once connected
+    let mut target_peer: Option<PeerId> = None;
+    let end = sleep_until(deadline);
+    tokio::pin!(end);
+    loop {
+        tokio::select! {
+            _ = &mut end => break,
+            _ = lookups.tick(), if target_peer.is_some() => {
+                let query_id = swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .get_closest_peers(PeerId::random());
+                pending_lookups.insert(query_id, Instant::now());
+            }
+            event = swarm.select_next_some() => match event {
+                // lookups connect to the other peers as well
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if target_peer.is_none() => {
+                    stats.connect_latency = Some(dialed.elapsed());
+                    target_peer = Some(peer_id);
+                    swarm
+                        .behaviour_mut()
+                        .kademlia
+                        .add_address(&peer_id, target.clone());
+                }
+                SwarmEvent::OutgoingConnectionError { error, .. } if target_peer.is_none() => {
+                    debug!("Simulated client unable to connect to {target}: {error}.");
+                    stats.is_dial_failed = true;
+                    break;
+                }
+                SwarmEvent::ConnectionClosed {
+                    peer_id,
+                    num_established: 0,
+                    ..
+                } if target_peer == Some(peer_id) => {
+                    stats.is_disconnected = true;
+                    break;
+                }
+                SwarmEvent::Behaviour(SimulatedBehaviourEvent::Identify(
+                    identify::Event::Received { peer_id, .. },
+                )) if target_peer == Some(peer_id) => {
+                    stats.is_identified = true;
+                }
+                SwarmEvent::Behaviour(SimulatedBehaviourEvent::Kademlia(
+                    kad::Event::OutboundQueryProgressed {
+                        id,
+                        result: QueryResult::GetClosestPeers(result),
+                        step,
+                        ..
+                    },
+                )) if step.last => {
+                    let Some(started) = pending_lookups.remove(&id) else {
+                        continue;
+                    };
+                    match result {
+                        Ok(_) => stats.lookup_latencies.push(started.elapsed()),
+                        Err(_) => stats.failed_lookups += 1,
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    stats
+}
+
+fn report(stats: Vec<ClientStats>, elapsed: Duration) -> SimulationReport {
+    let count =
+        |is_counted: fn(&ClientStats) -> bool| stats.iter().filter(|s| is_counted(s)).count();
+    let connect_latencies: Vec<Duration> = stats.iter().filter_map(|s| s.connect_latency).collect();
+    let lookup_latencies: Vec<Duration> = stats
+        .iter()
+        .flat_map(|s| s.lookup_latencies.iter().copied())
+        .collect();
+    SimulationReport {
+        clients: stats.len(),
+        connected: connect_latencies.len(),
+        dial_failures: count(|s| s.is_dial_failed),
+        disconnections: count(|s| s.is_disconnected),
+        identified: count(|s| s.is_identified),
+        connect_latency: LatencySummary::new(connect_latencies.len() as u64, connect_latencies),
+        failed_lookups: stats.iter().map(|s| s.failed_lookups).sum(),
+        lookups_per_second: lookup_latencies.len() as f64 / elapsed.as_secs_f64(),
+        lookup_latency: LatencySummary::new(lookup_latencies.len() as u64, lookup_latencies),
+    }
+}
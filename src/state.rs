@@ -0,0 +1,261 @@
+//! Persistent state of the node, kept in the `state_path` directory.
+//!
+//! Directory holds a manifest with the version of its layout and file formats,
+//! so the state written by the earlier versions is migrated once the node is upgraded.
+
+use anyhow::{Context, Result};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::{
+    crawl::Crawler,
+    p2p::client::{Client, ExternalAddress},
+    topology::TopologySnapshot,
+};
+
+/// Version of the state directory written by this version of the node
+const STATE_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "state.toml";
+const IDENTITY_FILE: &str = "identity.toml";
+const ROUTING_TABLE_FILE: &str = "routing_table.json";
+const BLOCKED_PEERS_FILE: &str = "blocked_peers.json";
+const CRAWL_FILE: &str = "crawl.json";
+const EXTERNAL_ADDRESSES_FILE: &str = "external_addresses.json";
+// topology snapshots are written into this subdirectory, one file per snapshot
+const TOPOLOGY_DIR: &str = "topology";
+const TOPOLOGY_FILE_PREFIX: &str = "topology-";
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RoutingTablePeer {
+    peer_id: String,
+    addresses: Vec<String>,
+}
+
+/// Path of the identity file in the state directory
+pub fn identity_path(state_path: &str) -> String {
+    Path::new(state_path)
+        .join(IDENTITY_FILE)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[derive(Clone)]
+pub struct State {
+    path: PathBuf,
+}
+
+impl State {
+    /// Opens the state directory, creating it on the first start and migrating the state of the earlier versions.
+    /// Identity file at `identity_path` is copied into the directory, if the node was started without it before.
+    pub fn open(path: &str, identity_path: &str) -> Result<Self> {
+        std::fs::create_dir_all(path)
+            .context(format!("Failed to create state directory {path}"))?;
+        let state = State { path: path.into() };
+
+        let manifest_path = state.path.join(MANIFEST_FILE);
+        let mut version = match std::fs::read_to_string(&manifest_path) {
+            Ok(content) => {
+                let manifest: Manifest = toml::from_str(&content).context(format!(
+                    "Failed to parse state manifest {}",
+                    manifest_path.display()
+                ))?;
+                manifest.version
+            }
+            // directory is either new or written before the state was versioned
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => {
+                return Err(err).context(format!(
+                    "Failed to read state manifest {}",
+                    manifest_path.display()
+                ))
+            }
+        };
+        if version > STATE_VERSION {
+            anyhow::bail!("State directory {path} is written by a newer version of the node (state version {version}, supported {STATE_VERSION})");
+        }
+
+        while version < STATE_VERSION {
+            match version {
+                0 => state.migrate_identity(identity_path)?,
+                _ => unreachable!("state version {version} has no migration"),
+            }
+            version += 1;
+            info!("State directory {path} migrated to version {version}.");
+        }
+
+        let manifest = toml::to_string(&Manifest { version })?;
+        std::fs::write(&manifest_path, manifest).context(format!(
+            "Failed to write state manifest {}",
+            manifest_path.display()
+        ))?;
+        Ok(state)
+    }
+
+    // Identity file kept at `identity_path` is copied into the state directory
+    fn migrate_identity(&self, identity_path: &str) -> Result<()> {
+        let state_identity = self.path.join(IDENTITY_FILE);
+        if state_identity.exists() || !Path::new(identity_path).exists() {
+            return Ok(());
+        }
+        // copy keeps the owner only permissions of the identity file
+        std::fs::copy(identity_path, &state_identity).context(format!(
+            "Failed to copy identity file {identity_path} into the state directory"
+        ))?;
+        info!(
+            "Identity file {identity_path} copied to {}, it can be removed.",
+            state_identity.display()
+        );
+        Ok(())
+    }
+
+    fn read<T: DeserializeOwned>(&self, file: &str) -> Result<Option<T>> {
+        let path = self.path.join(file);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context(format!("Failed to read {}", path.display())),
+        };
+        let value = serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", path.display()))?;
+        Ok(Some(value))
+    }
+
+    // Written into the temporary file first, so the state isn't left half-written
+    fn write<T: Serialize>(&self, file: &str, value: &T) -> Result<()> {
+        let path = self.path.join(file);
+        let temp_path = path.with_extension("tmp");
+        let content = serde_json::to_string_pretty(value)?;
+        std::fs::write(&temp_path, content)
+            .context(format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Restores the state saved on the last shutdown.
+    /// Peers of the routing table are dialed, to be added back once they are reachable,
+    /// and the external addresses are probed by AutoNAT, to be advertised once confirmed again.
+    pub async fn restore(&self, client: &Client, crawler: &Crawler) -> Result<()> {
+        let blocked_peers: Vec<String> = self.read(BLOCKED_PEERS_FILE)?.unwrap_or_default();
+        for peer_id in &blocked_peers {
+            let peer_id: PeerId = peer_id
+                .parse()
+                .context(format!("Invalid blocked peer ID {peer_id}"))?;
+            client.block_peer(peer_id).await?;
+        }
+
+        if let Some(summary) = self.read(CRAWL_FILE)? {
+            crawler.restore(summary).await;
+        }
+
+        let external_addresses: Vec<ExternalAddress> =
+            self.read(EXTERNAL_ADDRESSES_FILE)?.unwrap_or_default();
+        let external_address_count = external_addresses.len();
+        client
+            .restore_external_addresses(external_addresses)
+            .await?;
+
+        let peers: Vec<RoutingTablePeer> = self.read(ROUTING_TABLE_FILE)?.unwrap_or_default();
+        let mut dialed = 0;
+        for peer in &peers {
+            let Ok(peer_id) = peer.peer_id.parse::<PeerId>() else {
+                warn!("Skipping invalid routing table peer ID {}", peer.peer_id);
+                continue;
+            };
+            for address in &peer.addresses {
+                let Ok(multiaddr) = address.parse::<Multiaddr>() else {
+                    warn!("Skipping invalid address {address} of peer {peer_id}");
+                    continue;
+                };
+                match client.dial(multiaddr.with(Protocol::P2p(peer_id))).await {
+                    Ok(()) => dialed += 1,
+                    Err(err) => debug!("Unable to dial peer {peer_id} at {address}: {err:#}"),
+                }
+            }
+        }
+        info!(
+            "State restored: {} blocked peers, {external_address_count} external addresses, {dialed} addresses of {} routing table peers dialed.",
+            blocked_peers.len(),
+            peers.len()
+        );
+        Ok(())
+    }
+
+    /// Saves the routing table, blocked peers, external addresses and the latest crawl
+    pub async fn save(&self, client: &Client, crawler: &Crawler) -> Result<()> {
+        let peers: Vec<RoutingTablePeer> = client
+            .get_dht_entries()
+            .await?
+            .into_iter()
+            .map(|entry| RoutingTablePeer {
+                peer_id: entry.peer_id,
+                addresses: entry.addresses,
+            })
+            .collect();
+        self.write(ROUTING_TABLE_FILE, &peers)?;
+
+        let blocked_peers: Vec<String> = client
+            .get_blocked_peers()
+            .await?
+            .iter()
+            .map(PeerId::to_string)
+            .collect();
+        self.write(BLOCKED_PEERS_FILE, &blocked_peers)?;
+
+        let external_addresses = client.get_external_addresses().await?;
+        self.write(EXTERNAL_ADDRESSES_FILE, &external_addresses)?;
+
+        if let Some(summary) = crawler.latest().await {
+            self.write(CRAWL_FILE, &summary)?;
+        }
+        info!(
+            "State saved: {} routing table peers, {} blocked peers, {} external addresses.",
+            peers.len(),
+            blocked_peers.len(),
+            external_addresses.len()
+        );
+        Ok(())
+    }
+
+    /// Writes the topology snapshot, removing the oldest ones beyond the `retention` most recent, unless it is 0
+    pub fn save_topology(&self, snapshot: &TopologySnapshot, retention: usize) -> Result<()> {
+        let dir = self.path.join(TOPOLOGY_DIR);
+        std::fs::create_dir_all(&dir)
+            .context(format!("Failed to create directory {}", dir.display()))?;
+        let file = format!("{TOPOLOGY_FILE_PREFIX}{}.json", snapshot.taken_at);
+        self.write(&format!("{TOPOLOGY_DIR}/{file}"), snapshot)?;
+        if retention == 0 {
+            return Ok(());
+        }
+
+        let mut snapshots: Vec<(u64, PathBuf)> = std::fs::read_dir(&dir)
+            .context(format!("Failed to read directory {}", dir.display()))?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let taken_at = path
+                    .file_stem()?
+                    .to_str()?
+                    .strip_prefix(TOPOLOGY_FILE_PREFIX)?
+                    .parse()
+                    .ok()?;
+                (path.extension()? == "json").then_some((taken_at, path))
+            })
+            .collect();
+        snapshots.sort();
+        let expired = snapshots.len().saturating_sub(retention);
+        for (_, path) in snapshots.into_iter().take(expired) {
+            if let Err(err) = std::fs::remove_file(&path) {
+                warn!(
+                    "Unable to remove topology snapshot {}: {err}",
+                    path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! systemd service notifications, for the `Type=notify` units and the systemd watchdog.
+//! Notifications are sent only if the node is started by systemd, which sets `NOTIFY_SOCKET`.
+
+use sd_notify::NotifyState;
+use std::{future::Future, time::Duration};
+use tokio::time::{interval, timeout};
+use tracing::{debug, info, warn};
+
+use crate::p2p::client::Client;
+
+fn notify(state: NotifyState) {
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        warn!("Unable to notify systemd: {err}");
+    }
+}
+
+/// Notifies systemd that the node is started, once it is listening and bootstrapped
+pub fn notify_ready() {
+    notify(NotifyState::Ready);
+}
+
+pub fn notify_stopping() {
+    notify(NotifyState::Stopping);
+}
+
+/// Pings the systemd watchdog, if enabled by `WatchdogSec`, twice per the watchdog interval.
+/// Pings are skipped while the network event loop doesn't respond, so systemd restarts the wedged node.
+/// Runs until the shutdown, even if the watchdog is not enabled.
+pub async fn run_watchdog(client: Client, shutdown: impl Future<Output = ()> + Send + 'static) {
+    let mut watchdog_usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut watchdog_usec) {
+        debug!("systemd watchdog is not enabled.");
+        shutdown.await;
+        return;
+    }
+    let period = Duration::from_micros(watchdog_usec) / 2;
+    info!("Pinging systemd watchdog every {period:?}.");
+
+    let mut interval = interval(period);
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut shutdown => break,
+        }
+        match timeout(period, client.ping()).await {
+            Ok(Ok(())) => notify(NotifyState::Watchdog),
+            Ok(Err(err)) => {
+                warn!("Network event loop is unavailable, skipping watchdog ping: {err:#}")
+            }
+            Err(_) => warn!(
+                "Network event loop didn't respond within {period:?}, skipping watchdog ping."
+            ),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{sync::Arc, time::Duration};
+
+use super::{MetricCounter, MetricHistogram, MetricValue};
+
+/// Metrics implementation which forwards everything to multiple backends at once
+pub struct Metrics {
+    backends: Vec<Arc<dyn super::Metrics>>,
+}
+
+impl Metrics {
+    pub fn new(backends: Vec<Arc<dyn super::Metrics>>) -> Self {
+        Self { backends }
+    }
+}
+
+#[async_trait]
+impl super::Metrics for Metrics {
+    async fn count_n(&self, counter: MetricCounter, value: u64) {
+        for backend in &self.backends {
+            backend.count_n(counter.clone(), value).await;
+        }
+    }
+
+    // all backends are recorded to, even if some of them fail, with the first error returned
+    async fn record(&self, value: MetricValue) -> Result<()> {
+        let mut result = Ok(());
+        for backend in &self.backends {
+            if let Err(err) = backend.record(value.clone()).await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64) {
+        for backend in &self.backends {
+            backend.record_histogram(histogram, value).await;
+        }
+    }
+
+    async fn set_multiaddress(&self, multiaddr: String) {
+        for backend in &self.backends {
+            backend.set_multiaddress(multiaddr.clone()).await;
+        }
+    }
+
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration) {
+        for backend in &self.backends {
+            backend.record_http_request(route, status, latency).await;
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        let mut result = Ok(());
+        for backend in &self.backends {
+            if let Err(err) = backend.shutdown().await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
@@ -1,15 +1,808 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+use tracing::warn;
 
+use crate::types::BUILD_INFO;
+
+pub mod fanout;
+pub mod noop;
 pub mod otlp;
+pub mod process;
+pub mod prometheus;
+pub mod relabel;
+pub mod statsd;
+
+/// Upper bound on the shutdown delay caused by the export of pending telemetry
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Label value used when the actual value is not known
+pub const UNKNOWN_LABEL: &str = "unknown";
+/// Label value replacing the values over the cardinality limits
+pub const OTHER_LABEL: &str = "other";
+
+// attribute keys set by the node itself, which extra labels cannot override
+const ATTRIBUTE_KEYS: [&str; 8] = [
+    "peerID",
+    "multiaddress",
+    "ip",
+    "role",
+    "version",
+    "commit",
+    "origin",
+    "network",
+];
+
+/// Attributes attached to all the exported metrics
+#[derive(Debug, Clone)]
+pub struct MetricAttributes {
+    pub peer_id: String,
+    /// Public multiaddress, empty until it is known
+    pub multiaddress: String,
+    /// IP address of the public multiaddress, empty until it is known
+    pub ip: String,
+    pub role: String,
+    pub version: String,
+    /// Git commit the node was built from
+    pub commit: String,
+    pub origin: String,
+    pub network: String,
+    /// Operator defined labels, from the configuration
+    pub extra: BTreeMap<String, String>,
+}
+
+impl MetricAttributes {
+    /// Creates attributes with the given extra labels.
+    /// Extra labels with invalid names, or the ones overriding the node attributes, are skipped.
+    pub fn new(
+        peer_id: String,
+        role: String,
+        origin: String,
+        network: String,
+        extra_labels: &BTreeMap<String, String>,
+    ) -> Self {
+        let mut extra = BTreeMap::new();
+        for (key, value) in extra_labels {
+            if ATTRIBUTE_KEYS.contains(&key.as_str()) {
+                warn!(
+                    "Skipping extra metrics label {key}, overriding node attributes is not allowed"
+                );
+                continue;
+            }
+            if !is_valid_label_name(key) {
+                warn!("Skipping extra metrics label with invalid name: {key}");
+                continue;
+            }
+            extra.insert(key.clone(), value.clone());
+        }
+        MetricAttributes {
+            peer_id,
+            multiaddress: "".to_string(),
+            ip: "".to_string(),
+            role,
+            version: clap::crate_version!().to_string(),
+            commit: BUILD_INFO.git_commit.to_string(),
+            origin,
+            network,
+            extra,
+        }
+    }
 
+    /// Sets the public multiaddress, along with its IP address
+    pub fn set_multiaddress(&mut self, multiaddress: String) {
+        self.ip = multiaddress
+            .parse::<Multiaddr>()
+            .ok()
+            .and_then(|multiaddress| {
+                multiaddress.iter().find_map(|protocol| match protocol {
+                    Protocol::Ip4(ip) => Some(ip.to_string()),
+                    Protocol::Ip6(ip) => Some(ip.to_string()),
+                    _ => None,
+                })
+            })
+            .unwrap_or_default();
+        self.multiaddress = multiaddress;
+    }
+
+    /// Attributes which don't change during runtime, including the extra labels
+    pub fn constant(&self) -> Vec<(String, String)> {
+        let mut attributes = vec![
+            ("version".to_string(), self.version.clone()),
+            ("commit".to_string(), self.commit.clone()),
+            ("role".to_string(), self.role.clone()),
+            ("peerID".to_string(), self.peer_id.clone()),
+            ("origin".to_string(), self.origin.clone()),
+            ("network".to_string(), self.network.clone()),
+        ];
+        attributes.extend(self.extra.clone());
+        attributes
+    }
+
+    /// All the attributes, including the public multiaddress and IP
+    pub fn all(&self) -> Vec<(String, String)> {
+        let mut attributes = self.constant();
+        attributes.push(("multiaddress".to_string(), self.multiaddress.clone()));
+        attributes.push(("ip".to_string(), self.ip.clone()));
+        attributes
+    }
+}
+
+// Label names valid for both Prometheus and OpenTelemetry, with names starting with `__` reserved
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    starts_valid && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !name.starts_with("__")
+}
+
+#[derive(Clone)]
 pub enum MetricValue {
     KadRoutingPeerNum(usize),
-    HealthCheck(),
+    /// Number of routing table entries per non-empty k-bucket, keyed by bucket index
+    KadRoutingBucketPeerNum(BTreeMap<u32, usize>),
+    /// Number of currently established connections, by direction and transport
+    ConnectionNum(ConnectionCounts),
+    AutoNatStatus(NatStatus),
+    AutoNatThrottleUsage(AutoNatThrottleUsage),
+    /// Bytes transferred over all the connections since the previous metrics interval
+    IntervalTransferredBytes(TransferredBytes),
+    /// Number of peers blocked from connecting
+    BlockedPeerNum(usize),
+    /// Number of commands waiting to be handled by the event loop
+    CommandQueueDepth(usize),
+    /// Share of the command channel capacity taken by the queued commands, from 0 to 1
+    CommandQueueSaturation(f64),
+    ProcessUsage(process::ProcessUsage),
+    /// Number of connected peers per country code, with `unknown` for peers not found in the GeoIP database
+    CountryPeerNum(BTreeMap<String, usize>),
+    /// Number of distinct peers and remote IPs connected within each of the rolling windows
+    DistinctSeenNum(BTreeMap<SeenWindow, DistinctCounts>),
+    /// Recorded on every metrics interval, with the time elapsed since the node was started
+    HealthCheck(Duration),
+}
+
+/// Number of bytes transferred over the connections, in both directions.
+/// Bytes are counted by the multiplexed streams, so the protocols running on top aren't distinguished.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferredBytes {
+    pub received: u64,
+    pub sent: u64,
+}
+
+impl TransferredBytes {
+    /// Returns bytes transferred since the given earlier totals
+    pub fn since(&self, earlier: &TransferredBytes) -> TransferredBytes {
+        TransferredBytes {
+            received: self.received.saturating_sub(earlier.received),
+            sent: self.sent.saturating_sub(earlier.sent),
+        }
+    }
+
+    pub fn by_direction(&self) -> [(TransferDirection, u64); 2] {
+        [
+            (TransferDirection::Received, self.received),
+            (TransferDirection::Sent, self.sent),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransferDirection {
+    Received,
+    Sent,
+}
+
+impl TransferDirection {
+    pub const ALL: [TransferDirection; 2] = [TransferDirection::Received, TransferDirection::Sent];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferDirection::Received => "received",
+            TransferDirection::Sent => "sent",
+        }
+    }
+}
+
+/// NAT status determined by AutoNAT, exported as 0 for unknown, 1 for private and 2 for public
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatStatus {
+    Unknown,
+    Private,
+    Public,
+}
+
+impl NatStatus {
+    pub fn value(&self) -> u64 {
+        match self {
+            NatStatus::Unknown => 0,
+            NatStatus::Private => 1,
+            NatStatus::Public => 2,
+        }
+    }
+}
+
+/// Outcome of an AutoNAT dial-back probe, either served to a peer or requested by us
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AutoNatProbeOutcome {
+    Ok,
+    /// Dial-back was refused by the server
+    Refused,
+    /// Dial-back was refused by the server due to the clients throttling
+    Throttled,
+    Failed,
+    /// No AutoNAT server was available to probe
+    NoServer,
+    /// No addresses were available to be probed
+    NoAddresses,
+}
+
+impl AutoNatProbeOutcome {
+    pub const INBOUND: [AutoNatProbeOutcome; 4] = [
+        AutoNatProbeOutcome::Ok,
+        AutoNatProbeOutcome::Refused,
+        AutoNatProbeOutcome::Throttled,
+        AutoNatProbeOutcome::Failed,
+    ];
+    pub const OUTBOUND: [AutoNatProbeOutcome; 5] = [
+        AutoNatProbeOutcome::Ok,
+        AutoNatProbeOutcome::Refused,
+        AutoNatProbeOutcome::Failed,
+        AutoNatProbeOutcome::NoServer,
+        AutoNatProbeOutcome::NoAddresses,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutoNatProbeOutcome::Ok => "ok",
+            AutoNatProbeOutcome::Refused => "refused",
+            AutoNatProbeOutcome::Throttled => "throttled",
+            AutoNatProbeOutcome::Failed => "failed",
+            AutoNatProbeOutcome::NoServer => "no_server",
+            AutoNatProbeOutcome::NoAddresses => "no_addresses",
+        }
+    }
+}
+
+/// Limit of the AutoNAT server clients throttling, over which dial-back requests are refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AutoNatThrottleLimit {
+    Global,
+    Peer,
+}
+
+impl AutoNatThrottleLimit {
+    pub const ALL: [AutoNatThrottleLimit; 2] =
+        [AutoNatThrottleLimit::Global, AutoNatThrottleLimit::Peer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutoNatThrottleLimit::Global => "global",
+            AutoNatThrottleLimit::Peer => "peer",
+        }
+    }
+}
+
+/// Peak number of dial-back requests within the throttling period, since the previous metrics interval,
+/// as a fraction of the configured limits. Per-peer usage is the one of the busiest peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AutoNatThrottleUsage {
+    pub global: f64,
+    pub peer: f64,
+}
+
+impl AutoNatThrottleUsage {
+    pub fn by_limit(&self) -> [(AutoNatThrottleLimit, f64); 2] {
+        [
+            (AutoNatThrottleLimit::Global, self.global),
+            (AutoNatThrottleLimit::Peer, self.peer),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionDirection {
+    Inbound,
+    Outbound,
+}
+
+impl ConnectionDirection {
+    pub const ALL: [ConnectionDirection; 2] =
+        [ConnectionDirection::Inbound, ConnectionDirection::Outbound];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionDirection::Inbound => "inbound",
+            ConnectionDirection::Outbound => "outbound",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionTransport {
+    Tcp,
+    Quic,
+    Websocket,
+    Other,
+}
+
+impl ConnectionTransport {
+    pub const ALL: [ConnectionTransport; 4] = [
+        ConnectionTransport::Tcp,
+        ConnectionTransport::Quic,
+        ConnectionTransport::Websocket,
+        ConnectionTransport::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionTransport::Tcp => "tcp",
+            ConnectionTransport::Quic => "quic",
+            ConnectionTransport::Websocket => "websocket",
+            ConnectionTransport::Other => "other",
+        }
+    }
+}
+
+pub type ConnectionCounts = HashMap<(ConnectionDirection, ConnectionTransport), usize>;
+
+/// Rolling window over which the distinct peers and remote IPs are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SeenWindow {
+    FiveMinutes,
+    Hour,
+    Day,
+}
+
+impl SeenWindow {
+    pub const ALL: [SeenWindow; 3] = [SeenWindow::FiveMinutes, SeenWindow::Hour, SeenWindow::Day];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeenWindow::FiveMinutes => "5m",
+            SeenWindow::Hour => "1h",
+            SeenWindow::Day => "24h",
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            SeenWindow::FiveMinutes => Duration::from_secs(5 * 60),
+            SeenWindow::Hour => Duration::from_secs(60 * 60),
+            SeenWindow::Day => Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// Number of distinct peer IDs and remote IPs seen within a [SeenWindow]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DistinctCounts {
+    pub peers: usize,
+    pub ips: usize,
+}
+
+/// Cause of a closed connection. Connection handler errors are not reported
+/// as a close cause by libp2p anymore, so there is no dedicated variant for those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionCloseCause {
+    /// Connection was closed gracefully, by either side
+    Closed,
+    Io,
+    KeepAliveTimeout,
+}
+
+impl ConnectionCloseCause {
+    pub const ALL: [ConnectionCloseCause; 3] = [
+        ConnectionCloseCause::Closed,
+        ConnectionCloseCause::Io,
+        ConnectionCloseCause::KeepAliveTimeout,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionCloseCause::Closed => "closed",
+            ConnectionCloseCause::Io => "io",
+            ConnectionCloseCause::KeepAliveTimeout => "keep_alive_timeout",
+        }
+    }
+}
+
+/// Reason of a peer removal from the routing table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerEvictionReason {
+    /// Peer runs a release version which is no longer supported
+    UnsupportedVersion,
+    /// Peer doesn't support the Kademlia protocol of the network
+    UnsupportedProtocol,
+    Blocked,
+    /// Unreachable peer replaced by Kademlia with a new one, since its bucket was full
+    Unreachable,
+    /// Peer pruned by the maintenance, since it wasn't connected for too long
+    Stale,
+}
+
+impl PeerEvictionReason {
+    pub const ALL: [PeerEvictionReason; 5] = [
+        PeerEvictionReason::UnsupportedVersion,
+        PeerEvictionReason::UnsupportedProtocol,
+        PeerEvictionReason::Blocked,
+        PeerEvictionReason::Unreachable,
+        PeerEvictionReason::Stale,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PeerEvictionReason::UnsupportedVersion => "unsupported_version",
+            PeerEvictionReason::UnsupportedProtocol => "unsupported_protocol",
+            PeerEvictionReason::Blocked => "blocked",
+            PeerEvictionReason::Unreachable => "unreachable",
+            PeerEvictionReason::Stale => "stale",
+        }
+    }
+}
+
+/// Reason of a record or provider record not being stored, when put by another peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KadStoreRejection {
+    MaxRecords,
+    MaxProvidedKeys,
+    ValueTooLarge,
+}
+
+impl KadStoreRejection {
+    pub const ALL: [KadStoreRejection; 3] = [
+        KadStoreRejection::MaxRecords,
+        KadStoreRejection::MaxProvidedKeys,
+        KadStoreRejection::ValueTooLarge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KadStoreRejection::MaxRecords => "max_records",
+            KadStoreRejection::MaxProvidedKeys => "max_provided_keys",
+            KadStoreRejection::ValueTooLarge => "value_too_large",
+        }
+    }
+}
+
+/// Type of an outbound Kademlia query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KadQueryType {
+    Bootstrap,
+    GetClosestPeers,
+    /// Record and provider queries, which aren't started by the bootstrap node itself
+    Other,
+}
+
+impl KadQueryType {
+    pub const ALL: [KadQueryType; 3] = [
+        KadQueryType::Bootstrap,
+        KadQueryType::GetClosestPeers,
+        KadQueryType::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KadQueryType::Bootstrap => "bootstrap",
+            KadQueryType::GetClosestPeers => "get_closest_peers",
+            KadQueryType::Other => "other",
+        }
+    }
+
+    /// Possible outcomes, since bootstrap and closest peers lookups fail only on timeout
+    pub fn outcomes(&self) -> [KadQueryOutcome; 2] {
+        match self {
+            KadQueryType::Bootstrap | KadQueryType::GetClosestPeers => {
+                [KadQueryOutcome::Ok, KadQueryOutcome::Timeout]
+            }
+            KadQueryType::Other => [KadQueryOutcome::Ok, KadQueryOutcome::Error],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KadQueryOutcome {
+    Ok,
+    Timeout,
+    Error,
+}
+
+impl KadQueryOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KadQueryOutcome::Ok => "ok",
+            KadQueryOutcome::Timeout => "timeout",
+            KadQueryOutcome::Error => "error",
+        }
+    }
+}
+
+/// Kademlia bootstrap, either the initial one on startup or one of the periodic ones after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootstrapKind {
+    Startup,
+    Periodic,
+}
+
+impl BootstrapKind {
+    pub const ALL: [BootstrapKind; 2] = [BootstrapKind::Startup, BootstrapKind::Periodic];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootstrapKind::Startup => "startup",
+            BootstrapKind::Periodic => "periodic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdentifyEventKind {
+    Received,
+    Sent,
+    Pushed,
+}
+
+impl IdentifyEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdentifyEventKind::Received => "received",
+            IdentifyEventKind::Sent => "sent",
+            IdentifyEventKind::Pushed => "pushed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MetricCounter {
+    Starts,
+    /// Counted on every metrics interval, so missing heartbeats can be alerted on
+    Heartbeat,
+    /// Restarts of the network event loop, after it panicked or stopped
+    EventLoopRestart,
+    IncomingConnection,
+    IncomingConnectionError,
+    OutgoingConnectionError,
+    /// Identify events, labelled by the (sanitized) agent version of the remote peer
+    Identify(IdentifyEventKind, String),
+    /// Listen addresses of the known peers changed, as reported over identify, e.g. after their NAT rebinding
+    IdentifyAddressChange,
+    BootstrapAttempt(BootstrapKind),
+    /// Bootstraps finished with all the buckets refreshed
+    BootstrapSuccess(BootstrapKind),
+    BootstrapError(BootstrapKind),
+    /// Random walks started, i.e. the closest peers lookups of random keys
+    RandomWalk,
+    ConnectionEstablished(ConnectionDirection),
+    ConnectionClosed(ConnectionDirection, ConnectionCloseCause),
+    /// Dial-back probes served to other peers
+    AutoNatInboundProbe(AutoNatProbeOutcome),
+    /// Dial-back probes of our own addresses
+    AutoNatOutboundProbe(AutoNatProbeOutcome),
+    /// Dial-back requests refused due to the clients throttling, by the limit reached
+    AutoNatThrottled(AutoNatThrottleLimit),
+    /// Bytes transferred over all the connections
+    TransferredBytes(TransferDirection),
+    /// Peers blocked through the admin interfaces
+    PeerBlocked,
+    /// Non-critical client commands rejected, since the command channel was full
+    OverloadedCommand,
+    /// Peer exchange requests served, with the connected routing table peers handed out
+    PeerExchangeRequest,
+    /// Bootstrap info requests served
+    BootstrapInfoRequest,
+    PeerEvicted(PeerEvictionReason),
+    /// Connections of the peers not identified as Avail peers in time, closed by the connection policy
+    ConnectionExpired,
+    /// Connections of blocked peers denied, either dialed by them or by us
+    BlockedConnectionDenied(ConnectionDirection),
+    /// Results of outbound Kademlia queries, counted on each query step
+    KadQueryResult(KadQueryType, KadQueryOutcome),
+    /// Inserts into the Kademlia store rejected due to the configured store limits
+    KadStoreRejected(KadStoreRejection),
+    /// Histogram samples dropped while the metrics backends lag behind the event loop
+    HistogramSampleDropped,
+}
+
+impl MetricCounter {
+    /// Returns all the counters, including every label combination of the labelled ones.
+    /// Counters labelled with values known only at runtime are included with `unknown` label value.
+    pub fn all() -> Vec<MetricCounter> {
+        let mut counters = vec![
+            MetricCounter::Starts,
+            MetricCounter::Heartbeat,
+            MetricCounter::EventLoopRestart,
+            MetricCounter::IncomingConnection,
+            MetricCounter::IncomingConnectionError,
+            MetricCounter::OutgoingConnectionError,
+            MetricCounter::PeerBlocked,
+            MetricCounter::HistogramSampleDropped,
+            MetricCounter::OverloadedCommand,
+            MetricCounter::IdentifyAddressChange,
+            MetricCounter::PeerExchangeRequest,
+            MetricCounter::BootstrapInfoRequest,
+            MetricCounter::RandomWalk,
+            MetricCounter::ConnectionExpired,
+        ];
+        for kind in BootstrapKind::ALL {
+            counters.push(MetricCounter::BootstrapAttempt(kind));
+            counters.push(MetricCounter::BootstrapSuccess(kind));
+            counters.push(MetricCounter::BootstrapError(kind));
+        }
+        for direction in ConnectionDirection::ALL {
+            counters.push(MetricCounter::ConnectionEstablished(direction));
+            counters.push(MetricCounter::BlockedConnectionDenied(direction));
+            for cause in ConnectionCloseCause::ALL {
+                counters.push(MetricCounter::ConnectionClosed(direction, cause));
+            }
+        }
+        for kind in [
+            IdentifyEventKind::Received,
+            IdentifyEventKind::Sent,
+            IdentifyEventKind::Pushed,
+        ] {
+            counters.push(MetricCounter::Identify(kind, UNKNOWN_LABEL.to_string()));
+        }
+        for outcome in AutoNatProbeOutcome::INBOUND {
+            counters.push(MetricCounter::AutoNatInboundProbe(outcome));
+        }
+        for outcome in AutoNatProbeOutcome::OUTBOUND {
+            counters.push(MetricCounter::AutoNatOutboundProbe(outcome));
+        }
+        for limit in AutoNatThrottleLimit::ALL {
+            counters.push(MetricCounter::AutoNatThrottled(limit));
+        }
+        for direction in TransferDirection::ALL {
+            counters.push(MetricCounter::TransferredBytes(direction));
+        }
+        for reason in PeerEvictionReason::ALL {
+            counters.push(MetricCounter::PeerEvicted(reason));
+        }
+        for query in KadQueryType::ALL {
+            for outcome in query.outcomes() {
+                counters.push(MetricCounter::KadQueryResult(query, outcome));
+            }
+        }
+        for reason in KadStoreRejection::ALL {
+            counters.push(MetricCounter::KadStoreRejected(reason));
+        }
+        counters
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetricCounter::Starts => "starts",
+            MetricCounter::Heartbeat => "heartbeats",
+            MetricCounter::EventLoopRestart => "event_loop_restarts",
+            MetricCounter::IncomingConnection => "incoming_connections",
+            MetricCounter::IncomingConnectionError => "incoming_connection_errors",
+            MetricCounter::OutgoingConnectionError => "outgoing_connection_errors",
+            MetricCounter::Identify(..) => "identify_events",
+            MetricCounter::BootstrapAttempt(_) => "bootstrap_attempts",
+            MetricCounter::BootstrapSuccess(_) => "bootstrap_successes",
+            MetricCounter::BootstrapError(_) => "bootstrap_errors",
+            MetricCounter::RandomWalk => "random_walks",
+            MetricCounter::ConnectionEstablished(_) => "connections_established",
+            MetricCounter::ConnectionClosed(..) => "connections_closed",
+            MetricCounter::AutoNatInboundProbe(_) => "autonat_inbound_probes",
+            MetricCounter::AutoNatOutboundProbe(_) => "autonat_outbound_probes",
+            MetricCounter::AutoNatThrottled(_) => "autonat_throttled_requests",
+            MetricCounter::TransferredBytes(_) => "transferred_bytes",
+            MetricCounter::PeerBlocked => "peers_blocked",
+            MetricCounter::OverloadedCommand => "overloaded_commands",
+            MetricCounter::IdentifyAddressChange => "identify_address_changes",
+            MetricCounter::PeerExchangeRequest => "peer_exchange_requests",
+            MetricCounter::BootstrapInfoRequest => "bootstrap_info_requests",
+            MetricCounter::PeerEvicted(_) => "peer_evictions",
+            MetricCounter::ConnectionExpired => "connections_expired",
+            MetricCounter::BlockedConnectionDenied(_) => "blocked_connections_denied",
+            MetricCounter::KadQueryResult(..) => "kad_query_results",
+            MetricCounter::KadStoreRejected(_) => "kad_store_rejected_inserts",
+            MetricCounter::HistogramSampleDropped => "dropped_histogram_samples",
+        }
+    }
+
+    /// Labels distinguishing the counters sharing the same name
+    pub fn labels(&self) -> Vec<(&'static str, String)> {
+        match self {
+            MetricCounter::Identify(kind, agent_version) => vec![
+                ("event", kind.as_str().to_string()),
+                ("agent_version", agent_version.clone()),
+            ],
+            MetricCounter::BootstrapAttempt(kind)
+            | MetricCounter::BootstrapSuccess(kind)
+            | MetricCounter::BootstrapError(kind) => vec![("kind", kind.as_str().to_string())],
+            MetricCounter::ConnectionEstablished(direction)
+            | MetricCounter::BlockedConnectionDenied(direction) => {
+                vec![("direction", direction.as_str().to_string())]
+            }
+            MetricCounter::ConnectionClosed(direction, cause) => vec![
+                ("direction", direction.as_str().to_string()),
+                ("cause", cause.as_str().to_string()),
+            ],
+            MetricCounter::AutoNatInboundProbe(outcome)
+            | MetricCounter::AutoNatOutboundProbe(outcome) => {
+                vec![("outcome", outcome.as_str().to_string())]
+            }
+            MetricCounter::AutoNatThrottled(limit) => vec![("limit", limit.as_str().to_string())],
+            MetricCounter::TransferredBytes(direction) => {
+                vec![("direction", direction.as_str().to_string())]
+            }
+            MetricCounter::PeerEvicted(reason) => vec![("reason", reason.as_str().to_string())],
+            MetricCounter::KadStoreRejected(reason) => {
+                vec![("reason", reason.as_str().to_string())]
+            }
+            MetricCounter::KadQueryResult(query, outcome) => vec![
+                ("query", query.as_str().to_string()),
+                ("outcome", outcome.as_str().to_string()),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetricHistogram {
+    /// Duration from the start of a bootstrap until all the buckets are refreshed, or until it fails
+    KadBootstrapDuration(BootstrapKind),
+    KadGetClosestPeersDuration,
+    PingRtt,
+    /// Round-trip of a no-op command, showing how long the event loop takes to handle commands
+    EventLoopLatency,
+}
+
+impl MetricHistogram {
+    /// Returns all the histograms, including every label combination of the labelled ones
+    pub fn all() -> Vec<MetricHistogram> {
+        let mut histograms: Vec<MetricHistogram> = BootstrapKind::ALL
+            .into_iter()
+            .map(MetricHistogram::KadBootstrapDuration)
+            .collect();
+        histograms.push(MetricHistogram::KadGetClosestPeersDuration);
+        histograms.push(MetricHistogram::PingRtt);
+        histograms.push(MetricHistogram::EventLoopLatency);
+        histograms
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetricHistogram::KadBootstrapDuration(_) => "kad_bootstrap_duration_ms",
+            MetricHistogram::KadGetClosestPeersDuration => "kad_get_closest_peers_duration_ms",
+            MetricHistogram::PingRtt => "ping_rtt_ms",
+            MetricHistogram::EventLoopLatency => "event_loop_latency_ms",
+        }
+    }
+
+    /// Labels distinguishing the histograms sharing the same name
+    pub fn labels(&self) -> Vec<(&'static str, String)> {
+        match self {
+            MetricHistogram::KadBootstrapDuration(kind) => {
+                vec![("kind", kind.as_str().to_string())]
+            }
+            _ => vec![],
+        }
+    }
 }
 
 #[async_trait]
-pub trait Metrics {
+pub trait Metrics: Send + Sync {
+    async fn count(&self, counter: MetricCounter) {
+        self.count_n(counter, 1).await;
+    }
+    async fn count_n(&self, counter: MetricCounter, value: u64);
     async fn record(&self, value: MetricValue) -> Result<()>;
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64);
     async fn set_multiaddress(&self, multiaddrs: String);
+    /// Records served HTTP request, labelled by route name and response status code
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration);
+    /// Flushes pending metrics and releases the backend, called once on graceful shutdown
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::{MetricCounter, MetricHistogram, MetricValue};
+
+/// Metrics implementation which discards everything, used when metrics are disabled
+pub struct Metrics;
+
+#[async_trait]
+impl super::Metrics for Metrics {
+    async fn count_n(&self, _counter: MetricCounter, _value: u64) {}
+
+    async fn record(&self, _value: MetricValue) -> Result<()> {
+        Ok(())
+    }
+
+    async fn record_histogram(&self, _histogram: MetricHistogram, _value: f64) {}
+
+    async fn set_multiaddress(&self, _multiaddr: String) {}
+
+    async fn record_http_request(&self, _route: &'static str, _status: u16, _latency: Duration) {}
+}
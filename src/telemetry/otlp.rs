@@ -1,98 +1,535 @@
-use anyhow::{Error, Ok, Result};
+use anyhow::{Context, Error, Result};
 use async_trait::async_trait;
-use opentelemetry_api::{global, metrics::Meter, KeyValue};
-use opentelemetry_otlp::{ExportConfig, Protocol, WithExportConfig};
-use std::time::Duration;
-use tokio::sync::RwLock;
+use opentelemetry_api::{
+    global,
+    metrics::{Counter, Histogram, Meter, ObservableCounter, ObservableGauge},
+    KeyValue,
+};
+use opentelemetry_otlp::{
+    ExportConfig, HttpExporterBuilder, LogExporterBuilder, MetricsExporterBuilder, Protocol,
+    TonicExporterBuilder, WithExportConfig,
+};
+use opentelemetry_sdk::{
+    logs::{self, LoggerProvider},
+    metrics::{
+        reader::{DefaultAggregationSelector, DefaultTemporalitySelector},
+        MeterProvider, PeriodicReader,
+    },
+    Resource,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use super::{
+    process::ProcessUsage, AutoNatThrottleUsage, ConnectionCounts, DistinctCounts,
+    MetricAttributes, MetricCounter, MetricHistogram, SeenWindow, TransferredBytes,
+};
+use crate::types::OtlpProtocol;
+
+mod buffer;
+
+const HTTP_METRICS_PATH: &str = "/v1/metrics";
+const HTTP_LOGS_PATH: &str = "/v1/logs";
 
 pub struct Metrics {
-    meter: Meter,
-    peer_id: String,
-    multiaddress: RwLock<String>,
-    role: String,
-    origin: String,
-    network: String,
+    /// Counters by name, labelled counters share the same instrument
+    counters: HashMap<&'static str, Counter<u64>>,
+    /// Histograms by name, same as the counters
+    histograms: HashMap<&'static str, Histogram<f64>>,
+    http_requests: Counter<u64>,
+    http_request_errors: Counter<u64>,
+    http_request_duration: Histogram<f64>,
+    state: Arc<State>,
+    provider: MeterProvider,
 }
 
-impl Metrics {
-    async fn attributes(&self) -> [KeyValue; 6] {
-        [
-            KeyValue::new("version", clap::crate_version!()),
-            KeyValue::new("role", self.role.clone()),
-            KeyValue::new("peerID", self.peer_id.clone()),
-            KeyValue::new("multiaddress", self.multiaddress.read().await.clone()),
-            KeyValue::new("origin", self.origin.clone()),
-            KeyValue::new("network", self.network.clone()),
-        ]
+// State shared with the gauges callback, which is invoked by the meter on each export
+struct State {
+    attributes: RwLock<MetricAttributes>,
+    kad_routing_peer_num: AtomicU64,
+    kad_routing_bucket_peer_num: RwLock<BTreeMap<u32, usize>>,
+    connection_num: RwLock<ConnectionCounts>,
+    nat_status: AtomicU64,
+    autonat_throttle_usage: RwLock<AutoNatThrottleUsage>,
+    interval_transferred_bytes: RwLock<TransferredBytes>,
+    blocked_peer_num: AtomicU64,
+    command_queue_depth: AtomicU64,
+    // bits of the saturation ratio
+    command_queue_saturation: AtomicU64,
+    process_usage: RwLock<Option<ProcessUsage>>,
+    country_peer_num: RwLock<BTreeMap<String, usize>>,
+    distinct_seen_num: RwLock<BTreeMap<SeenWindow, DistinctCounts>>,
+    up: AtomicU64,
+    uptime_seconds: AtomicU64,
+    /// Shared with the exporter, which drops the buffered data points when the collector is unreachable for too long
+    dropped_data_points: Arc<AtomicU64>,
+}
+
+impl State {
+    fn attributes(&self) -> Vec<KeyValue> {
+        let Ok(attributes) = self.attributes.read() else {
+            return vec![];
+        };
+        attributes
+            .all()
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect()
     }
+}
 
-    async fn record_u64(&self, name: &'static str, value: u64) -> Result<()> {
-        let instrument = self.meter.u64_observable_gauge(name).try_init()?;
-        let attributes = self.attributes().await;
-        self.meter
-            .register_callback(&[instrument.as_any()], move |observer| {
-                observer.observe_u64(&instrument, value, &attributes)
-            })?;
-        Ok(())
+impl Metrics {
+    fn attributes(&self) -> Vec<KeyValue> {
+        self.state.attributes()
     }
 
-    async fn set_multiaddress(&self, multiaddr: String) {
-        let mut m = self.multiaddress.write().await;
-        *m = multiaddr;
+    fn set_multiaddress(&self, multiaddr: String) {
+        if let Ok(mut attributes) = self.state.attributes.write() {
+            attributes.set_multiaddress(multiaddr);
+        }
     }
 }
 
 #[async_trait]
 impl super::Metrics for Metrics {
+    async fn count_n(&self, counter: MetricCounter, value: u64) {
+        if let Some(instrument) = self.counters.get(counter.name()) {
+            let mut attributes = self.attributes();
+            for (key, value) in counter.labels() {
+                attributes.push(KeyValue::new(key, value));
+            }
+            instrument.add(value, &attributes);
+        }
+    }
+
     async fn record(&self, value: super::MetricValue) -> Result<()> {
         match value {
             super::MetricValue::KadRoutingPeerNum(num) => {
-                self.record_u64("kad_routing_peer_num", num as u64).await?;
+                self.state
+                    .kad_routing_peer_num
+                    .store(num as u64, Ordering::Relaxed);
+            }
+            super::MetricValue::KadRoutingBucketPeerNum(bucket_peers) => {
+                if let Ok(mut kad_routing_bucket_peer_num) =
+                    self.state.kad_routing_bucket_peer_num.write()
+                {
+                    *kad_routing_bucket_peer_num = bucket_peers;
+                }
             }
-            super::MetricValue::HealthCheck() => {
-                self.record_u64("up", 1).await?;
+            super::MetricValue::ConnectionNum(counts) => {
+                if let Ok(mut connection_num) = self.state.connection_num.write() {
+                    *connection_num = counts;
+                }
+            }
+            super::MetricValue::AutoNatStatus(status) => {
+                self.state
+                    .nat_status
+                    .store(status.value(), Ordering::Relaxed);
+            }
+            super::MetricValue::AutoNatThrottleUsage(usage) => {
+                if let Ok(mut autonat_throttle_usage) = self.state.autonat_throttle_usage.write() {
+                    *autonat_throttle_usage = usage;
+                }
+            }
+            super::MetricValue::IntervalTransferredBytes(bytes) => {
+                if let Ok(mut interval_transferred_bytes) =
+                    self.state.interval_transferred_bytes.write()
+                {
+                    *interval_transferred_bytes = bytes;
+                }
+            }
+            super::MetricValue::BlockedPeerNum(num) => {
+                self.state
+                    .blocked_peer_num
+                    .store(num as u64, Ordering::Relaxed);
+            }
+            super::MetricValue::CommandQueueDepth(depth) => {
+                self.state
+                    .command_queue_depth
+                    .store(depth as u64, Ordering::Relaxed);
+            }
+            super::MetricValue::CommandQueueSaturation(saturation) => {
+                self.state
+                    .command_queue_saturation
+                    .store(saturation.to_bits(), Ordering::Relaxed);
+            }
+            super::MetricValue::ProcessUsage(usage) => {
+                if let Ok(mut process_usage) = self.state.process_usage.write() {
+                    *process_usage = Some(usage);
+                }
+            }
+            super::MetricValue::CountryPeerNum(counts) => {
+                if let Ok(mut country_peer_num) = self.state.country_peer_num.write() {
+                    *country_peer_num = counts;
+                }
+            }
+            super::MetricValue::DistinctSeenNum(counts) => {
+                if let Ok(mut distinct_seen_num) = self.state.distinct_seen_num.write() {
+                    *distinct_seen_num = counts;
+                }
+            }
+            super::MetricValue::HealthCheck(uptime) => {
+                self.state.up.store(1, Ordering::Relaxed);
+                self.state
+                    .uptime_seconds
+                    .store(uptime.as_secs(), Ordering::Relaxed);
             }
         }
         Ok(())
     }
 
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64) {
+        if let Some(instrument) = self.histograms.get(histogram.name()) {
+            let mut attributes = self.attributes();
+            for (key, value) in histogram.labels() {
+                attributes.push(KeyValue::new(key, value));
+            }
+            instrument.record(value, &attributes);
+        }
+    }
+
     async fn set_multiaddress(&self, multiaddr: String) {
-        self.set_multiaddress(multiaddr).await;
+        self.set_multiaddress(multiaddr);
     }
+
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration) {
+        let mut attributes = self.attributes();
+        attributes.push(KeyValue::new("route", route));
+        attributes.push(KeyValue::new("status", i64::from(status)));
+
+        self.http_requests.add(1, &attributes);
+        if status >= 400 {
+            self.http_request_errors.add(1, &attributes);
+        }
+        self.http_request_duration
+            .record(latency.as_secs_f64() * 1000.0, &attributes);
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        // shutdown exports the data points collected since the last export,
+        // blocking until the periodic reader task is done with it
+        let provider = self.provider.clone();
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .context("Metrics flush task failed")?
+            .context("Failed to flush OpenTelemetry metrics")
+    }
+}
+
+// Registers gauges with a single callback, observing the latest values stored in the state,
+// along with the counter of the data points dropped by the exporter
+fn register_gauges(meter: &Meter, state: Arc<State>) -> Result<()> {
+    let kad_routing_peer_num: ObservableGauge<u64> = meter
+        .u64_observable_gauge("kad_routing_peer_num")
+        .try_init()?;
+    let kad_routing_bucket_peer_num: ObservableGauge<u64> = meter
+        .u64_observable_gauge("kad_routing_bucket_peer_num")
+        .try_init()?;
+    let connection_num: ObservableGauge<u64> =
+        meter.u64_observable_gauge("connection_num").try_init()?;
+    let nat_status: ObservableGauge<u64> = meter.u64_observable_gauge("nat_status").try_init()?;
+    let autonat_throttle_usage: ObservableGauge<f64> = meter
+        .f64_observable_gauge("autonat_throttle_usage")
+        .try_init()?;
+    let interval_transferred_bytes: ObservableGauge<u64> = meter
+        .u64_observable_gauge("interval_transferred_bytes")
+        .try_init()?;
+    let blocked_peer_num: ObservableGauge<u64> =
+        meter.u64_observable_gauge("blocked_peer_num").try_init()?;
+    let command_queue_depth: ObservableGauge<u64> = meter
+        .u64_observable_gauge("command_queue_depth")
+        .try_init()?;
+    let command_queue_saturation: ObservableGauge<f64> = meter
+        .f64_observable_gauge("command_queue_saturation")
+        .try_init()?;
+    let process_cpu_usage: ObservableGauge<f64> =
+        meter.f64_observable_gauge("process_cpu_usage").try_init()?;
+    let process_resident_memory: ObservableGauge<u64> = meter
+        .u64_observable_gauge("process_resident_memory_bytes")
+        .try_init()?;
+    let process_open_fds: ObservableGauge<u64> =
+        meter.u64_observable_gauge("process_open_fds").try_init()?;
+    let tokio_workers: ObservableGauge<u64> =
+        meter.u64_observable_gauge("tokio_workers").try_init()?;
+    let tokio_alive_tasks: ObservableGauge<u64> =
+        meter.u64_observable_gauge("tokio_alive_tasks").try_init()?;
+    let country_peer_num: ObservableGauge<u64> =
+        meter.u64_observable_gauge("country_peer_num").try_init()?;
+    let distinct_peer_num: ObservableGauge<u64> =
+        meter.u64_observable_gauge("distinct_peer_num").try_init()?;
+    let distinct_ip_num: ObservableGauge<u64> =
+        meter.u64_observable_gauge("distinct_ip_num").try_init()?;
+    let up: ObservableGauge<u64> = meter.u64_observable_gauge("up").try_init()?;
+    let uptime_seconds: ObservableGauge<u64> =
+        meter.u64_observable_gauge("uptime_seconds").try_init()?;
+    let dropped_data_points: ObservableCounter<u64> = meter
+        .u64_observable_counter("otlp_dropped_data_points")
+        .with_description("Number of data points dropped while the collector was unreachable")
+        .try_init()?;
+
+    meter.register_callback(
+        &[
+            kad_routing_peer_num.as_any(),
+            kad_routing_bucket_peer_num.as_any(),
+            connection_num.as_any(),
+            nat_status.as_any(),
+            autonat_throttle_usage.as_any(),
+            interval_transferred_bytes.as_any(),
+            blocked_peer_num.as_any(),
+            command_queue_depth.as_any(),
+            command_queue_saturation.as_any(),
+            process_cpu_usage.as_any(),
+            process_resident_memory.as_any(),
+            process_open_fds.as_any(),
+            tokio_workers.as_any(),
+            tokio_alive_tasks.as_any(),
+            country_peer_num.as_any(),
+            distinct_peer_num.as_any(),
+            distinct_ip_num.as_any(),
+            up.as_any(),
+            uptime_seconds.as_any(),
+            dropped_data_points.as_any(),
+        ],
+        move |observer| {
+            let attributes = state.attributes();
+            observer.observe_u64(
+                &kad_routing_peer_num,
+                state.kad_routing_peer_num.load(Ordering::Relaxed),
+                &attributes,
+            );
+            if let Ok(bucket_peers) = state.kad_routing_bucket_peer_num.read() {
+                for (bucket, peers) in bucket_peers.iter() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("bucket", i64::from(*bucket)));
+                    observer.observe_u64(&kad_routing_bucket_peer_num, *peers as u64, &attributes);
+                }
+            }
+            if let Ok(counts) = state.connection_num.read() {
+                for ((direction, transport), num) in counts.iter() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("direction", direction.as_str()));
+                    attributes.push(KeyValue::new("transport", transport.as_str()));
+                    observer.observe_u64(&connection_num, *num as u64, &attributes);
+                }
+            }
+            observer.observe_u64(
+                &nat_status,
+                state.nat_status.load(Ordering::Relaxed),
+                &attributes,
+            );
+            if let Ok(usage) = state.autonat_throttle_usage.read() {
+                for (limit, value) in usage.by_limit() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("limit", limit.as_str()));
+                    observer.observe_f64(&autonat_throttle_usage, value, &attributes);
+                }
+            }
+            if let Ok(bytes) = state.interval_transferred_bytes.read() {
+                for (direction, value) in bytes.by_direction() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("direction", direction.as_str()));
+                    observer.observe_u64(&interval_transferred_bytes, value, &attributes);
+                }
+            }
+            observer.observe_u64(
+                &blocked_peer_num,
+                state.blocked_peer_num.load(Ordering::Relaxed),
+                &attributes,
+            );
+            observer.observe_u64(
+                &command_queue_depth,
+                state.command_queue_depth.load(Ordering::Relaxed),
+                &attributes,
+            );
+            observer.observe_f64(
+                &command_queue_saturation,
+                f64::from_bits(state.command_queue_saturation.load(Ordering::Relaxed)),
+                &attributes,
+            );
+            if let Ok(usage) = state.process_usage.read() {
+                if let Some(usage) = usage.as_ref() {
+                    observer.observe_f64(&process_cpu_usage, usage.cpu_usage, &attributes);
+                    observer.observe_u64(
+                        &process_resident_memory,
+                        usage.resident_memory,
+                        &attributes,
+                    );
+                    if let Some(open_fds) = usage.open_fds {
+                        observer.observe_u64(&process_open_fds, open_fds, &attributes);
+                    }
+                    observer.observe_u64(&tokio_workers, usage.tokio_workers, &attributes);
+                    observer.observe_u64(&tokio_alive_tasks, usage.tokio_alive_tasks, &attributes);
+                }
+            }
+            if let Ok(counts) = state.country_peer_num.read() {
+                for (country, num) in counts.iter() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("country", country.clone()));
+                    observer.observe_u64(&country_peer_num, *num as u64, &attributes);
+                }
+            }
+            if let Ok(counts) = state.distinct_seen_num.read() {
+                for (window, counts) in counts.iter() {
+                    let mut attributes = attributes.to_vec();
+                    attributes.push(KeyValue::new("window", window.as_str()));
+                    observer.observe_u64(&distinct_peer_num, counts.peers as u64, &attributes);
+                    observer.observe_u64(&distinct_ip_num, counts.ips as u64, &attributes);
+                }
+            }
+            observer.observe_u64(&up, state.up.load(Ordering::Relaxed), &attributes);
+            observer.observe_u64(
+                &uptime_seconds,
+                state.uptime_seconds.load(Ordering::Relaxed),
+                &attributes,
+            );
+            observer.observe_u64(
+                &dropped_data_points,
+                state.dropped_data_points.load(Ordering::Relaxed),
+                &attributes,
+            );
+        },
+    )?;
+    Ok(())
 }
 
+// Unlike gRPC exporter, HTTP exporter sends requests to the endpoint as is, so signal path is added here
+fn http_signal_endpoint(endpoint: &str, signal_path: &str) -> String {
+    let mut endpoint = endpoint.trim_end_matches('/');
+    for path in [HTTP_METRICS_PATH, HTTP_LOGS_PATH] {
+        endpoint = endpoint.trim_end_matches(path);
+    }
+    format!("{endpoint}{signal_path}")
+}
+
+// Creates the exporter builder of the given protocol, which is then converted into the signal specific one
+fn exporter<B>(endpoint: &str, protocol: OtlpProtocol, signal_path: &str) -> B
+where
+    B: From<TonicExporterBuilder> + From<HttpExporterBuilder>,
+{
+    match protocol {
+        OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_export_config(ExportConfig {
+                endpoint: endpoint.to_string(),
+                timeout: Duration::from_secs(10),
+                protocol: Protocol::Grpc,
+            })
+            .into(),
+        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::new_exporter()
+            .http()
+            .with_export_config(ExportConfig {
+                endpoint: http_signal_endpoint(endpoint, signal_path),
+                timeout: Duration::from_secs(10),
+                protocol: Protocol::HttpBinary,
+            })
+            .into(),
+    }
+}
+
+/// Initializes the logs export, returning the provider with batching log processor.
+/// Provider has to be kept alive for as long as the logs are exported.
+pub fn initialize_logs(
+    endpoint: &str,
+    protocol: OtlpProtocol,
+    resource: Vec<KeyValue>,
+) -> Result<LoggerProvider> {
+    let exporter: LogExporterBuilder = exporter(endpoint, protocol, HTTP_LOGS_PATH);
+    let provider = LoggerProvider::builder()
+        .with_batch_exporter(
+            exporter.build_log_exporter()?,
+            opentelemetry_sdk::runtime::Tokio,
+        )
+        .with_config(logs::Config::default().with_resource(Resource::new(resource)))
+        .build();
+    Ok(provider)
+}
+
+/// Initializes the metrics export. Exports failed due to the collector being unreachable
+/// are buffered, keeping up to `export_buffer_size` of them until the collector is reachable again.
 pub fn initialize(
     endpoint: String,
-    peer_id: String,
-    role: String,
-    origin: String,
-    network: String,
+    protocol: OtlpProtocol,
+    export_buffer_size: usize,
+    attributes: MetricAttributes,
 ) -> Result<Metrics, Error> {
-    let export_config = ExportConfig {
-        endpoint,
-        timeout: Duration::from_secs(10),
-        protocol: Protocol::Grpc,
-    };
-    let provider = opentelemetry_otlp::new_pipeline()
-        .metrics(opentelemetry_sdk::runtime::Tokio)
-        .with_exporter(
-            opentelemetry_otlp::new_exporter()
-                .tonic()
-                .with_export_config(export_config),
-        )
-        .with_period(Duration::from_secs(10))
+    let exporter: MetricsExporterBuilder = exporter(&endpoint, protocol, HTTP_METRICS_PATH);
+    let exporter = exporter.build_metrics_exporter(
+        Box::new(DefaultTemporalitySelector::new()),
+        Box::new(DefaultAggregationSelector::new()),
+    )?;
+    let dropped_data_points = Arc::new(AtomicU64::new(0));
+    let exporter =
+        buffer::BufferedExporter::new(exporter, export_buffer_size, dropped_data_points.clone());
+    let reader = PeriodicReader::builder(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_interval(Duration::from_secs(10))
         .with_timeout(Duration::from_secs(15))
-        .build()?;
+        .build();
+    let provider = MeterProvider::builder().with_reader(reader).build();
 
-    global::set_meter_provider(provider);
+    global::set_meter_provider(provider.clone());
     let meter = global::meter("avail_light_bootstrap");
 
+    let http_requests = meter
+        .u64_counter("http_requests")
+        .with_description("Number of served HTTP requests")
+        .try_init()?;
+    let http_request_errors = meter
+        .u64_counter("http_request_errors")
+        .with_description("Number of HTTP requests served with an error status code")
+        .try_init()?;
+    let http_request_duration = meter
+        .f64_histogram("http_request_duration_ms")
+        .with_description("Duration of served HTTP requests in milliseconds")
+        .try_init()?;
+
+    let mut counters = HashMap::new();
+    for counter in MetricCounter::all() {
+        if !counters.contains_key(counter.name()) {
+            let instrument = meter.u64_counter(counter.name()).try_init()?;
+            counters.insert(counter.name(), instrument);
+        }
+    }
+    let mut histograms = HashMap::new();
+    for histogram in MetricHistogram::all() {
+        if !histograms.contains_key(histogram.name()) {
+            let instrument = meter.f64_histogram(histogram.name()).try_init()?;
+            histograms.insert(histogram.name(), instrument);
+        }
+    }
+
+    let state = Arc::new(State {
+        attributes: RwLock::new(attributes),
+        kad_routing_peer_num: AtomicU64::new(0),
+        kad_routing_bucket_peer_num: Default::default(),
+        connection_num: Default::default(),
+        nat_status: AtomicU64::new(0),
+        autonat_throttle_usage: Default::default(),
+        interval_transferred_bytes: Default::default(),
+        blocked_peer_num: AtomicU64::new(0),
+        command_queue_depth: AtomicU64::new(0),
+        command_queue_saturation: AtomicU64::new(0),
+        process_usage: Default::default(),
+        country_peer_num: Default::default(),
+        distinct_seen_num: Default::default(),
+        up: AtomicU64::new(0),
+        uptime_seconds: AtomicU64::new(0),
+        dropped_data_points,
+    });
+    register_gauges(&meter, state.clone())?;
+
     Ok(Metrics {
-        meter,
-        peer_id,
-        multiaddress: RwLock::new("".to_string()),
-        role,
-        origin,
-        network,
+        counters,
+        histograms,
+        http_requests,
+        http_request_errors,
+        http_request_duration,
+        state,
+        provider,
     })
 }
@@ -0,0 +1,183 @@
+//! Metrics exporter keeping the exports in memory while the collector is unreachable.
+
+use async_trait::async_trait;
+use opentelemetry_api::metrics::Result;
+use opentelemetry_otlp::MetricsExporter;
+use opentelemetry_sdk::{
+    metrics::{
+        data::{Gauge, Histogram, ResourceMetrics, Sum, Temporality},
+        exporter::PushMetricsExporter,
+        reader::{AggregationSelector, TemporalitySelector},
+        Aggregation, InstrumentKind,
+    },
+    Resource,
+};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{debug, info, warn};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+struct Buffer {
+    exports: VecDeque<ResourceMetrics>,
+    /// Set while the collector is unreachable, exports are only buffered until then
+    retry_at: Option<Instant>,
+    backoff: Duration,
+}
+
+/// Wraps the OTLP exporter, buffering up to `capacity` exports that failed to be sent.
+/// Buffered exports are retried in order, with exponential backoff in between the failed attempts.
+/// Oldest exports are dropped once the buffer is full, counting their data points as dropped.
+pub struct BufferedExporter {
+    inner: MetricsExporter,
+    capacity: usize,
+    buffer: Mutex<Buffer>,
+    dropped_data_points: Arc<AtomicU64>,
+}
+
+impl BufferedExporter {
+    pub fn new(
+        inner: MetricsExporter,
+        capacity: usize,
+        dropped_data_points: Arc<AtomicU64>,
+    ) -> Self {
+        BufferedExporter {
+            inner,
+            capacity,
+            buffer: Mutex::new(Buffer {
+                exports: VecDeque::new(),
+                retry_at: None,
+                backoff: INITIAL_BACKOFF,
+            }),
+            dropped_data_points,
+        }
+    }
+
+    fn drop_export(&self, export: &ResourceMetrics) -> u64 {
+        let data_points = data_points(export);
+        self.dropped_data_points
+            .fetch_add(data_points, Ordering::Relaxed);
+        data_points
+    }
+}
+
+fn aggregation_data_points<T: 'static>(data: &dyn Any) -> Option<usize> {
+    data.downcast_ref::<Gauge<T>>()
+        .map(|gauge| gauge.data_points.len())
+        .or_else(|| {
+            data.downcast_ref::<Sum<T>>()
+                .map(|sum| sum.data_points.len())
+        })
+        .or_else(|| {
+            data.downcast_ref::<Histogram<T>>()
+                .map(|histogram| histogram.data_points.len())
+        })
+}
+
+// Counts the data points of all the metrics in the export, as recorded by the instruments in use
+fn data_points(export: &ResourceMetrics) -> u64 {
+    export
+        .scope_metrics
+        .iter()
+        .flat_map(|scope| scope.metrics.iter())
+        .map(|metric| {
+            let data = metric.data.as_any();
+            aggregation_data_points::<u64>(data)
+                .or_else(|| aggregation_data_points::<i64>(data))
+                .or_else(|| aggregation_data_points::<f64>(data))
+                .unwrap_or_default() as u64
+        })
+        .sum()
+}
+
+impl TemporalitySelector for BufferedExporter {
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.inner.temporality(kind)
+    }
+}
+
+impl AggregationSelector for BufferedExporter {
+    fn aggregation(&self, kind: InstrumentKind) -> Aggregation {
+        self.inner.aggregation(kind)
+    }
+}
+
+#[async_trait]
+impl PushMetricsExporter for BufferedExporter {
+    async fn export(&self, metrics: &mut ResourceMetrics) -> Result<()> {
+        // reader collects into the same value on each export, so it is taken over by the buffer
+        let export = std::mem::replace(
+            metrics,
+            ResourceMetrics {
+                resource: Resource::empty(),
+                scope_metrics: vec![],
+            },
+        );
+
+        let mut buffer = self.buffer.lock().await;
+        buffer.exports.push_back(export);
+        while buffer.exports.len() > self.capacity {
+            if let Some(dropped) = buffer.exports.pop_front() {
+                let data_points = self.drop_export(&dropped);
+                debug!("Metrics export buffer is full, dropped {data_points} data points.");
+            }
+        }
+
+        if buffer
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Ok(());
+        }
+
+        let pending = buffer.exports.len();
+        // exports are removed only once sent, so they are kept if the export is cancelled on timeout
+        while let Some(export) = buffer.exports.front_mut() {
+            if let Err(err) = self.inner.export(export).await {
+                let backoff = buffer.backoff;
+                if buffer.retry_at.is_none() {
+                    warn!("Unable to export metrics, buffering them until the OpenTelemetry Collector is reachable: {err}");
+                } else {
+                    debug!("Unable to export buffered metrics, retrying in {backoff:?}: {err}");
+                }
+                buffer.retry_at = Some(Instant::now() + backoff);
+                buffer.backoff = (backoff * 2).min(MAX_BACKOFF);
+                return Ok(());
+            }
+            buffer.exports.pop_front();
+        }
+
+        if buffer.retry_at.take().is_some() {
+            info!("Metrics export resumed, {pending} buffered exports sent.");
+        }
+        buffer.backoff = INITIAL_BACKOFF;
+        Ok(())
+    }
+
+    async fn force_flush(&self) -> Result<()> {
+        self.inner.force_flush().await
+    }
+
+    fn shutdown(&self) -> Result<()> {
+        if let Ok(buffer) = self.buffer.try_lock() {
+            if !buffer.exports.is_empty() {
+                let data_points: u64 = buffer
+                    .exports
+                    .iter()
+                    .map(|export| self.drop_export(export))
+                    .sum();
+                warn!("OpenTelemetry Collector is unreachable, {data_points} buffered data points are dropped.");
+            }
+        }
+        self.inner.shutdown()
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Result};
+use sysinfo::{Pid, ProcessExt, ProcessRefreshKind, System, SystemExt};
+use tokio::runtime::Handle;
+
+/// Resource usage of the node process
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessUsage {
+    /// CPU usage since the previous measurement, in percent of a single core
+    pub cpu_usage: f64,
+    /// Resident set size in bytes
+    pub resident_memory: u64,
+    /// Not available on platforms other than Linux
+    pub open_fds: Option<u64>,
+    pub tokio_workers: u64,
+    pub tokio_alive_tasks: u64,
+}
+
+/// Measures the resource usage of the current process
+pub struct ProcessMonitor {
+    system: System,
+    pid: Pid,
+}
+
+impl ProcessMonitor {
+    pub fn new() -> Result<Self> {
+        let pid = sysinfo::get_current_pid().map_err(|err| anyhow!(err))?;
+        let mut monitor = ProcessMonitor {
+            system: System::new(),
+            pid,
+        };
+        // CPU usage is computed as the difference between two refreshes
+        monitor.refresh();
+        Ok(monitor)
+    }
+
+    fn refresh(&mut self) -> bool {
+        self.system
+            .refresh_process_specifics(self.pid, ProcessRefreshKind::new().with_cpu())
+    }
+
+    /// Measures the current usage, has to be called from within the Tokio runtime
+    pub fn usage(&mut self) -> Result<ProcessUsage> {
+        if !self.refresh() {
+            return Err(anyhow!("Process {} not found", self.pid));
+        }
+        let process = self
+            .system
+            .process(self.pid)
+            .ok_or_else(|| anyhow!("Process {} not found", self.pid))?;
+        let runtime = Handle::current().metrics();
+        Ok(ProcessUsage {
+            cpu_usage: process.cpu_usage().into(),
+            resident_memory: process.memory(),
+            open_fds: open_fds(),
+            tokio_workers: runtime.num_workers() as u64,
+            tokio_alive_tasks: runtime.num_alive_tasks() as u64,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    Some(entries.count() as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<u64> {
+    None
+}
@@ -0,0 +1,449 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use prometheus_client::{
+    encoding::{text::encode, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Mutex},
+    time::Duration,
+};
+
+use super::{MetricAttributes, MetricCounter, MetricHistogram, MetricValue};
+
+// duration buckets in milliseconds, ranging from 1ms up to ~65s
+fn duration_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(1.0, 2.0, 17))
+}
+
+type CounterLabels = Vec<(&'static str, String)>;
+type HistogramFamily = Family<CounterLabels, Histogram, fn() -> Histogram>;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct HttpRequestLabels {
+    route: String,
+    status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct BucketLabels {
+    bucket: u32,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ConnectionLabels {
+    direction: &'static str,
+    transport: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TransferLabels {
+    direction: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ThrottleLabels {
+    limit: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct CountryLabels {
+    country: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct WindowLabels {
+    window: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MultiaddressLabels {
+    multiaddress: String,
+    ip: String,
+}
+
+/// Metrics kept in a Prometheus registry, scraped through the HTTP server `/metrics` endpoint
+pub struct Metrics {
+    registry: Registry,
+    /// Attributes which change during runtime are exported as labels of the `multiaddress` gauge
+    attributes: Mutex<MetricAttributes>,
+    /// Counter families by name, labelled counters are series of the same family
+    counters: HashMap<&'static str, Family<CounterLabels, Counter>>,
+    /// Histogram families by name, same as the counters
+    histograms: HashMap<&'static str, HistogramFamily>,
+    kad_routing_peer_num: Gauge,
+    kad_routing_bucket_peer_num: Family<BucketLabels, Gauge>,
+    connection_num: Family<ConnectionLabels, Gauge>,
+    nat_status: Gauge,
+    autonat_throttle_usage: Family<ThrottleLabels, Gauge<f64, AtomicU64>>,
+    interval_transferred_bytes: Family<TransferLabels, Gauge>,
+    blocked_peer_num: Gauge,
+    command_queue_depth: Gauge,
+    command_queue_saturation: Gauge<f64, AtomicU64>,
+    process_cpu_usage: Gauge<f64, AtomicU64>,
+    process_resident_memory: Gauge,
+    process_open_fds: Gauge,
+    tokio_workers: Gauge,
+    tokio_alive_tasks: Gauge,
+    country_peer_num: Family<CountryLabels, Gauge>,
+    distinct_peer_num: Family<WindowLabels, Gauge>,
+    distinct_ip_num: Family<WindowLabels, Gauge>,
+    up: Gauge,
+    uptime_seconds: Gauge,
+    multiaddress: Family<MultiaddressLabels, Gauge>,
+    http_requests: Family<HttpRequestLabels, Counter>,
+    http_request_errors: Family<HttpRequestLabels, Counter>,
+    http_request_duration: Family<HttpRequestLabels, Histogram, fn() -> Histogram>,
+}
+
+impl Metrics {
+    /// Encodes all registered metrics in the OpenMetrics text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).context("Failed to encode Prometheus metrics")?;
+        Ok(buffer)
+    }
+}
+
+#[async_trait]
+impl super::Metrics for Metrics {
+    async fn count_n(&self, counter: MetricCounter, value: u64) {
+        if let Some(family) = self.counters.get(counter.name()) {
+            family.get_or_create(&counter.labels()).inc_by(value);
+        }
+    }
+
+    async fn record(&self, value: MetricValue) -> Result<()> {
+        match value {
+            MetricValue::KadRoutingPeerNum(num) => {
+                self.kad_routing_peer_num.set(num as i64);
+            }
+            MetricValue::KadRoutingBucketPeerNum(bucket_peers) => {
+                // buckets which got emptied since the previous update are dropped
+                self.kad_routing_bucket_peer_num.clear();
+                for (bucket, peers) in bucket_peers {
+                    self.kad_routing_bucket_peer_num
+                        .get_or_create(&BucketLabels { bucket })
+                        .set(peers as i64);
+                }
+            }
+            MetricValue::ConnectionNum(counts) => {
+                for ((direction, transport), num) in counts {
+                    let labels = ConnectionLabels {
+                        direction: direction.as_str(),
+                        transport: transport.as_str(),
+                    };
+                    self.connection_num.get_or_create(&labels).set(num as i64);
+                }
+            }
+            MetricValue::AutoNatStatus(status) => {
+                self.nat_status.set(status.value() as i64);
+            }
+            MetricValue::AutoNatThrottleUsage(usage) => {
+                for (limit, value) in usage.by_limit() {
+                    let labels = ThrottleLabels {
+                        limit: limit.as_str(),
+                    };
+                    self.autonat_throttle_usage
+                        .get_or_create(&labels)
+                        .set(value);
+                }
+            }
+            MetricValue::IntervalTransferredBytes(bytes) => {
+                for (direction, value) in bytes.by_direction() {
+                    let labels = TransferLabels {
+                        direction: direction.as_str(),
+                    };
+                    self.interval_transferred_bytes
+                        .get_or_create(&labels)
+                        .set(value as i64);
+                }
+            }
+            MetricValue::BlockedPeerNum(num) => {
+                self.blocked_peer_num.set(num as i64);
+            }
+            MetricValue::CommandQueueDepth(depth) => {
+                self.command_queue_depth.set(depth as i64);
+            }
+            MetricValue::CommandQueueSaturation(saturation) => {
+                self.command_queue_saturation.set(saturation);
+            }
+            MetricValue::ProcessUsage(usage) => {
+                self.process_cpu_usage.set(usage.cpu_usage);
+                self.process_resident_memory
+                    .set(usage.resident_memory as i64);
+                if let Some(open_fds) = usage.open_fds {
+                    self.process_open_fds.set(open_fds as i64);
+                }
+                self.tokio_workers.set(usage.tokio_workers as i64);
+                self.tokio_alive_tasks.set(usage.tokio_alive_tasks as i64);
+            }
+            MetricValue::CountryPeerNum(counts) => {
+                // countries without connected peers since the previous update are dropped
+                self.country_peer_num.clear();
+                for (country, num) in counts {
+                    self.country_peer_num
+                        .get_or_create(&CountryLabels { country })
+                        .set(num as i64);
+                }
+            }
+            MetricValue::DistinctSeenNum(counts) => {
+                for (window, counts) in counts {
+                    let labels = WindowLabels {
+                        window: window.as_str(),
+                    };
+                    self.distinct_peer_num
+                        .get_or_create(&labels)
+                        .set(counts.peers as i64);
+                    self.distinct_ip_num
+                        .get_or_create(&labels)
+                        .set(counts.ips as i64);
+                }
+            }
+            MetricValue::HealthCheck(uptime) => {
+                self.up.set(1);
+                self.uptime_seconds.set(uptime.as_secs() as i64);
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64) {
+        if let Some(family) = self.histograms.get(histogram.name()) {
+            family.get_or_create(&histogram.labels()).observe(value);
+        }
+    }
+
+    async fn set_multiaddress(&self, multiaddr: String) {
+        let Ok(mut attributes) = self.attributes.lock() else {
+            return;
+        };
+        attributes.set_multiaddress(multiaddr);
+        self.multiaddress.clear();
+        self.multiaddress
+            .get_or_create(&MultiaddressLabels {
+                multiaddress: attributes.multiaddress.clone(),
+                ip: attributes.ip.clone(),
+            })
+            .set(1);
+    }
+
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration) {
+        let labels = HttpRequestLabels {
+            route: route.to_string(),
+            status,
+        };
+        self.http_requests.get_or_create(&labels).inc();
+        if status >= 400 {
+            self.http_request_errors.get_or_create(&labels).inc();
+        }
+        self.http_request_duration
+            .get_or_create(&labels)
+            .observe(latency.as_secs_f64() * 1000.0);
+    }
+}
+
+pub fn initialize(attributes: MetricAttributes) -> Metrics {
+    let mut registry = Registry::with_prefix_and_labels(
+        "avail_light_bootstrap",
+        attributes
+            .constant()
+            .into_iter()
+            .map(|(key, value)| (Cow::Owned(key), Cow::Owned(value))),
+    );
+
+    let mut counters: HashMap<&'static str, Family<CounterLabels, Counter>> = HashMap::new();
+    for counter in MetricCounter::all() {
+        let family = counters.entry(counter.name()).or_insert_with(|| {
+            let family = Family::default();
+            registry.register(counter.name(), counter.name(), family.clone());
+            family
+        });
+        // series are created upfront, so they are exported even before the first increment
+        _ = family.get_or_create(&counter.labels());
+    }
+    let mut histograms: HashMap<&'static str, HistogramFamily> = HashMap::new();
+    for histogram in MetricHistogram::all() {
+        let family = histograms.entry(histogram.name()).or_insert_with(|| {
+            let family: HistogramFamily = Family::new_with_constructor(duration_histogram);
+            registry.register(histogram.name(), histogram.name(), family.clone());
+            family
+        });
+        _ = family.get_or_create(&histogram.labels());
+    }
+
+    let kad_routing_peer_num = Gauge::default();
+    registry.register(
+        "kad_routing_peer_num",
+        "Number of peers in the routing table",
+        kad_routing_peer_num.clone(),
+    );
+    let kad_routing_bucket_peer_num = Family::default();
+    registry.register(
+        "kad_routing_bucket_peer_num",
+        "Number of peers per non-empty k-bucket of the routing table",
+        kad_routing_bucket_peer_num.clone(),
+    );
+    let connection_num = Family::default();
+    registry.register(
+        "connection_num",
+        "Number of currently established connections",
+        connection_num.clone(),
+    );
+    let nat_status = Gauge::default();
+    registry.register(
+        "nat_status",
+        "NAT status determined by AutoNAT (0 - unknown, 1 - private, 2 - public)",
+        nat_status.clone(),
+    );
+    let autonat_throttle_usage = Family::default();
+    registry.register(
+        "autonat_throttle_usage",
+        "Peak AutoNAT dial-back requests within the throttling period, as a fraction of the limit",
+        autonat_throttle_usage.clone(),
+    );
+    let interval_transferred_bytes = Family::default();
+    registry.register(
+        "interval_transferred_bytes",
+        "Number of bytes transferred over all the connections during the last metrics interval",
+        interval_transferred_bytes.clone(),
+    );
+    let blocked_peer_num = Gauge::default();
+    registry.register(
+        "blocked_peer_num",
+        "Number of peers blocked from connecting",
+        blocked_peer_num.clone(),
+    );
+    let command_queue_depth = Gauge::default();
+    registry.register(
+        "command_queue_depth",
+        "Number of commands waiting to be handled by the event loop",
+        command_queue_depth.clone(),
+    );
+    let command_queue_saturation = Gauge::<f64, AtomicU64>::default();
+    registry.register(
+        "command_queue_saturation",
+        "Share of the command channel capacity taken by the queued commands, from 0 to 1",
+        command_queue_saturation.clone(),
+    );
+    let process_cpu_usage = Gauge::<f64, AtomicU64>::default();
+    registry.register(
+        "process_cpu_usage",
+        "CPU usage of the process since the previous metrics interval, in percent of a single core",
+        process_cpu_usage.clone(),
+    );
+    let process_resident_memory = Gauge::default();
+    registry.register(
+        "process_resident_memory_bytes",
+        "Resident memory size of the process in bytes",
+        process_resident_memory.clone(),
+    );
+    let process_open_fds = Gauge::default();
+    registry.register(
+        "process_open_fds",
+        "Number of file descriptors opened by the process",
+        process_open_fds.clone(),
+    );
+    let tokio_workers = Gauge::default();
+    registry.register(
+        "tokio_workers",
+        "Number of Tokio runtime worker threads",
+        tokio_workers.clone(),
+    );
+    let tokio_alive_tasks = Gauge::default();
+    registry.register(
+        "tokio_alive_tasks",
+        "Number of Tokio tasks which are not finished yet",
+        tokio_alive_tasks.clone(),
+    );
+    let country_peer_num = Family::default();
+    registry.register(
+        "country_peer_num",
+        "Number of connected peers per country, looked up in the GeoIP database",
+        country_peer_num.clone(),
+    );
+    let distinct_peer_num = Family::default();
+    registry.register(
+        "distinct_peer_num",
+        "Number of distinct peer IDs connected within the rolling window",
+        distinct_peer_num.clone(),
+    );
+    let distinct_ip_num = Family::default();
+    registry.register(
+        "distinct_ip_num",
+        "Number of distinct remote IPs connected within the rolling window",
+        distinct_ip_num.clone(),
+    );
+    let up = Gauge::default();
+    registry.register("up", "Set once the node is up and running", up.clone());
+    let uptime_seconds = Gauge::default();
+    registry.register(
+        "uptime_seconds",
+        "Time elapsed since the node was started, in seconds",
+        uptime_seconds.clone(),
+    );
+    let multiaddress = Family::default();
+    registry.register(
+        "multiaddress",
+        "Public multiaddress of the node, as label",
+        multiaddress.clone(),
+    );
+
+    let http_requests = Family::default();
+    registry.register(
+        "http_requests",
+        "Number of served HTTP requests",
+        http_requests.clone(),
+    );
+    let http_request_errors = Family::default();
+    registry.register(
+        "http_request_errors",
+        "Number of HTTP requests served with an error status code",
+        http_request_errors.clone(),
+    );
+    let http_request_duration: Family<_, _, fn() -> Histogram> =
+        Family::new_with_constructor(duration_histogram);
+    registry.register(
+        "http_request_duration_ms",
+        "Duration of served HTTP requests in milliseconds",
+        http_request_duration.clone(),
+    );
+
+    Metrics {
+        registry,
+        attributes: Mutex::new(attributes),
+        counters,
+        histograms,
+        kad_routing_peer_num,
+        kad_routing_bucket_peer_num,
+        connection_num,
+        nat_status,
+        autonat_throttle_usage,
+        interval_transferred_bytes,
+        blocked_peer_num,
+        command_queue_depth,
+        command_queue_saturation,
+        process_cpu_usage,
+        process_resident_memory,
+        process_open_fds,
+        tokio_workers,
+        tokio_alive_tasks,
+        country_peer_num,
+        distinct_peer_num,
+        distinct_ip_num,
+        up,
+        uptime_seconds,
+        multiaddress,
+        http_requests,
+        http_request_errors,
+        http_request_duration,
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::warn;
+
+use super::{MetricCounter, MetricHistogram, MetricValue, OTHER_LABEL, UNKNOWN_LABEL};
+use crate::types::RelabelConfig;
+
+const MAX_LABEL_VALUE_LEN: usize = 64;
+const MAX_MULTIADDRESS_LEN: usize = 256;
+
+// Truncates the value and replaces characters which are not expected in label values
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .take(MAX_LABEL_VALUE_LEN)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '/' | ':' => c,
+            _ => '_',
+        })
+        .collect();
+    if value.is_empty() {
+        return UNKNOWN_LABEL.to_string();
+    }
+    value
+}
+
+// Series with free-form label values, keyed by metric name and all the label values
+type Series = (&'static str, Vec<String>);
+
+#[derive(Default)]
+struct Labels {
+    values: HashMap<&'static str, HashSet<String>>,
+    series: HashSet<Series>,
+    // limits are reported once, not on every capped value
+    is_capped_reported: bool,
+}
+
+/// Metrics implementation which bounds the free-form label values (e.g. the agent versions
+/// reported by peers) before passing them to the inner backend. Values are truncated and
+/// sanitized, and once the number of distinct values of a label, or the total number
+/// of series with free-form labels reaches its limit, new values are replaced with `other`.
+pub struct Metrics {
+    inner: Arc<dyn super::Metrics>,
+    cfg: RelabelConfig,
+    labels: Mutex<Labels>,
+}
+
+impl Metrics {
+    pub fn new(inner: Arc<dyn super::Metrics>, cfg: RelabelConfig) -> Self {
+        Self {
+            inner,
+            cfg,
+            labels: Default::default(),
+        }
+    }
+
+    // Relabels the free-form label of the series, with the other labels of the series being bounded already
+    fn relabel(
+        &self,
+        name: &'static str,
+        labels: &[&str],
+        label: &'static str,
+        value: &str,
+    ) -> String {
+        let value = sanitize(value);
+        let Ok(mut labels_state) = self.labels.lock() else {
+            return OTHER_LABEL.to_string();
+        };
+        let state = &mut *labels_state;
+        let mut key: Vec<String> = labels.iter().map(|value| value.to_string()).collect();
+        key.push(value.clone());
+        let series = (name, key);
+        if state.series.contains(&series) {
+            return value;
+        }
+
+        let values = state.values.entry(label).or_default();
+        let is_new_value = !values.contains(&value);
+        let is_capped = (is_new_value && values.len() >= self.cfg.max_label_values)
+            || state.series.len() >= self.cfg.max_series;
+        if is_capped {
+            if !state.is_capped_reported {
+                state.is_capped_reported = true;
+                warn!("Metric label values limit reached, new values of label {label} are exported as {OTHER_LABEL}.");
+            }
+            return OTHER_LABEL.to_string();
+        }
+        if is_new_value {
+            values.insert(value.clone());
+        }
+        state.series.insert(series);
+        value
+    }
+
+    fn relabel_counter(&self, counter: MetricCounter) -> MetricCounter {
+        let name = counter.name();
+        match counter {
+            MetricCounter::Identify(kind, agent_version) => {
+                let agent_version =
+                    self.relabel(name, &[kind.as_str()], "agent_version", &agent_version);
+                MetricCounter::Identify(kind, agent_version)
+            }
+            counter => counter,
+        }
+    }
+
+    fn relabel_value(&self, value: MetricValue) -> MetricValue {
+        match value {
+            MetricValue::CountryPeerNum(counts) => {
+                // peers of the countries exported as `other` are summed up
+                let mut relabeled = BTreeMap::new();
+                for (country, num) in counts {
+                    let country = self.relabel("country_peer_num", &[], "country", &country);
+                    *relabeled.entry(country).or_default() += num;
+                }
+                MetricValue::CountryPeerNum(relabeled)
+            }
+            value => value,
+        }
+    }
+}
+
+#[async_trait]
+impl super::Metrics for Metrics {
+    async fn count_n(&self, counter: MetricCounter, value: u64) {
+        self.inner
+            .count_n(self.relabel_counter(counter), value)
+            .await;
+    }
+
+    async fn record(&self, value: MetricValue) -> Result<()> {
+        self.inner.record(self.relabel_value(value)).await
+    }
+
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64) {
+        self.inner.record_histogram(histogram, value).await;
+    }
+
+    async fn set_multiaddress(&self, multiaddr: String) {
+        // there is a single multiaddress at a time, so only its length is limited
+        let multiaddr = multiaddr.chars().take(MAX_MULTIADDRESS_LEN).collect();
+        self.inner.set_multiaddress(multiaddr).await;
+    }
+
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration) {
+        self.inner.record_http_request(route, status, latency).await;
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+}
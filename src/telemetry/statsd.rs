@@ -0,0 +1,262 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::{sync::RwLock, time::Duration};
+use tokio::net::{lookup_host, UdpSocket};
+use tracing::debug;
+
+use super::{MetricAttributes, MetricCounter, MetricHistogram, MetricValue};
+use crate::types::StatsdConfig;
+
+// keeps datagrams within the common network MTU, as recommended for StatsD over UDP
+const MAX_PACKET_SIZE: usize = 1432;
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Metrics sent over UDP to a StatsD server, with labels sent as DogStatsD tags if enabled
+pub struct Metrics {
+    socket: UdpSocket,
+    prefix: String,
+    tags_enabled: bool,
+    attributes: RwLock<MetricAttributes>,
+}
+
+// Characters used as separators by the DogStatsD protocol are replaced in tag values
+fn sanitize_tag(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '|' | ',' | '#' | '\n' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+// Metric name segments may contain only alphanumerics, underscores and dashes
+fn sanitize_segment(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+impl Metrics {
+    // Formats the metric line, with labels as tags, or as name segments when tags are disabled
+    fn line(&self, name: &str, value: &str, kind: &str, labels: Labels) -> String {
+        let mut line = format!("{}.{name}", self.prefix);
+        if !self.tags_enabled {
+            for (_, value) in &labels {
+                line.push('.');
+                line.push_str(&sanitize_segment(value));
+            }
+            return format!("{line}:{value}|{kind}");
+        }
+
+        let mut tags: Vec<String> = labels
+            .into_iter()
+            .map(|(key, value)| format!("{key}:{}", sanitize_tag(&value)))
+            .collect();
+        if let Ok(attributes) = self.attributes.read() {
+            for (key, value) in attributes.all() {
+                if !value.is_empty() {
+                    tags.push(format!("{key}:{}", sanitize_tag(&value)));
+                }
+            }
+        }
+        format!("{line}:{value}|{kind}|#{}", tags.join(","))
+    }
+
+    // Sends the lines, packing as many of them as possible into a single datagram
+    async fn send(&self, lines: Vec<String>) {
+        let mut packet = String::new();
+        for line in lines {
+            if !packet.is_empty() && packet.len() + line.len() + 1 > MAX_PACKET_SIZE {
+                self.send_packet(&packet).await;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.send_packet(&packet).await;
+        }
+    }
+
+    async fn send_packet(&self, packet: &str) {
+        // metrics are best effort, unreachable server shouldn't affect the node
+        if let Err(err) = self.socket.send(packet.as_bytes()).await {
+            debug!("Unable to send StatsD metrics: {err}");
+        }
+    }
+
+    fn gauge(&self, name: &str, value: u64, labels: Labels) -> String {
+        self.line(name, &value.to_string(), "g", labels)
+    }
+}
+
+#[async_trait]
+impl super::Metrics for Metrics {
+    async fn count_n(&self, counter: MetricCounter, value: u64) {
+        if value == 0 {
+            return;
+        }
+        let line = self.line(counter.name(), &value.to_string(), "c", counter.labels());
+        self.send(vec![line]).await;
+    }
+
+    async fn record(&self, value: MetricValue) -> Result<()> {
+        let lines = match value {
+            MetricValue::KadRoutingPeerNum(num) => {
+                vec![self.gauge("kad_routing_peer_num", num as u64, vec![])]
+            }
+            MetricValue::KadRoutingBucketPeerNum(bucket_peers) => bucket_peers
+                .into_iter()
+                .map(|(bucket, peers)| {
+                    let labels = vec![("bucket", bucket.to_string())];
+                    self.gauge("kad_routing_bucket_peer_num", peers as u64, labels)
+                })
+                .collect(),
+            MetricValue::ConnectionNum(counts) => counts
+                .into_iter()
+                .map(|((direction, transport), num)| {
+                    let labels = vec![
+                        ("direction", direction.as_str().to_string()),
+                        ("transport", transport.as_str().to_string()),
+                    ];
+                    self.gauge("connection_num", num as u64, labels)
+                })
+                .collect(),
+            MetricValue::AutoNatStatus(status) => {
+                vec![self.gauge("nat_status", status.value(), vec![])]
+            }
+            MetricValue::AutoNatThrottleUsage(usage) => usage
+                .by_limit()
+                .into_iter()
+                .map(|(limit, value)| {
+                    let labels = vec![("limit", limit.as_str().to_string())];
+                    self.line("autonat_throttle_usage", &value.to_string(), "g", labels)
+                })
+                .collect(),
+            MetricValue::IntervalTransferredBytes(bytes) => bytes
+                .by_direction()
+                .into_iter()
+                .map(|(direction, value)| {
+                    let labels = vec![("direction", direction.as_str().to_string())];
+                    self.gauge("interval_transferred_bytes", value, labels)
+                })
+                .collect(),
+            MetricValue::BlockedPeerNum(num) => {
+                vec![self.gauge("blocked_peer_num", num as u64, vec![])]
+            }
+            MetricValue::CommandQueueDepth(depth) => {
+                vec![self.gauge("command_queue_depth", depth as u64, vec![])]
+            }
+            MetricValue::CommandQueueSaturation(saturation) => {
+                vec![self.line(
+                    "command_queue_saturation",
+                    &saturation.to_string(),
+                    "g",
+                    vec![],
+                )]
+            }
+            MetricValue::ProcessUsage(usage) => {
+                let cpu_usage = usage.cpu_usage.to_string();
+                let mut lines = vec![
+                    self.line("process_cpu_usage", &cpu_usage, "g", vec![]),
+                    self.gauge(
+                        "process_resident_memory_bytes",
+                        usage.resident_memory,
+                        vec![],
+                    ),
+                    self.gauge("tokio_workers", usage.tokio_workers, vec![]),
+                    self.gauge("tokio_alive_tasks", usage.tokio_alive_tasks, vec![]),
+                ];
+                if let Some(open_fds) = usage.open_fds {
+                    lines.push(self.gauge("process_open_fds", open_fds, vec![]));
+                }
+                lines
+            }
+            MetricValue::CountryPeerNum(counts) => counts
+                .into_iter()
+                .map(|(country, num)| {
+                    self.gauge("country_peer_num", num as u64, vec![("country", country)])
+                })
+                .collect(),
+            MetricValue::DistinctSeenNum(counts) => counts
+                .into_iter()
+                .flat_map(|(window, counts)| {
+                    let labels = vec![("window", window.as_str().to_string())];
+                    [
+                        self.gauge("distinct_peer_num", counts.peers as u64, labels.clone()),
+                        self.gauge("distinct_ip_num", counts.ips as u64, labels),
+                    ]
+                })
+                .collect(),
+            MetricValue::HealthCheck(uptime) => vec![
+                self.gauge("up", 1, vec![]),
+                self.gauge("uptime_seconds", uptime.as_secs(), vec![]),
+            ],
+        };
+        self.send(lines).await;
+        Ok(())
+    }
+
+    async fn record_histogram(&self, histogram: MetricHistogram, value: f64) {
+        // all the histograms are durations in milliseconds, sent as timers
+        let line = self.line(
+            histogram.name(),
+            &value.to_string(),
+            "ms",
+            histogram.labels(),
+        );
+        self.send(vec![line]).await;
+    }
+
+    async fn set_multiaddress(&self, multiaddr: String) {
+        if let Ok(mut attributes) = self.attributes.write() {
+            attributes.set_multiaddress(multiaddr);
+        }
+    }
+
+    async fn record_http_request(&self, route: &'static str, status: u16, latency: Duration) {
+        let labels = vec![("route", route.to_string()), ("status", status.to_string())];
+        let mut lines = vec![self.line("http_requests", "1", "c", labels.clone())];
+        if status >= 400 {
+            lines.push(self.line("http_request_errors", "1", "c", labels.clone()));
+        }
+        let latency_ms = (latency.as_secs_f64() * 1000.0).to_string();
+        lines.push(self.line("http_request_duration_ms", &latency_ms, "ms", labels));
+        self.send(lines).await;
+    }
+}
+
+pub async fn initialize(cfg: StatsdConfig, attributes: MetricAttributes) -> Result<Metrics> {
+    let server = lookup_host((cfg.host.as_str(), cfg.port))
+        .await
+        .context("Unable to resolve StatsD server address")?
+        .next()
+        .context("StatsD server address not found")?;
+    let local = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)
+        .await
+        .context("Unable to bind StatsD socket")?;
+    socket
+        .connect(server)
+        .await
+        .context("Unable to connect StatsD socket")?;
+
+    Ok(Metrics {
+        socket,
+        prefix: cfg.prefix,
+        tags_enabled: cfg.tags_enabled,
+        attributes: RwLock::new(attributes),
+    })
+}
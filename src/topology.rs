@@ -0,0 +1,76 @@
+//! Snapshots of the network topology as seen by the node, taken periodically,
+//! so the evolution of the network is analyzed over time from the vantage points of the bootstrap nodes.
+//!
+//! Latest snapshot is served over HTTP, and the recent ones are kept in the state directory, if set.
+
+use anyhow::Result;
+use libp2p::futures::StreamExt;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::RwLock;
+
+use crate::p2p::client::{Client, ConnectionEntry, DHTEntry};
+
+// number of the routing table entries fetched from the event loop at once
+const TOPOLOGY_BATCH_SIZE: usize = 100;
+
+/// Peers, routing table layout and connections of the node at the given time
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologySnapshot {
+    /// Snapshot time, as UNIX timestamp in seconds
+    pub taken_at: u64,
+    pub peer_id: String,
+    /// Number of entries per non-empty k-bucket, keyed by bucket index
+    pub buckets: BTreeMap<u32, usize>,
+    pub routing_table: Vec<DHTEntry>,
+    /// Established connections, i.e. the edges of the connection graph between the node and its peers
+    pub connections: Vec<ConnectionEntry>,
+}
+
+pub struct Topology {
+    client: Client,
+    peer_id: String,
+    latest: RwLock<Option<TopologySnapshot>>,
+}
+
+impl Topology {
+    pub fn new(client: Client, peer_id: String) -> Self {
+        Self {
+            client,
+            peer_id,
+            latest: RwLock::new(None),
+        }
+    }
+
+    pub async fn latest(&self) -> Option<TopologySnapshot> {
+        self.latest.read().await.clone()
+    }
+
+    /// Takes a snapshot, replacing the latest one.
+    /// Routing table is fetched in batches after the buckets and connections, so it may differ from them slightly.
+    pub async fn take(&self) -> Result<TopologySnapshot> {
+        let topology = self.client.get_topology().await?;
+        let batches = self.client.stream_dht_entries(TOPOLOGY_BATCH_SIZE);
+        tokio::pin!(batches);
+        let mut routing_table = vec![];
+        while let Some(batch) = batches.next().await {
+            routing_table.extend(batch?);
+        }
+        let taken_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let snapshot = TopologySnapshot {
+            taken_at,
+            peer_id: self.peer_id.clone(),
+            buckets: topology.buckets,
+            routing_table,
+            connections: topology.connections,
+        };
+        *self.latest.write().await = Some(snapshot.clone());
+        Ok(snapshot)
+    }
+}
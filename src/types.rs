@@ -1,27 +1,159 @@
 use anyhow::Context;
-use libp2p::StreamProtocol;
+use libp2p::{
+    kad::{self, store::MemoryStoreConfig},
+    multiaddr::Protocol,
+    Multiaddr, StreamProtocol,
+};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
     fmt::{self, Display},
-    net::SocketAddr,
+    net::{Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
     str::FromStr,
     time::Duration,
 };
 
-const MINIMUM_SUPPORTED_BOOTSTRAP_VERSION: &str = "0.1.1";
-const MINIMUM_SUPPORTED_LIGHT_CLIENT_VERSION: &str = "1.9.2";
+use crate::state;
+
+pub const MINIMUM_SUPPORTED_BOOTSTRAP_VERSION: &str = "0.1.1";
+pub const MINIMUM_SUPPORTED_LIGHT_CLIENT_VERSION: &str = "1.9.2";
 pub const KADEMLIA_PROTOCOL_BASE: &str = "/avail_kad/id/1.0.0";
 pub const IDENTITY_PROTOCOL: &str = "/avail_kad/id/1.0.0";
 pub const IDENTITY_AGENT_BASE: &str = "avail-light-client";
 pub const IDENTITY_AGENT_ROLE: &str = "bootstrap";
 pub const IDENTITY_AGENT_CLIENT_TYPE: &str = "rust-client";
+/// Role of the node, exported with the telemetry
+pub const CLIENT_ROLE: &str = "bootnode";
+
+/// Build information, embedded by the build script
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    /// Build time, as HTTP date
+    pub build_timestamp: &'static str,
+    pub rustc_version: &'static str,
+}
+
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_commit: env!("GIT_COMMIT"),
+    build_timestamp: env!("BUILD_TIMESTAMP"),
+    rustc_version: env!("RUSTC_VERSION"),
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum SecretKey {
-    Seed { seed: String },
-    Key { key: String },
+    Seed {
+        seed: String,
+    },
+    Key {
+        key: String,
+    },
+    /// Hex encoded ed25519 private key read from the file
+    KeyFile {
+        path: String,
+    },
+    /// Hex encoded ed25519 private key read from the environment variable
+    Env {
+        var: String,
+    },
+    /// Secret key of the identity file at `identity_path`, generated into it on the first start, set as `secret_key = "identity"`
+    Identity(IdentityKey),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentityKey {
+    Identity,
+}
+
+/// Content of the identity file, holding the hex encoded ed25519 secret key of the node.
+/// Identity files of the avail-light client are supported as well, with the keypair
+/// generated from the Avail account secret the same way as from the `seed`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IdentityFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avail_secret_seed_phrase: Option<String>,
+    #[serde(alias = "avail_secret_key", skip_serializing_if = "Option::is_none")]
+    pub avail_secret_uri: Option<String>,
+}
+
+impl IdentityFile {
+    /// Returns the secret key, preferring the node key over the Avail account secret,
+    /// and the secret URI over the seed phrase, same as the avail-light client
+    pub fn secret_key(&self) -> Option<SecretKey> {
+        if let Some(key) = &self.key {
+            return Some(SecretKey::Key { key: key.clone() });
+        }
+        self.avail_secret_uri
+            .as_ref()
+            .or(self.avail_secret_seed_phrase.as_ref())
+            .map(|seed| SecretKey::Seed { seed: seed.clone() })
+    }
+}
+
+/// Backend to which the metrics are exported
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsBackend {
+    /// Push metrics to the OpenTelemetry Collector
+    Otlp,
+    /// Expose metrics for scraping on the HTTP server `/metrics` endpoint
+    Prometheus,
+    /// Send metrics over UDP to the StatsD server
+    Statsd,
+    /// Do not export metrics
+    None,
+}
+
+/// Either a single metrics backend, or a list of backends to which the metrics are exported at once
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MetricsBackends {
+    Single(MetricsBackend),
+    Multiple(Vec<MetricsBackend>),
+}
+
+impl MetricsBackends {
+    /// Returns the distinct backends to export metrics to, without the `none` ones
+    pub fn enabled(&self) -> Vec<MetricsBackend> {
+        let backends = match self {
+            MetricsBackends::Single(backend) => vec![*backend],
+            MetricsBackends::Multiple(backends) => backends.clone(),
+        };
+        let mut enabled = vec![];
+        for backend in backends {
+            if backend != MetricsBackend::None && !enabled.contains(&backend) {
+                enabled.push(backend);
+            }
+        }
+        enabled
+    }
+}
+
+/// Format of the console logs
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Plain,
+    Json,
+    Logfmt,
+}
+
+/// Protocol used to export metrics to the OpenTelemetry Collector
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OtlpProtocol {
+    Grpc,
+    HttpProtobuf,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,72 +163,417 @@ pub struct RuntimeConfig {
     pub http_server_host: String,
     /// Bootstrap HTTP server port (default: 7700).
     pub http_server_port: u16,
+    /// If set, HTTP server is served on the unix domain socket at the given path, instead of the TCP host and port (default: None).
+    pub http_server_unix_socket: Option<String>,
+    /// Minimum number of peers in the routing table for the node to be reported as healthy (default: 1).
+    pub health_min_dht_peers: usize,
+    /// Maximum time allowed for the event loop to respond to a health check (default: 5s).
+    pub health_check_timeout: u64,
+    /// Enables the JSON-RPC 2.0 admin server (default: false).
+    pub rpc_enable: bool,
+    /// JSON-RPC admin server host name (default: 127.0.0.1).
+    pub rpc_host: String,
+    /// JSON-RPC admin server port (default: 7701).
+    pub rpc_port: u16,
+    /// Enables the gRPC admin server (default: false).
+    pub grpc_enable: bool,
+    /// gRPC admin server host name (default: 127.0.0.1).
+    pub grpc_host: String,
+    /// gRPC admin server port (default: 7702).
+    pub grpc_port: u16,
     /// Log level. See `<https://docs.rs/log/0.4.17/log/enum.LevelFilter.html>` for possible log level values. (default: `INFO`)
+    /// Plain log level applies to the node logs only. `EnvFilter` directives can be set instead, e.g. `info,libp2p_kad=debug`,
+    /// filtering the logs of all the modules, the same way as `RUST_LOG` does, which takes precedence over the config file if set.
     pub log_level: String,
-    /// Set to display structured logs in JSON format. Otherwise, plain text format is used. (default: false)
+    /// Format of the logs, either `plain`, `json` or `logfmt` for structured logging (default: plain).
+    pub log_format: LogFormat,
+    /// Deprecated, set to display structured logs in JSON format, same as `log_format = "json"`. (default: false)
     pub log_format_json: bool,
-    /// Sets the listening P2P network service port. (default: 39000)
-    pub port: u16,
+    /// Sets the listening P2P network service TCP port, also set by the former `port` key. (default: 39000)
+    #[serde(alias = "port")]
+    pub tcp_port: u16,
+    /// Enable QUIC transport, next to the TCP one (default: false)
+    pub quic_enable: bool,
+    /// Sets the listening P2P network service QUIC (UDP) port, if QUIC transport is enabled. (default: 39001)
+    pub quic_port: u16,
+    /// Multiaddresses the P2P network service listens on, instead of all the IPv4 interfaces on `tcp_port` and `quic_port`,
+    /// e.g. `["/ip4/0.0.0.0/tcp/39000", "/ip6/::/tcp/39000"]`, with `/ws` appended if WebSocket transport is enabled,
+    /// `/ip4/0.0.0.0/udp/39001/quic-v1` for QUIC and `/ip4/0.0.0.0/tcp/443/wss` for WSS (default: []).
+    pub listen_multiaddrs: Vec<String>,
     /// Enable WebSocket transport over TCP
     pub ws_transport_enable: bool,
+    /// If set to true, Noise handshakes of the TCP and WebSocket connections are bound to the genesis hash through the prologue,
+    /// so the peers of the other networks fail the handshake right away. Peers have to use the same prologue,
+    /// i.e. `/avail/noise/<genesis hash>` with the lowercase hex hash, without `0x`. Ignored for the `DEV` genesis hash (default: false).
+    pub noise_genesis_prologue: bool,
+    /// Enable secure WebSocket (WSS) listener next to the other transports, for the clients restricted to 443 (default: false).
+    pub wss_enable: bool,
+    /// Sets the listening secure WebSocket TCP port, if WSS is enabled. Ports below 1024 require the `CAP_NET_BIND_SERVICE` capability (default: 443)
+    pub wss_port: u16,
+    /// Path of the PEM encoded TLS certificate chain of the WSS listener. If neither it nor `wss_key_path` is set,
+    /// a self-signed certificate for `localhost` is generated on each start, suitable for the development only (default: None).
+    pub wss_cert_path: Option<String>,
+    /// Path of the PEM encoded private key of the WSS listener certificate (default: None).
+    pub wss_key_path: Option<String>,
+    /// Maximum number of the concurrent substreams of a single connection, e.g. the Kademlia and identify requests,
+    /// so a single peer can't monopolize the node with the parallel queries. TCP and WebSocket connections exceeding it are closed,
+    /// while QUIC peers are kept from opening more (default: 256).
+    pub connection_max_substreams: usize,
+    /// Minimum time the connections of the light clients, i.e. the peers identified as Avail peers other than bootstrap nodes,
+    /// are kept alive since established, even if idle, so they can finish the discovery, in seconds. Set to 0 to disable (default: 120s).
+    pub connection_light_client_min_duration: u64,
+    /// Time after which the connections of the peers not identified as Avail peers with a supported version are closed,
+    /// counted since established, in seconds. Set to 0 to keep them until idle (default: 15s).
+    pub connection_unidentified_timeout: u64,
     /// Sets the amount of time to keep connections alive when they're idle. (default: 30s).
     /// NOTE: libp2p default value is 10s, but because of Avail block time of 20s the value has been increased
     pub connection_idle_timeout: u64,
-    /// Autonat server config - max total dial requests (Default: 30).
+    /// Autonat server config - max total dial requests (Default: 120).
     pub autonat_throttle_clients_global_max: usize,
-    /// Autonat server config - max dial requests for a single peer (Default: 3).
+    /// Autonat server config - max dial requests for a single peer (Default: 4).
     pub autonat_throttle_clients_peer_max: usize,
     /// Autonat server config - period for throttling clients requests (Default 1s).
     pub autonat_throttle_clients_period: u32,
     /// Autonat server config - configures AutoNAT behaviour to reject probes as a server for clients that are observed at a non-global ip address (default: true)
     pub autonat_only_global_ips: bool,
+    /// Autonat client config - delay before the first NAT status probe after start, in seconds (default: 15s).
+    pub autonat_boot_delay: u64,
+    /// Autonat client config - interval of the NAT status probes once the status is confirmed, in seconds (default: 900s).
+    pub autonat_refresh_interval: u64,
+    /// Autonat client config - interval of the NAT status probes while the status is unknown or changing, in seconds (default: 90s).
+    pub autonat_retry_interval: u64,
     /// Sets the timeout for a single Kademlia query. (default: 60s).
     pub kad_query_timeout: u32,
+    /// Maximum number of records kept in the Kademlia store, further records put by the other peers are rejected. (default: 1024)
+    pub kad_store_max_records: usize,
+    /// Maximum number of keys with provider records kept in the Kademlia store, providers of further keys are rejected. (default: 1024)
+    pub kad_store_max_provided_keys: usize,
+    /// Maximum size of a record value kept in the Kademlia store, in bytes, larger records are rejected. (default: 65536)
+    pub kad_store_max_value_bytes: usize,
+    /// Kademlia protocol names served besides the one derived from the genesis hash, e.g. the former one during a migration,
+    /// so the clients of both generations can query the node. Own queries use the derived one (default: []).
+    pub kad_extra_protocol_names: Vec<String>,
+    /// If set to true, lookups are run over the disjoint paths, as in S/Kademlia, so they succeed as long as
+    /// any of the paths is free of the adversarial peers, at the cost of more requests per lookup (default: false).
+    pub kad_disjoint_query_paths: bool,
+    /// Number of the peers queried at once by each lookup, also the number of the disjoint paths if enabled (default: 3).
+    pub kad_query_parallelism: usize,
+    /// Number of the closest peers a lookup converges on, and records are replicated to (default: 20).
+    pub kad_replication_factor: usize,
     /// Defines a period of time in which periodic bootstraps will be repeated. (default: 300s)
     pub bootstrap_period: u64,
-    /// OpenTelemetry Collector endpoint (default: http://127.0.0.1:4317)
+    /// Period of the periodic bootstraps while the routing table has less than `bootstrap_target_peers` peers, in seconds (default: 30s).
+    pub bootstrap_cold_period: u64,
+    /// Routing table size below which bootstraps are repeated every `bootstrap_cold_period` instead of `bootstrap_period`.
+    /// Set to 0 to always use `bootstrap_period` (default: 20).
+    pub bootstrap_target_peers: usize,
+    /// Interval of the random walks, i.e. the closest peers lookups of random keys, keeping the distant buckets populated
+    /// and discovering the new peers, besides the periodic bootstraps, in seconds. Set to 0 to disable them (default: 60s).
+    pub random_walk_interval: u64,
+    /// Maximum number of the random walks running at once, further ones are skipped until they finish (default: 2).
+    pub random_walk_concurrency: usize,
+    /// Interval in which the listen addresses reported over identify by a peer are all re-added to the routing table.
+    /// In between, only the addresses not reported before are added. Set to 0 to add all of them on every identify (default: 300s).
+    pub identify_address_refresh_interval: u64,
+    /// If set to true, observed public addresses of the inbound QUIC connections are added to the routing table as well,
+    /// if they have the same UDP port as any of the private listen addresses reported by the peer over identify,
+    /// so the peers behind the port-preserving NATs become reachable (default: true).
+    pub identify_quic_address_translation: bool,
+    /// Maximum number of the connected routing table peers handed out in a single response of the `/avail/peer-exchange/1` protocol.
+    /// Set to 0 to respond without any peers (default: 50).
+    pub peer_exchange_max_peers: usize,
+    /// Maximum number of network event loop restarts within `event_loop_restart_window`, after it panics or stops.
+    /// Node is shut down once exceeded, and the event loop isn't restarted at all if set to 0 (default: 5).
+    pub event_loop_max_restarts: usize,
+    /// Period in which the network event loop restarts are counted against `event_loop_max_restarts`, in seconds (default: 600s).
+    pub event_loop_restart_window: u64,
+    /// Interval of the network event loop stall checks, in seconds. Stalled event loop is restarted, same as the failed one.
+    /// Stall checks are disabled if set to 0 (default: 60s).
+    pub stall_check_interval: u64,
+    /// Maximum time for the event loop to respond to the stall check, in seconds (default: 30s).
+    pub stall_command_timeout: u64,
+    /// Maximum time without any swarm events handled while there are established connections, in seconds (default: 600s).
+    pub stall_swarm_event_timeout: u64,
+    /// Maximum time for the network event loop to finish the pending queries on shutdown, once the listeners are closed, in seconds (default: 5s).
+    pub shutdown_grace_period: u64,
+    /// Interval of the maintenance, pruning stale peers, re-probing external addresses and re-publishing provider records, in seconds.
+    /// Maintenance is disabled if set to 0 (default: 600s).
+    pub maintenance_interval: u64,
+    /// Time after which disconnected routing table peers are pruned, counted since the last connection, in seconds (default: 3600s).
+    pub maintenance_stale_peer_timeout: u64,
+    /// Interval of the network topology snapshots, served at `/v1/topology` and written into the `topology` directory of `state_path`, if set, in seconds.
+    /// Snapshots are disabled if set to 0 (default: 3600s).
+    pub topology_snapshot_interval: u64,
+    /// Number of the most recent topology snapshots kept in the state directory, the older ones are removed. Set to 0 to keep all of them (default: 168).
+    pub topology_snapshot_retention: usize,
+    /// Maximum time for the network event loop to respond to the client commands, in seconds (default: 30s).
+    pub client_command_timeout: u64,
+    /// Maximum time for the network event loop to respond to the client commands waiting on the other peers,
+    /// i.e. bootstraps, dials and closest peers lookups, in seconds (default: 300s).
+    pub client_query_timeout: u64,
+    /// Number of the client commands queued for the network event loop. Once the queue is full,
+    /// non-critical commands, i.e. the metrics and status getters, are rejected as overloaded,
+    /// while the others wait for the capacity (default: 1000).
+    pub client_command_channel_capacity: usize,
+    /// Metrics backend, either `otlp`, `prometheus`, `statsd` or `none` to disable metrics.
+    /// List of backends can be set as well, to export metrics to all of them (default: otlp).
+    pub metrics_backend: MetricsBackends,
+    /// OpenTelemetry Collector endpoint. Metrics are disabled if set to empty string (default: http://127.0.0.1:4317)
     pub ot_collector_endpoint: String,
+    /// OpenTelemetry Collector protocol, either `grpc` or `http-protobuf`. For `http-protobuf`, `/v1/metrics` path is appended to the endpoint if missing (default: grpc).
+    pub ot_collector_protocol: OtlpProtocol,
+    /// Maximum number of metric exports kept in memory while the OpenTelemetry Collector is unreachable, to be sent once it is reachable again (default: 60).
+    pub ot_export_buffer_size: usize,
+    /// StatsD server host name (default: 127.0.0.1).
+    pub statsd_host: String,
+    /// StatsD server port (default: 8125).
+    pub statsd_port: u16,
+    /// Prefix of the metric names sent to the StatsD server (default: avail_light_bootstrap).
+    pub statsd_prefix: String,
+    /// Set to send metric labels as DogStatsD tags. Otherwise, label values are appended to the metric names (default: true).
+    pub statsd_tags_enable: bool,
     /// Defines a period of time in which periodic metric network dump events will be repeated. (default: 15s)
     pub metrics_network_dump_interval: u64,
-    /// Secret key used to generate keypair. Can be either set to `seed` or to `key`. (default: seed="1")
+    /// Set to export logs to the OpenTelemetry Collector, in addition to the console (default: false).
+    pub ot_logs_enable: bool,
+    /// Log level of the logs exported to the OpenTelemetry Collector, independent of the console log level (default: `INFO`).
+    pub ot_logs_level: String,
+    /// Secret key used to generate keypair. Can be set to `seed`, `key`, `path`, `var` or to `"identity"`. (default: seed="1")
     /// If set to seed, keypair will be generated from that seed.
     /// If set to key, a valid ed25519 private key must be provided, else the client will fail
-    /// If `secret_key` is not set, random seed will be used.
+    /// If set to path or var, the hex encoded ed25519 private key is read from that file or environment variable,
+    /// so it doesn't have to be kept in the config file.
+    /// If set to `"identity"`, or not set at all, the key is loaded from the identity file, or generated into it on the first start.
     /// Default bootstrap peerID is 12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz
     pub secret_key: Option<SecretKey>,
+    /// Path of the identity file, holding the secret key used when `secret_key` is set to `"identity"` (default: identity.toml).
+    /// Relative path is resolved against the directory of the config file. Identity file of the avail-light client can be used as well.
+    pub identity_path: String,
+    /// If set, PID of the node is written to the file at the given path on start, and the file is removed on shutdown (default: None).
+    pub pid_file: Option<String>,
+    /// If set, the node state is kept in the directory at the given path: the identity file, used instead of `identity_path`,
+    /// and the routing table, blocked peers, external addresses and latest crawl, saved on shutdown and restored on start (default: None).
+    pub state_path: Option<String>,
     pub origin: String,
     /// Genesis hash of the network to be connected to. Set to a string beginning with "DEV" to connect to any network.
     pub genesis_hash: String,
+    /// Multiaddresses of the other bootstrap nodes of the network, dialed on start.
+    /// `/dnsaddr` addresses are resolved into all the addresses listed in their TXT records,
+    /// which are resolved again once the records expire, dialing the bootstrap nodes added since (default: []).
+    pub bootstraps: Vec<String>,
+    /// Extra labels attached to all the exported metrics, e.g. `{ region = "eu" }` (default: none).
+    /// Label names must be valid Prometheus label names, and can't override the node attributes.
+    pub metrics_extra_labels: BTreeMap<String, String>,
+    /// Maximum number of distinct values of a free-form metric label, such as the agent version reported by peers.
+    /// Values over the limit are exported as `other` (default: 50).
+    pub metrics_max_label_values: usize,
+    /// Maximum number of metric series with free-form label values, over which new values are exported as `other` (default: 1000).
+    pub metrics_max_series: usize,
+    /// Path to the MaxMind country (or city) database, used to look up the countries of connected peers (default: None).
+    pub geoip_country_db_path: Option<String>,
+    /// Path to the MaxMind ASN database, used to look up the autonomous systems of connected peers (default: None).
+    pub geoip_asn_db_path: Option<String>,
+}
+
+/// Metric labels cardinality limits (see [RuntimeConfig] for details)
+pub struct RelabelConfig {
+    pub max_label_values: usize,
+    pub max_series: usize,
 }
 
+impl From<&RuntimeConfig> for RelabelConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        RelabelConfig {
+            max_label_values: val.metrics_max_label_values,
+            max_series: val.metrics_max_series,
+        }
+    }
+}
+
+/// GeoIP databases configuration (see [RuntimeConfig] for details)
+pub struct GeoIpConfig {
+    pub country_db_path: Option<String>,
+    pub asn_db_path: Option<String>,
+}
+
+impl From<&RuntimeConfig> for GeoIpConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        GeoIpConfig {
+            country_db_path: val.geoip_country_db_path.clone(),
+            asn_db_path: val.geoip_asn_db_path.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct LibP2PConfig {
     pub port: u16,
     pub autonat: AutonatConfig,
     pub identify: IdentifyConfig,
     pub kademlia: KademliaConfig,
     pub secret_key: Option<SecretKey>,
-    pub bootstrap_interval: Duration,
+    pub identity_path: String,
+    pub bootstrap: BootstrapConfig,
+    pub random_walk: RandomWalkConfig,
+    pub client: ClientConfig,
     pub connection_idle_timeout: Duration,
+    pub max_substreams_per_connection: usize,
+    pub connection_policy: ConnectionPolicyConfig,
+    pub shutdown_grace_period: Duration,
+    /// Secure WebSocket listener, if enabled
+    pub wss: Option<WssConfig>,
+    /// Prologue of the Noise handshakes, empty unless bound to the genesis hash
+    pub noise_prologue: Vec<u8>,
+    pub network_name: String,
+    pub genesis_hash: String,
+    /// Other bootstrap nodes, recommended to the clients over the bootstrap info protocol
+    pub bootstraps: Vec<String>,
+    pub peer_exchange_max_peers: usize,
 }
 
 impl From<&RuntimeConfig> for LibP2PConfig {
     fn from(rtcfg: &RuntimeConfig) -> Self {
         Self {
-            port: rtcfg.port,
+            port: rtcfg.tcp_port,
             autonat: rtcfg.into(),
-            identify: IdentifyConfig::new(),
+            identify: rtcfg.into(),
             kademlia: rtcfg.into(),
             secret_key: rtcfg.secret_key.clone(),
-            bootstrap_interval: Duration::from_secs(rtcfg.bootstrap_period),
+            identity_path: rtcfg.identity_file(),
+            bootstrap: rtcfg.into(),
+            random_walk: rtcfg.into(),
+            client: rtcfg.into(),
             connection_idle_timeout: Duration::from_secs(rtcfg.connection_idle_timeout),
+            max_substreams_per_connection: rtcfg.connection_max_substreams,
+            connection_policy: rtcfg.into(),
+            shutdown_grace_period: Duration::from_secs(rtcfg.shutdown_grace_period),
+            wss: rtcfg.wss_enable.then(|| rtcfg.into()),
+            noise_prologue: rtcfg.noise_prologue(),
+            network_name: network_name(&rtcfg.genesis_hash),
+            genesis_hash: rtcfg.genesis_hash.clone(),
+            bootstraps: rtcfg.bootstraps.clone(),
+            peer_exchange_max_peers: rtcfg.peer_exchange_max_peers,
+        }
+    }
+}
+
+/// Secure WebSocket listener configuration (see [RuntimeConfig] for details)
+#[derive(Clone)]
+pub struct WssConfig {
+    /// PEM encoded certificate chain and private key, read on each (re)start of the event loop,
+    /// with a self-signed certificate generated if not set
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+}
+
+impl From<&RuntimeConfig> for WssConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        WssConfig {
+            cert_path: val.wss_cert_path.clone(),
+            key_path: val.wss_key_path.clone(),
+        }
+    }
+}
+
+/// Periodic bootstraps configuration (see [RuntimeConfig] for details)
+#[derive(Clone)]
+pub struct BootstrapConfig {
+    pub interval: Duration,
+    /// Used instead of `interval` while the routing table has less than `target_peers` peers
+    pub cold_interval: Duration,
+    pub target_peers: usize,
+}
+
+impl From<&RuntimeConfig> for BootstrapConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        BootstrapConfig {
+            interval: Duration::from_secs(val.bootstrap_period),
+            cold_interval: Duration::from_secs(val.bootstrap_cold_period),
+            target_peers: val.bootstrap_target_peers,
+        }
+    }
+}
+
+/// Connection durations by the peer class, as identified (see [RuntimeConfig] for details)
+#[derive(Clone, Copy)]
+pub struct ConnectionPolicyConfig {
+    /// Light clients are kept connected as usual if zero
+    pub light_client_min_duration: Duration,
+    /// Unidentified peers are kept connected until idle if zero
+    pub unidentified_timeout: Duration,
+}
+
+impl From<&RuntimeConfig> for ConnectionPolicyConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        ConnectionPolicyConfig {
+            light_client_min_duration: Duration::from_secs(
+                val.connection_light_client_min_duration,
+            ),
+            unidentified_timeout: Duration::from_secs(val.connection_unidentified_timeout),
+        }
+    }
+}
+
+/// Random walks configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Copy)]
+pub struct RandomWalkConfig {
+    /// Random walks are disabled if zero
+    pub interval: Duration,
+    pub concurrency: usize,
+}
+
+impl From<&RuntimeConfig> for RandomWalkConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        RandomWalkConfig {
+            interval: Duration::from_secs(val.random_walk_interval),
+            concurrency: val.random_walk_concurrency,
+        }
+    }
+}
+
+/// Deadlines of the network client command round-trips and the command channel capacity (see [RuntimeConfig] for details)
+#[derive(Clone, Copy)]
+pub struct ClientConfig {
+    pub command_timeout: Duration,
+    /// Used instead of `command_timeout` for the commands waiting on the other peers
+    pub query_timeout: Duration,
+    pub command_channel_capacity: usize,
+}
+
+impl From<&RuntimeConfig> for ClientConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        ClientConfig {
+            command_timeout: Duration::from_secs(val.client_command_timeout),
+            query_timeout: Duration::from_secs(val.client_query_timeout),
+            command_channel_capacity: val.client_command_channel_capacity,
         }
     }
 }
 
 /// Kademlia configuration (see [RuntimeConfig] for details)
+#[derive(Clone)]
 pub struct KademliaConfig {
     pub query_timeout: Duration,
     pub protocol_name: StreamProtocol,
+    /// Served along with `protocol_name`, which is preferred for the own queries
+    pub extra_protocol_names: Vec<StreamProtocol>,
+    pub is_disjoint_query_paths: bool,
+    pub query_parallelism: NonZeroUsize,
+    pub replication_factor: NonZeroUsize,
+    pub store: MemoryStoreConfig,
+}
+
+impl KademliaConfig {
+    /// Returns all the served protocol names, starting with the preferred one
+    pub fn protocol_names(&self) -> Vec<StreamProtocol> {
+        let mut protocol_names = vec![self.protocol_name.clone()];
+        for protocol_name in &self.extra_protocol_names {
+            if !protocol_names.contains(protocol_name) {
+                protocol_names.push(protocol_name.clone());
+            }
+        }
+        protocol_names
+    }
 }
 
 impl From<&RuntimeConfig> for KademliaConfig {
@@ -110,18 +587,41 @@ impl From<&RuntimeConfig> for KademliaConfig {
             gen_hash = genhash_short
         ))
         .expect("Invalid Kademlia protocol name");
+        // invalid names are reported by the config validation
+        let extra_protocol_names = val
+            .kad_extra_protocol_names
+            .iter()
+            .filter_map(|name| StreamProtocol::try_from_owned(name.clone()).ok())
+            .collect();
         KademliaConfig {
             query_timeout: Duration::from_secs(val.kad_query_timeout.into()),
             protocol_name,
+            extra_protocol_names,
+            is_disjoint_query_paths: val.kad_disjoint_query_paths,
+            // zero values are reported by the config validation
+            query_parallelism: NonZeroUsize::new(val.kad_query_parallelism)
+                .unwrap_or(kad::ALPHA_VALUE),
+            replication_factor: NonZeroUsize::new(val.kad_replication_factor)
+                .unwrap_or(kad::K_VALUE),
+            store: MemoryStoreConfig {
+                max_records: val.kad_store_max_records,
+                max_provided_keys: val.kad_store_max_provided_keys,
+                max_value_bytes: val.kad_store_max_value_bytes,
+                ..Default::default()
+            },
         }
     }
 }
 
+#[derive(Clone)]
 pub struct AutonatConfig {
     pub throttle_clients_global_max: usize,
     pub throttle_clients_peer_max: usize,
     pub throttle_clients_period: Duration,
     pub only_global_ips: bool,
+    pub boot_delay: Duration,
+    pub refresh_interval: Duration,
+    pub retry_interval: Duration,
 }
 
 impl From<&RuntimeConfig> for AutonatConfig {
@@ -133,6 +633,9 @@ impl From<&RuntimeConfig> for AutonatConfig {
                 val.autonat_throttle_clients_period.into(),
             ),
             only_global_ips: val.autonat_only_global_ips,
+            boot_delay: Duration::from_secs(val.autonat_boot_delay),
+            refresh_interval: Duration::from_secs(val.autonat_refresh_interval),
+            retry_interval: Duration::from_secs(val.autonat_retry_interval),
         }
     }
 }
@@ -142,25 +645,597 @@ impl Default for RuntimeConfig {
         RuntimeConfig {
             http_server_host: "127.0.0.1".to_owned(),
             http_server_port: 7700,
+            http_server_unix_socket: None,
+            health_min_dht_peers: 1,
+            health_check_timeout: 5,
+            rpc_enable: false,
+            rpc_host: "127.0.0.1".to_owned(),
+            rpc_port: 7701,
+            grpc_enable: false,
+            grpc_host: "127.0.0.1".to_owned(),
+            grpc_port: 7702,
             log_level: "INFO".to_string(),
+            log_format: LogFormat::Plain,
             log_format_json: false,
             secret_key: Some(SecretKey::Seed {
                 seed: "1".to_string(),
             }),
-            port: 39000,
+            identity_path: "identity.toml".to_string(),
+            pid_file: None,
+            state_path: None,
+            tcp_port: 39000,
+            quic_enable: false,
+            quic_port: 39001,
+            listen_multiaddrs: vec![],
             ws_transport_enable: false,
+            noise_genesis_prologue: false,
+            wss_enable: false,
+            wss_port: 443,
+            wss_cert_path: None,
+            wss_key_path: None,
             autonat_throttle_clients_global_max: 120,
             autonat_throttle_clients_peer_max: 4,
             autonat_throttle_clients_period: 1,
             autonat_only_global_ips: true,
+            autonat_boot_delay: 15,
+            autonat_refresh_interval: 900,
+            autonat_retry_interval: 90,
             connection_idle_timeout: 30,
+            connection_light_client_min_duration: 120,
+            connection_unidentified_timeout: 15,
+            connection_max_substreams: 256,
             kad_query_timeout: 60,
+            kad_store_max_records: 1024,
+            kad_store_max_provided_keys: 1024,
+            kad_store_max_value_bytes: 65 * 1024,
+            kad_extra_protocol_names: vec![],
+            kad_disjoint_query_paths: false,
+            kad_query_parallelism: 3,
+            kad_replication_factor: 20,
             bootstrap_period: 300,
+            bootstrap_cold_period: 30,
+            bootstrap_target_peers: 20,
+            random_walk_interval: 60,
+            random_walk_concurrency: 2,
+            identify_address_refresh_interval: 300,
+            identify_quic_address_translation: true,
+            peer_exchange_max_peers: 50,
+            event_loop_max_restarts: 5,
+            event_loop_restart_window: 600,
+            stall_check_interval: 60,
+            stall_command_timeout: 30,
+            stall_swarm_event_timeout: 600,
+            shutdown_grace_period: 5,
+            maintenance_interval: 600,
+            maintenance_stale_peer_timeout: 3600,
+            topology_snapshot_interval: 3600,
+            topology_snapshot_retention: 168,
+            client_command_timeout: 30,
+            client_query_timeout: 300,
+            client_command_channel_capacity: 1000,
+            metrics_backend: MetricsBackends::Single(MetricsBackend::Otlp),
             ot_collector_endpoint: "http://127.0.0.1:4317".to_string(),
+            ot_collector_protocol: OtlpProtocol::Grpc,
+            ot_export_buffer_size: 60,
+            statsd_host: "127.0.0.1".to_string(),
+            statsd_port: 8125,
+            statsd_prefix: "avail_light_bootstrap".to_string(),
+            statsd_tags_enable: true,
             metrics_network_dump_interval: 15,
+            ot_logs_enable: false,
+            ot_logs_level: "INFO".to_string(),
             origin: "external".to_string(),
             genesis_hash: "DEV".to_owned(),
+            bootstraps: vec![],
+            metrics_extra_labels: BTreeMap::new(),
+            metrics_max_label_values: 50,
+            metrics_max_series: 1000,
+            geoip_country_db_path: None,
+            geoip_asn_db_path: None,
+        }
+    }
+}
+
+/// Prefix of the environment variables overriding the config file values
+pub const ENV_PREFIX: &str = "AVAIL_BOOTSTRAP_";
+// former names of the renamed fields, still accepted
+const RENAMED_FIELDS: [(&str, &str); 1] = [("port", "tcp_port")];
+
+impl RuntimeConfig {
+    /// Overrides config values with the ones set by `AVAIL_BOOTSTRAP_<FIELD>` environment variables.
+    /// Values are parsed as TOML values, e.g. `["otlp", "prometheus"]` for lists or `{ key = "..." }` for the secret key,
+    /// with values of string fields taken as is, unless set to a list or a table. Variables not matching any of the fields are rejected,
+    /// so the misspelled ones don't leave the config values unset, except for the one holding the secret key, if set as `var`.
+    /// Only the prefixed variables have to be valid UTF-8, the other ones are skipped.
+    pub fn with_env_overrides(
+        self,
+        vars: impl Iterator<Item = (OsString, OsString)>,
+    ) -> anyhow::Result<Self> {
+        let mut value =
+            toml::Value::try_from(&self).context("Failed to serialize configuration")?;
+        let toml::Value::Table(table) = &mut value else {
+            anyhow::bail!("Configuration is expected to be a table");
+        };
+        let fields = config_fields();
+        // variable holding the secret key may be prefixed as well, without being a config field
+        let secret_key_var = match &self.secret_key {
+            Some(SecretKey::Env { var }) => Some(var.clone()),
+            _ => None,
+        };
+        let mut is_overridden = false;
+        for (name, raw) in vars {
+            if !name.as_encoded_bytes().starts_with(ENV_PREFIX.as_bytes()) {
+                continue;
+            }
+            let (Some(name), Some(raw)) = (name.to_str(), raw.to_str()) else {
+                anyhow::bail!(
+                    "Environment variable {} is not valid UTF-8",
+                    name.to_string_lossy()
+                );
+            };
+            if secret_key_var.as_deref() == Some(name) {
+                continue;
+            }
+            let mut key = name[ENV_PREFIX.len()..].to_lowercase();
+            if let Some((_, field)) = RENAMED_FIELDS.iter().find(|(old, _)| *old == key) {
+                key = field.to_string();
+            }
+            if !fields.contains(&key.as_str()) {
+                anyhow::bail!("Environment variable {name} doesn't match any of the config fields");
+            }
+            let raw = raw.to_string();
+            // string values are kept as is, unless set to a list or a table
+            let override_value = match (table.get(&key), parse_env_value(&raw)) {
+                (_, Some(value @ (toml::Value::Array(_) | toml::Value::Table(_)))) => value,
+                (Some(toml::Value::String(_)), _) | (_, None) => toml::Value::String(raw),
+                // fields unset by default, such as paths and the secret key, aren't serialized
+                (_, Some(value)) => value,
+            };
+            table.insert(key, override_value);
+            is_overridden = true;
+        }
+        if !is_overridden {
+            return Ok(self);
         }
+        value
+            .try_into()
+            .context("Failed to apply configuration from environment variables")
+    }
+}
+
+impl RuntimeConfig {
+    /// Whether the secret key is loaded from the identity file, rather than set in the config
+    pub fn uses_identity_file(&self) -> bool {
+        matches!(self.secret_key, Some(SecretKey::Identity(_)) | None)
+    }
+
+    /// Path of the identity file, kept in the state directory if `state_path` is set
+    pub fn identity_file(&self) -> String {
+        match &self.state_path {
+            Some(state_path) => state::identity_path(state_path),
+            None => self.identity_path.clone(),
+        }
+    }
+
+    /// Format of the logs, with the former `log_format_json` flag taking precedence over the default one
+    pub fn log_format(&self) -> LogFormat {
+        match self.log_format_json {
+            true => LogFormat::Json,
+            false => self.log_format,
+        }
+    }
+
+    /// Applies the network preset: its genesis hash, bootstrap nodes next to the configured ones, and recommended settings
+    pub fn apply_network(&mut self, network: Network) {
+        self.genesis_hash = network.genesis_hash().to_string();
+        for addr in network.bootstraps() {
+            if !self.bootstraps.iter().any(|bootstrap| bootstrap == addr) {
+                self.bootstraps.push(addr.to_string());
+            }
+        }
+        if network == Network::Local {
+            // local setups run on private addresses, which are otherwise rejected by the AutoNAT server
+            self.autonat_only_global_ips = false;
+        }
+    }
+
+    /// Prologue of the Noise handshakes, derived from the genesis hash if `noise_genesis_prologue` is set,
+    /// and empty otherwise or if connecting to any network
+    pub fn noise_prologue(&self) -> Vec<u8> {
+        if !self.noise_genesis_prologue || self.genesis_hash.starts_with("DEV") {
+            return vec![];
+        }
+        let genesis_hash = self.genesis_hash.trim_start_matches("0x").to_lowercase();
+        format!("/avail/noise/{genesis_hash}").into_bytes()
+    }
+
+    /// Multiaddresses for the P2P network service to listen on,
+    /// all IPv4 interfaces on `tcp_port`, `quic_port` if QUIC is enabled and `wss_port` if WSS is enabled, if not set
+    pub fn listen_multiaddrs(&self) -> anyhow::Result<Vec<Multiaddr>> {
+        if self.listen_multiaddrs.is_empty() {
+            let mut tcp_multiaddress = Multiaddr::empty()
+                .with(Protocol::from(Ipv4Addr::UNSPECIFIED))
+                .with(Protocol::Tcp(self.tcp_port));
+            if self.ws_transport_enable {
+                tcp_multiaddress = tcp_multiaddress.with(Protocol::Ws(std::borrow::Cow::Borrowed(
+                    "avail-light-bootstrap",
+                )));
+            }
+            let mut multiaddrs = vec![tcp_multiaddress];
+            if self.quic_enable {
+                multiaddrs.push(
+                    Multiaddr::empty()
+                        .with(Protocol::from(Ipv4Addr::UNSPECIFIED))
+                        .with(Protocol::Udp(self.quic_port))
+                        .with(Protocol::QuicV1),
+                );
+            }
+            if self.wss_enable {
+                multiaddrs.push(
+                    Multiaddr::empty()
+                        .with(Protocol::from(Ipv4Addr::UNSPECIFIED))
+                        .with(Protocol::Tcp(self.wss_port))
+                        .with(Protocol::Wss(std::borrow::Cow::Borrowed(
+                            "avail-light-bootstrap",
+                        ))),
+                );
+            }
+            return Ok(multiaddrs);
+        }
+        self.listen_multiaddrs
+            .iter()
+            .map(|addr| self.listen_multiaddr(addr))
+            .collect()
+    }
+
+    // Parses the listen multiaddress, checking it is supported by the configured transport
+    fn listen_multiaddr(&self, addr: &str) -> anyhow::Result<Multiaddr> {
+        let multiaddr: Multiaddr = addr
+            .parse()
+            .context(format!("`{addr}` is not a valid multiaddress"))?;
+        let mut protocols = multiaddr.iter();
+        if !matches!(
+            protocols.next(),
+            Some(
+                Protocol::Ip4(_)
+                    | Protocol::Ip6(_)
+                    | Protocol::Dns(_)
+                    | Protocol::Dns4(_)
+                    | Protocol::Dns6(_)
+            )
+        ) {
+            anyhow::bail!("`{addr}` must start with an IP address or a DNS name");
+        }
+        match protocols.next() {
+            Some(Protocol::Tcp(_)) if matches!(multiaddr.iter().last(), Some(Protocol::Wss(_))) => {
+                if !self.wss_enable {
+                    anyhow::bail!("`{addr}` is a WSS address, but WSS listener is disabled");
+                }
+                if !matches!(protocols.next(), Some(Protocol::Wss(_))) {
+                    anyhow::bail!("`{addr}` must end with /wss after the TCP port");
+                }
+            }
+            Some(Protocol::Tcp(_)) => {
+                let is_ws = matches!(protocols.next(), Some(Protocol::Ws(_)));
+                if is_ws != self.ws_transport_enable || protocols.next().is_some() {
+                    match self.ws_transport_enable {
+                        true => anyhow::bail!("`{addr}` must end with /ws, as WebSocket transport is enabled"),
+                        false => anyhow::bail!("`{addr}` must end with the TCP port, as WebSocket transport is disabled"),
+                    }
+                }
+            }
+            Some(Protocol::Udp(_)) if !self.quic_enable => {
+                anyhow::bail!("`{addr}` is a QUIC address, but QUIC transport is disabled")
+            }
+            Some(Protocol::Udp(_)) => {
+                if !matches!(protocols.next(), Some(Protocol::QuicV1)) || protocols.next().is_some()
+                {
+                    anyhow::bail!("`{addr}` must end with /quic-v1 after the UDP port");
+                }
+            }
+            _ => anyhow::bail!("`{addr}` must have a TCP or a UDP port after the address"),
+        }
+        Ok(multiaddr)
+    }
+
+    /// Checks the config values which would otherwise fail deep inside the network or telemetry stack,
+    /// returning all the violations at once, prefixed by the field names
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        let mut servers = vec![];
+        // HTTP server listens on the unix domain socket instead, if set
+        if self.http_server_unix_socket.is_none() {
+            servers.push((
+                "http_server_host",
+                "http_server_port",
+                &self.http_server_host,
+                self.http_server_port,
+            ));
+        }
+        if self.rpc_enable {
+            servers.push(("rpc_host", "rpc_port", &self.rpc_host, self.rpc_port));
+        }
+        if self.grpc_enable {
+            servers.push(("grpc_host", "grpc_port", &self.grpc_host, self.grpc_port));
+        }
+        for (i, (host_field, field, host, port)) in servers.iter().enumerate() {
+            if *port == 0 {
+                errors.push(format!("{field}: port must be in range 1-65535"));
+            }
+            let socket_addr: anyhow::Result<SocketAddr> = Addr {
+                host: host.to_string(),
+                port: *port,
+            }
+            .try_into();
+            if socket_addr.is_err() {
+                errors.push(format!("{host_field}: `{host}` is not a valid IP address"));
+            }
+            if let Some((_, other, ..)) = servers[..i]
+                .iter()
+                .find(|(_, _, other_host, other_port)| other_host == host && other_port == port)
+            {
+                errors.push(format!("{field}: port {port} is already used by {other}"));
+            }
+        }
+        for addr in &self.listen_multiaddrs {
+            if let Err(err) = self.listen_multiaddr(addr) {
+                errors.push(format!("listen_multiaddrs: {err}"));
+            }
+        }
+        if self.wss_enable {
+            if self.wss_port == 0 {
+                errors.push("wss_port: port must be in range 1-65535".to_string());
+            }
+            if self.listen_multiaddrs.is_empty() && self.wss_port == self.tcp_port {
+                errors.push(format!(
+                    "wss_port: port {} is already used by tcp_port",
+                    self.wss_port
+                ));
+            }
+            if self.wss_cert_path.is_some() != self.wss_key_path.is_some() {
+                errors.push(
+                    "wss_cert_path: must be set along with wss_key_path, or neither of them"
+                        .to_string(),
+                );
+            }
+        }
+
+        let backends = self.metrics_backend.enabled();
+        if backends.contains(&MetricsBackend::Statsd) && self.statsd_port == 0 {
+            errors.push("statsd_port: port must be in range 1-65535".to_string());
+        }
+
+        let is_otlp_enabled = backends.contains(&MetricsBackend::Otlp) || self.ot_logs_enable;
+        let endpoint = self.ot_collector_endpoint.trim();
+        if is_otlp_enabled && !endpoint.is_empty() {
+            match endpoint.parse::<warp::http::Uri>() {
+                Ok(uri) if !matches!(uri.scheme_str(), Some("http" | "https")) => {
+                    errors.push(format!(
+                        "ot_collector_endpoint: `{endpoint}` must start with http:// or https://"
+                    ))
+                }
+                Ok(uri) if uri.host().is_none() => errors.push(format!(
+                    "ot_collector_endpoint: `{endpoint}` is missing the host name"
+                )),
+                Ok(_) => {}
+                Err(err) => errors.push(format!(
+                    "ot_collector_endpoint: `{endpoint}` is not a valid URL: {err}"
+                )),
+            }
+        }
+
+        match &self.secret_key {
+            Some(SecretKey::Seed { seed }) if seed.is_empty() => {
+                errors.push("secret_key: seed must not be empty".to_string())
+            }
+            Some(SecretKey::Key { key }) if key.len() != 64 || hex::decode(key).is_err() => errors
+                .push("secret_key: key must be a 32 bytes ed25519 private key, hex encoded into 64 characters".to_string()),
+            Some(SecretKey::KeyFile { path }) if path.is_empty() => {
+                errors.push("secret_key: path must not be empty".to_string())
+            }
+            Some(SecretKey::Env { var }) if var.is_empty() => {
+                errors.push("secret_key: var must not be empty".to_string())
+            }
+            _ => {}
+        }
+
+        for name in &self.kad_extra_protocol_names {
+            if StreamProtocol::try_from_owned(name.clone()).is_err() {
+                errors.push(format!(
+                    "kad_extra_protocol_names: `{name}` must start with `/`"
+                ));
+            }
+        }
+
+        for addr in &self.bootstraps {
+            if addr.parse::<Multiaddr>().is_err() {
+                errors.push(format!("bootstraps: `{addr}` is not a valid multiaddress"));
+            }
+        }
+
+        let genesis_hash = self.genesis_hash.trim_start_matches("0x");
+        let is_hex_hash = genesis_hash.len() == 64 && hex::decode(genesis_hash).is_ok();
+        if !self.genesis_hash.starts_with("DEV") && !is_hex_hash {
+            errors.push(format!(
+                "genesis_hash: `{}` must be a hex encoded 32 bytes hash, or start with `DEV` to connect to any network",
+                self.genesis_hash
+            ));
+        }
+
+        let non_zero = [
+            ("health_check_timeout", self.health_check_timeout),
+            ("connection_idle_timeout", self.connection_idle_timeout),
+            (
+                "connection_max_substreams",
+                self.connection_max_substreams as u64,
+            ),
+            (
+                "autonat_throttle_clients_period",
+                self.autonat_throttle_clients_period.into(),
+            ),
+            ("autonat_refresh_interval", self.autonat_refresh_interval),
+            ("autonat_retry_interval", self.autonat_retry_interval),
+            ("kad_query_timeout", self.kad_query_timeout.into()),
+            ("kad_query_parallelism", self.kad_query_parallelism as u64),
+            ("kad_replication_factor", self.kad_replication_factor as u64),
+            ("bootstrap_period", self.bootstrap_period),
+            ("bootstrap_cold_period", self.bootstrap_cold_period),
+            (
+                "random_walk_concurrency",
+                self.random_walk_concurrency as u64,
+            ),
+            ("event_loop_restart_window", self.event_loop_restart_window),
+            ("stall_command_timeout", self.stall_command_timeout),
+            ("stall_swarm_event_timeout", self.stall_swarm_event_timeout),
+            (
+                "maintenance_stale_peer_timeout",
+                self.maintenance_stale_peer_timeout,
+            ),
+            ("client_command_timeout", self.client_command_timeout),
+            ("client_query_timeout", self.client_query_timeout),
+            (
+                "client_command_channel_capacity",
+                self.client_command_channel_capacity as u64,
+            ),
+            (
+                "metrics_network_dump_interval",
+                self.metrics_network_dump_interval,
+            ),
+            ("ot_export_buffer_size", self.ot_export_buffer_size as u64),
+        ];
+        for (field, value) in non_zero {
+            if value == 0 {
+                errors.push(format!("{field}: must be greater than 0"));
+            }
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        anyhow::bail!("Invalid configuration:\n  {}", errors.join("\n  "))
+    }
+}
+
+// README holds the config reference, with all the config values commented
+const README: &str = include_str!("../README.md");
+
+// Returns the lines of the README config reference
+fn config_reference() -> impl Iterator<Item = &'static str> {
+    README
+        .lines()
+        .skip_while(|line| !line.starts_with("## Config reference"))
+        .skip_while(|line| !line.starts_with("```"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("```"))
+}
+
+// Renders the value in the inline form, so tables can be placed in between other values
+fn inline_toml(value: &toml::Value) -> String {
+    match value {
+        toml::Value::Table(table) if table.is_empty() => "{}".to_string(),
+        toml::Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{key} = {}", inline_toml(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        toml::Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(inline_toml).collect();
+            format!("[{}]", values.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Renders the default config, commented the same way as in the README config reference.
+/// Values unset by default are included commented out, with an example value.
+pub fn default_config() -> anyhow::Result<String> {
+    let defaults = RuntimeConfig::default();
+    // unlike TOML, JSON keeps the fields unset by default, as nulls
+    let serde_json::Value::Object(fields) = serde_json::to_value(&defaults)? else {
+        anyhow::bail!("Configuration is expected to be an object");
+    };
+    let toml::Value::Table(values) = toml::Value::try_from(&defaults)? else {
+        anyhow::bail!("Configuration is expected to be a table");
+    };
+
+    let mut config = String::new();
+    let mut rendered = HashSet::new();
+    let mut comments = vec![];
+    for line in config_reference() {
+        let key = line
+            .trim_start_matches('#')
+            .trim_start()
+            .split_once(" =")
+            .map(|(key, _)| key)
+            .filter(|key| fields.contains_key(*key));
+        let Some(key) = key else {
+            comments.push(line);
+            continue;
+        };
+        for comment in comments.drain(..) {
+            config.push_str(comment);
+            config.push('\n');
+        }
+        match values.get(key) {
+            Some(value) => config.push_str(&format!("{key} = {}\n", inline_toml(value))),
+            None => {
+                config.push_str(line);
+                config.push('\n');
+            }
+        }
+        rendered.insert(key);
+    }
+    // values missing from the reference are added without comments
+    for (key, value) in &values {
+        if !rendered.contains(key.as_str()) {
+            config.push_str(&format!("{key} = {}\n", inline_toml(value)));
+        }
+    }
+    Ok(config)
+}
+
+fn parse_env_value(raw: &str) -> Option<toml::Value> {
+    let mut table: toml::value::Table = toml::from_str(&format!("value = {raw}")).ok()?;
+    table.remove("value")
+}
+
+// Names of the config fields, as listed by the derived deserializer
+fn config_fields() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    _ = RuntimeConfig::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+// Deserializer capturing the field names of the deserialized struct, failing right after
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> serde::Deserializer<'de> for FieldNames<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: serde::de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(serde::de::Error::custom("field names captured"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
     }
 }
 
@@ -192,12 +1267,53 @@ impl Display for Addr {
     }
 }
 
+/// Health check configuration (see [RuntimeConfig] for details)
+#[derive(Clone)]
+pub struct HealthCheckConfig {
+    pub min_dht_peers: usize,
+    pub timeout: Duration,
+}
+
+impl From<&RuntimeConfig> for HealthCheckConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        HealthCheckConfig {
+            min_dht_peers: val.health_min_dht_peers,
+            timeout: Duration::from_secs(val.health_check_timeout),
+        }
+    }
+}
+
+/// StatsD metrics backend configuration (see [RuntimeConfig] for details)
+pub struct StatsdConfig {
+    pub host: String,
+    pub port: u16,
+    pub prefix: String,
+    pub tags_enabled: bool,
+}
+
+impl From<&RuntimeConfig> for StatsdConfig {
+    fn from(val: &RuntimeConfig) -> Self {
+        StatsdConfig {
+            host: val.statsd_host.clone(),
+            port: val.statsd_port,
+            prefix: val.statsd_prefix.clone(),
+            tags_enabled: val.statsd_tags_enable,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct IdentifyConfig {
     pub agent_version: AgentVersion,
     /// Contains Avail genesis hash
     pub protocol_version: String,
+    /// Reported listen addresses are all re-added once it passes, only the new ones are added in between
+    pub address_refresh_interval: Duration,
+    /// Observed addresses of the inbound QUIC connections are added along with the private listen addresses of the same port
+    pub quic_address_translation: bool,
 }
 
+#[derive(Clone)]
 pub struct AgentVersion {
     pub base_version: String,
     pub role: String,
@@ -233,7 +1349,32 @@ impl FromStr for AgentVersion {
     }
 }
 
+// Replaces characters which are not expected in agent version parts, limiting their length
+fn sanitize_label_part(part: &str) -> String {
+    part.chars()
+        .take(32)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
 impl AgentVersion {
+    /// Returns a metric label in `role/client_type/release_version` format,
+    /// if the release version is a valid semantic version
+    pub fn metric_label(&self) -> Option<String> {
+        let release_version = Version::parse(&self.release_version).ok()?;
+        Some(format!(
+            "{}/{}/{}.{}.{}",
+            sanitize_label_part(&self.role),
+            sanitize_label_part(&self.client_type),
+            release_version.major,
+            release_version.minor,
+            release_version.patch
+        ))
+    }
+
     pub fn is_supported(&self) -> bool {
         let minimum_version = if self.role == "bootstrap" {
             MINIMUM_SUPPORTED_BOOTSTRAP_VERSION
@@ -249,8 +1390,8 @@ impl AgentVersion {
     }
 }
 
-impl IdentifyConfig {
-    fn new() -> Self {
+impl From<&RuntimeConfig> for IdentifyConfig {
+    fn from(val: &RuntimeConfig) -> Self {
         let agent_version = AgentVersion {
             base_version: IDENTITY_AGENT_BASE.to_string(),
             role: IDENTITY_AGENT_ROLE.to_string(),
@@ -261,18 +1402,168 @@ impl IdentifyConfig {
         Self {
             agent_version,
             protocol_version: IDENTITY_PROTOCOL.to_owned(),
+            address_refresh_interval: Duration::from_secs(val.identify_address_refresh_interval),
+            quic_address_translation: val.identify_quic_address_translation,
+        }
+    }
+}
+
+/// Known Avail networks, selected by the `--network` option
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Network {
+    Turing,
+    Mainnet,
+    Hex,
+    Local,
+}
+
+impl Network {
+    const ALL: [Network; 4] = [
+        Network::Turing,
+        Network::Mainnet,
+        Network::Hex,
+        Network::Local,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Turing => "turing",
+            Network::Mainnet => "mainnet",
+            Network::Hex => "hex",
+            Network::Local => "local",
+        }
+    }
+
+    pub fn genesis_hash(&self) -> &'static str {
+        match self {
+            Network::Turing => "d3d2f3a3495dc597434a99d7d449ebad6616db45e4e4f178f31cc6fa14378b70",
+            Network::Mainnet => "b91746b45e0346cc2f815a520b9c6cb4d5c0902af848db0a80f85932d2e8276a",
+            Network::Hex => "9d5ea6a5d7631e13028b684a1a0078e3970caa78bd677eaecaf2160304f174fb",
+            Network::Local => "DEV",
+        }
+    }
+
+    /// Bootstrap nodes run for the network, with peer IDs learned once connected
+    pub fn bootstraps(&self) -> &'static [&'static str] {
+        match self {
+            Network::Turing => &["/dns/bootnode.1.lightclient.turing.avail.so/tcp/37000"],
+            Network::Mainnet => &["/dns/bootnode.1.lightclient.mainnet.avail.so/tcp/37000"],
+            Network::Hex => &["/dns/bootnode.1.lightclient.hex.avail.so/tcp/37000"],
+            Network::Local => &[],
         }
     }
+
+    fn from_genesis_hash(genesis_hash: &str) -> Option<Network> {
+        Network::ALL
+            .into_iter()
+            .find(|network| network.genesis_hash() == genesis_hash)
+    }
 }
 
 pub fn network_name(genesis_hash: &str) -> String {
-    let network = match genesis_hash {
-        "9d5ea6a5d7631e13028b684a1a0078e3970caa78bd677eaecaf2160304f174fb" => "hex".to_string(),
-        "d3d2f3a3495dc597434a99d7d449ebad6616db45e4e4f178f31cc6fa14378b70" => "turing".to_string(),
-        "DEV" => "local".to_string(),
-        _ => "other".to_string(),
-    };
+    let network =
+        Network::from_genesis_hash(genesis_hash).map_or("other", |network| network.name());
 
     let prefix = &genesis_hash[..std::cmp::min(6, genesis_hash.len())];
     format!("{}:{}", network, prefix)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (OsString, OsString)> {
+        vars.iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect::<Vec<(OsString, OsString)>>()
+            .into_iter()
+    }
+
+    fn with_env_overrides(vars: &[(&str, &str)]) -> anyhow::Result<RuntimeConfig> {
+        RuntimeConfig::default().with_env_overrides(env_vars(vars))
+    }
+
+    #[test]
+    fn env_overrides_keep_strings_as_is() {
+        let cfg = with_env_overrides(&[
+            ("AVAIL_BOOTSTRAP_HTTP_SERVER_HOST", "0.0.0.0"),
+            ("AVAIL_BOOTSTRAP_LOG_LEVEL", "debug"),
+            ("AVAIL_BOOTSTRAP_GENESIS_HASH", "1234"),
+        ])
+        .unwrap();
+        assert_eq!(cfg.http_server_host, "0.0.0.0");
+        assert_eq!(cfg.log_level, "debug");
+        assert_eq!(cfg.genesis_hash, "1234");
+    }
+
+    #[test]
+    fn env_overrides_parse_numbers_and_lists() {
+        let cfg = with_env_overrides(&[
+            ("AVAIL_BOOTSTRAP_HTTP_SERVER_PORT", "7710"),
+            ("AVAIL_BOOTSTRAP_RPC_ENABLE", "true"),
+            (
+                "AVAIL_BOOTSTRAP_METRICS_BACKEND",
+                r#"["otlp", "prometheus"]"#,
+            ),
+        ])
+        .unwrap();
+        assert_eq!(cfg.http_server_port, 7710);
+        assert!(cfg.rpc_enable);
+        assert_eq!(
+            cfg.metrics_backend.enabled(),
+            vec![MetricsBackend::Otlp, MetricsBackend::Prometheus]
+        );
+    }
+
+    #[test]
+    fn env_overrides_parse_inline_tables() {
+        let key = "0".repeat(64);
+        let cfg = with_env_overrides(&[(
+            "AVAIL_BOOTSTRAP_SECRET_KEY",
+            &format!(r#"{{ key = "{key}" }}"#),
+        )])
+        .unwrap();
+        assert!(matches!(cfg.secret_key, Some(SecretKey::Key { key: value }) if value == key));
+    }
+
+    #[test]
+    fn env_overrides_set_renamed_port() {
+        let cfg = with_env_overrides(&[("AVAIL_BOOTSTRAP_PORT", "39001")]).unwrap();
+        assert_eq!(cfg.tcp_port, 39001);
+    }
+
+    #[test]
+    fn env_overrides_skip_other_variables() {
+        let cfg = with_env_overrides(&[("HTTP_SERVER_PORT", "7710")]).unwrap();
+        assert_eq!(
+            cfg.http_server_port,
+            RuntimeConfig::default().http_server_port
+        );
+    }
+
+    #[test]
+    fn env_overrides_reject_unknown_fields() {
+        let err =
+            with_env_overrides(&[("AVAIL_BOOTSTRAP_SECRET_KY", "{ seed = \"2\" }")]).unwrap_err();
+        assert!(err.to_string().contains("AVAIL_BOOTSTRAP_SECRET_KY"));
+    }
+
+    #[test]
+    fn env_overrides_skip_secret_key_variable() {
+        let cfg = RuntimeConfig {
+            secret_key: Some(SecretKey::Env {
+                var: "AVAIL_BOOTSTRAP_NODE_KEY".to_string(),
+            }),
+            ..Default::default()
+        };
+        let cfg = cfg
+            .with_env_overrides(env_vars(&[(
+                "AVAIL_BOOTSTRAP_NODE_KEY",
+                "0".repeat(64).as_str(),
+            )]))
+            .unwrap();
+        assert!(
+            matches!(cfg.secret_key, Some(SecretKey::Env { var }) if var == "AVAIL_BOOTSTRAP_NODE_KEY")
+        );
+    }
+}